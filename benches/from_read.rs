@@ -0,0 +1,32 @@
+use std::io::Cursor;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+fn bench_file<F>(c: &mut Criterion, name: &str, path: &str, measure: F)
+where
+	F: Fn(&mut Cursor<&Vec<u8>>) -> Result<std::time::Duration, mp3_duration::MP3DurationError>,
+{
+	let data = std::fs::read(path).unwrap();
+	let mut group = c.benchmark_group("from_read");
+	group.throughput(Throughput::Bytes(data.len() as u64));
+	group.bench_function(name, |b| b.iter(|| measure(&mut Cursor::new(&data)).unwrap()));
+	group.finish();
+}
+
+// VBR file with a Xing header, whose duration is known after reading its first frame.
+fn xing_header(c: &mut Criterion) {
+	bench_file(c, "xing header", "test/VBR0.mp3", |reader| mp3_duration::from_read(reader));
+}
+
+// CBR file without Xing/Info header, which requires scanning every frame.
+fn cbr_scan(c: &mut Criterion) {
+	bench_file(c, "cbr scan", "test/ID3v1.mp3", |reader| mp3_duration::from_read(reader));
+}
+
+// VBR file scanned without trusting its Xing header.
+fn vbr_scan(c: &mut Criterion) {
+	bench_file(c, "vbr scan", "test/VBR0.mp3", |reader| mp3_duration::from_read_multistream(reader));
+}
+
+criterion_group!(benches, xing_header, cbr_scan, vbr_scan);
+criterion_main!(benches);
@@ -0,0 +1,204 @@
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::constants::Mode;
+use crate::context::Context;
+use crate::error::MP3DurationError;
+use crate::options::ParseOptions;
+use crate::parser::{Measured, Parser, Progress};
+use crate::source::ByteSource;
+use crate::warning::Warning;
+
+/// Duration of a mp3 file along with a summary of how cleanly it could be parsed.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Analysis {
+	/// Sum of the durations of all audio frames.
+	pub duration: Duration,
+	/// Number of audio frames (excluding Xing/Info frames).
+	pub frames: usize,
+	/// Number of bytes which were neither audio frames nor tags, and had to be skipped to find the next frame.
+	pub resynced_bytes: u64,
+	/// Number of sync words followed by an invalid frame header.
+	pub header_anomalies: usize,
+	/// Longest sequence of consecutive frames where each frame starts exactly where the previous one ended.
+	pub longest_valid_run_frames: usize,
+	/// Whether the file ends exactly after its last frame or tag, as opposed to being cut mid-frame.
+	pub clean_eof: bool,
+	/// Total number of bytes read.
+	pub total_bytes: u64,
+	/// Number of audio frames using each channel mode, indexed by `Mode as usize` (see `frames_with_mode`).
+	pub mode_histogram: [usize; 4],
+	/// Number of audio frames whose audio data begins in previous frames (bit reservoir).
+	pub bit_reservoir_frames: usize,
+	/// Runs of bytes which had to be skipped to find the next frame, in the order they were found.
+	pub gaps: Vec<Gap>,
+	/// Anomalies which were worked around.
+	pub warnings: Vec<Warning>,
+}
+
+/// Run of bytes between the end of a frame or tag and the start of the next frame (or the end of the stream) which
+/// could not be parsed, such as a dropout in a recorded stream.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Gap {
+	/// Position of the first byte of the gap in the stream.
+	pub offset: u64,
+	/// Length of the gap in bytes, including any 0x00 bytes within it.
+	pub bytes: u64,
+}
+
+impl Analysis {
+	/// A rough measure of file health between 0 (unusable) and 1 (no anomalies found).
+	///
+	/// Files without any audio frame score 0. Otherwise, the score is the proportion of bytes which did not need to be
+	/// skipped over, reduced by 1% per invalid header (capped to 50%) and halved if the file is truncated.
+	pub fn integrity_score(&self) -> f64 {
+		if self.frames == 0 || self.total_bytes == 0 {
+			return 0.0;
+		}
+		let mut score = 1.0 - self.resynced_bytes as f64 / self.total_bytes as f64;
+		score *= 1.0 - (self.header_anomalies as f64 * 0.01).min(0.5);
+		if !self.clean_eof {
+			score *= 0.5;
+		}
+		score
+	}
+
+	/// Number of audio frames using the given channel mode.
+	pub fn frames_with_mode(&self, mode: Mode) -> usize {
+		self.mode_histogram[mode as usize]
+	}
+}
+
+/// Scans an entire mp3 file contained in any struct implementing Read and reports its duration along with parsing
+/// anomalies.
+///
+/// Parsing is lenient: invalid data is skipped over and a truncated last frame is reported through `clean_eof`
+/// rather than as an error. Xing/Info headers are not trusted, every frame is measured.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use std::fs::File;
+/// use std::io::BufReader;
+/// use mp3_duration;
+///
+/// let path = Path::new("test/source.mp3");
+/// let file = File::open(path).unwrap();
+/// let mut reader = BufReader::new(file);
+/// let analysis = mp3_duration::analyze_from_read(&mut reader).unwrap();
+/// println!("Integrity score: {}", analysis.integrity_score());
+/// ```
+pub fn analyze_from_read<T>(reader: &mut T) -> Result<Analysis, MP3DurationError>
+where
+	T: Read,
+{
+	let options = ParseOptions {
+		lenient: true,
+		trust_first_xing: false,
+		..Default::default()
+	};
+	let mut parser = Parser::new(Context::new(reader), &options);
+	let mut analysis = Analysis {
+		clean_eof: true,
+		..Default::default()
+	};
+
+	let mut current_run = 0;
+	let mut expected_offset = None;
+	// End of the last frame or tag, and number of bytes skipped until then
+	let mut block_end = 0;
+	let mut resynced_bytes = 0;
+
+	loop {
+		let progress = parser.measure_next_block_with(|parser, block| {
+			let start = match &block {
+				Measured::Frame(measured) => measured.frame.offset,
+				Measured::Tag(offset) => *offset,
+			};
+			if parser.state.resynced_bytes > resynced_bytes {
+				analysis.gaps.push(Gap {
+					offset: block_end,
+					bytes: start - block_end,
+				});
+				resynced_bytes = parser.state.resynced_bytes;
+			}
+			let measured = match block {
+				Measured::Frame(measured) => measured,
+				Measured::Tag(_) => {
+					block_end = parser.source.bytes_read();
+					return;
+				}
+			};
+			if !measured.complete {
+				analysis.clean_eof = false;
+				return;
+			}
+			let frame = measured.frame;
+			if expected_offset == Some(frame.offset) {
+				current_run += 1;
+			} else {
+				current_run = 1;
+			}
+			analysis.longest_valid_run_frames = analysis.longest_valid_run_frames.max(current_run);
+			expected_offset = Some(frame.offset + frame.header.frame_length() as u64);
+			block_end = parser.source.bytes_read();
+			if measured.is_audio {
+				analysis.frames += 1;
+				analysis.mode_histogram[frame.header.mode as usize] += 1;
+				if frame.main_data_begin != 0 {
+					analysis.bit_reservoir_frames += 1;
+				}
+			}
+		});
+		match progress {
+			Ok(Progress::Continue) => (),
+			Ok(Progress::Finished(_)) => {
+				// Data skipped until the end of the stream
+				if parser.state.resynced_bytes > resynced_bytes {
+					analysis.gaps.push(Gap {
+						offset: block_end,
+						bytes: parser.source.bytes_read() - block_end,
+					});
+				}
+				break;
+			}
+			Err(_) if parser.source.reached_eof() => {
+				analysis.clean_eof = false;
+				break;
+			}
+			Err(e) => return Err(e),
+		}
+	}
+
+	analysis.duration = parser.state.duration();
+	analysis.resynced_bytes = parser.state.resynced_bytes;
+	analysis.header_anomalies = parser.state.invalid_headers;
+	analysis.total_bytes = parser.source.bytes_read();
+	analysis.warnings = parser.state.warnings;
+	Ok(analysis)
+}
+
+/// Scans an entire mp3 file and reports its duration along with parsing anomalies.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use mp3_duration;
+///
+/// let path = Path::new("test/source.mp3");
+/// let analysis = mp3_duration::analyze_from_path(&path).unwrap();
+/// println!("File duration: {:?}", analysis.duration);
+/// ```
+pub fn analyze_from_path<P>(path: P) -> Result<Analysis, MP3DurationError>
+where
+	P: AsRef<Path>,
+{
+	File::open(path)
+		.map_err(|e| MP3DurationError::at_start(e.into()))
+		.and_then(|file| analyze_from_read(&mut BufReader::new(file)))
+}
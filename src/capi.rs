@@ -0,0 +1,124 @@
+//! C-compatible interface, available with the `capi` feature.
+//!
+//! To use it from C, build the crate as a C library (e.g. `cargo rustc --release --features capi --crate-type
+//! cdylib`) and generate a header with [cbindgen](https://github.com/eqrion/cbindgen):
+//!
+//! ```sh
+//! cbindgen --lang c --crate mp3-duration --output mp3_duration.h
+//! ```
+//!
+//! ```c
+//! double seconds;
+//! if (mp3_duration_from_path("music.mp3", &seconds) != MP3_DURATION_OK) {
+//!     fprintf(stderr, "%s\n", mp3_duration_last_error());
+//! }
+//! ```
+
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+
+use crate::error::{ErrorKind, MP3DurationError};
+
+pub const MP3_DURATION_OK: c_int = 0;
+pub const MP3_DURATION_ERROR_FORBIDDEN_VERSION: c_int = 1;
+pub const MP3_DURATION_ERROR_FORBIDDEN_LAYER: c_int = 2;
+pub const MP3_DURATION_ERROR_INVALID_BITRATE: c_int = 3;
+pub const MP3_DURATION_ERROR_INVALID_SAMPLING_RATE: c_int = 4;
+pub const MP3_DURATION_ERROR_NO_XING_HEADER: c_int = 5;
+pub const MP3_DURATION_ERROR_RESERVED_EMPHASIS: c_int = 6;
+pub const MP3_DURATION_ERROR_UNEXPECTED_FRAME: c_int = 7;
+pub const MP3_DURATION_ERROR_TAG_TOO_LARGE: c_int = 8;
+pub const MP3_DURATION_ERROR_UNEXPECTED_EOF: c_int = 9;
+pub const MP3_DURATION_ERROR_MPEG_FRAME_TOO_SHORT: c_int = 10;
+pub const MP3_DURATION_ERROR_TIMEOUT: c_int = 11;
+pub const MP3_DURATION_ERROR_IO: c_int = 12;
+pub const MP3_DURATION_ERROR_BAD_BITRATE_INDEX: c_int = 13;
+pub const MP3_DURATION_ERROR_NO_AUDIO_FRAMES: c_int = 14;
+pub const MP3_DURATION_ERROR_UNEXPECTED_TAG: c_int = 15;
+pub const MP3_DURATION_ERROR_FREE_FORMAT_UNSUPPORTED: c_int = 16;
+pub const MP3_DURATION_ERROR_CRC_MISMATCH: c_int = 17;
+pub const MP3_DURATION_ERROR_FRAME_LIMIT_EXCEEDED: c_int = 18;
+/// A pointer argument was null, or the path was not valid UTF-8.
+pub const MP3_DURATION_ERROR_INVALID_ARGUMENT: c_int = -1;
+
+thread_local! {
+	static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn error_code(kind: &ErrorKind) -> c_int {
+	match kind {
+		ErrorKind::ForbiddenVersion => MP3_DURATION_ERROR_FORBIDDEN_VERSION,
+		ErrorKind::ForbiddenLayer => MP3_DURATION_ERROR_FORBIDDEN_LAYER,
+		ErrorKind::InvalidBitrate { .. } => MP3_DURATION_ERROR_INVALID_BITRATE,
+		ErrorKind::BadBitrateIndex => MP3_DURATION_ERROR_BAD_BITRATE_INDEX,
+		ErrorKind::InvalidSamplingRate { .. } => MP3_DURATION_ERROR_INVALID_SAMPLING_RATE,
+		ErrorKind::NoXingHeader => MP3_DURATION_ERROR_NO_XING_HEADER,
+		ErrorKind::ReservedEmphasis => MP3_DURATION_ERROR_RESERVED_EMPHASIS,
+		ErrorKind::UnexpectedFrame { .. } => MP3_DURATION_ERROR_UNEXPECTED_FRAME,
+		ErrorKind::TagTooLarge { .. } => MP3_DURATION_ERROR_TAG_TOO_LARGE,
+		ErrorKind::UnexpectedEOF => MP3_DURATION_ERROR_UNEXPECTED_EOF,
+		ErrorKind::MPEGFrameTooShort => MP3_DURATION_ERROR_MPEG_FRAME_TOO_SHORT,
+		ErrorKind::Timeout => MP3_DURATION_ERROR_TIMEOUT,
+		ErrorKind::NoAudioFrames => MP3_DURATION_ERROR_NO_AUDIO_FRAMES,
+		ErrorKind::UnexpectedTag { .. } => MP3_DURATION_ERROR_UNEXPECTED_TAG,
+		ErrorKind::FreeFormatUnsupported => MP3_DURATION_ERROR_FREE_FORMAT_UNSUPPORTED,
+		ErrorKind::CrcMismatch => MP3_DURATION_ERROR_CRC_MISMATCH,
+		ErrorKind::FrameLimitExceeded { .. } => MP3_DURATION_ERROR_FRAME_LIMIT_EXCEEDED,
+		ErrorKind::IOError(_) => MP3_DURATION_ERROR_IO,
+	}
+}
+
+fn set_last_error(message: String) {
+	// Messages never contain NUL bytes, except maybe within IO error descriptions
+	let message = CString::new(message.replace('\0', "")).unwrap_or_default();
+	LAST_ERROR.with(|last_error| *last_error.borrow_mut() = Some(message));
+}
+
+fn fail(error: MP3DurationError) -> c_int {
+	let code = error_code(&error.kind);
+	set_last_error(error.to_string());
+	code
+}
+
+/// Measures the duration of a file, in seconds.
+///
+/// Returns `MP3_DURATION_OK` on success, or one of the `MP3_DURATION_ERROR_*` codes. On failure, `out_seconds` is
+/// left untouched and `mp3_duration_last_error` describes the error.
+///
+/// # Safety
+///
+/// `path` must be a NUL-terminated string and `out_seconds` must point to writable memory.
+#[no_mangle]
+pub unsafe extern "C" fn mp3_duration_from_path(path: *const c_char, out_seconds: *mut f64) -> c_int {
+	if path.is_null() || out_seconds.is_null() {
+		set_last_error("Null argument".to_owned());
+		return MP3_DURATION_ERROR_INVALID_ARGUMENT;
+	}
+	let path = match CStr::from_ptr(path).to_str() {
+		Ok(path) => path,
+		Err(e) => {
+			set_last_error(format!("Invalid path: {}", e));
+			return MP3_DURATION_ERROR_INVALID_ARGUMENT;
+		}
+	};
+	match crate::from_path(path) {
+		Ok(duration) => {
+			*out_seconds = duration.as_secs_f64();
+			MP3_DURATION_OK
+		}
+		Err(e) => fail(e),
+	}
+}
+
+/// Describes the last error which occurred on the calling thread, or returns null if there was none.
+///
+/// The string remains valid until the next failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn mp3_duration_last_error() -> *const c_char {
+	LAST_ERROR.with(|last_error| match &*last_error.borrow() {
+		Some(message) => message.as_ptr(),
+		None => ptr::null(),
+	})
+}
@@ -1,11 +1,17 @@
-#[derive(Clone, Copy, Debug)]
+use std::convert::TryFrom;
+
+/// MPEG audio version.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Version {
 	Mpeg1,
 	Mpeg2,
 	Mpeg25,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+/// MPEG audio layer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Layer {
 	NotDefined,
 	Layer1,
@@ -13,7 +19,29 @@ pub enum Layer {
 	Layer3,
 }
 
-#[derive(Clone, Copy, Debug)]
+/// Name of an MPEG audio version as used by the specification ("MPEG-1", "MPEG-2" or "MPEG-2.5").
+pub fn version_name(version: Version) -> &'static str {
+	match version {
+		Version::Mpeg1 => "MPEG-1",
+		Version::Mpeg2 => "MPEG-2",
+		Version::Mpeg25 => "MPEG-2.5",
+	}
+}
+
+/// Name of an MPEG audio layer as used by the specification ("Layer I", "Layer II" or "Layer III"). The reserved
+/// layer value is named "Reserved".
+pub fn layer_name(layer: Layer) -> &'static str {
+	match layer {
+		Layer::NotDefined => "Reserved",
+		Layer::Layer1 => "Layer I",
+		Layer::Layer2 => "Layer II",
+		Layer::Layer3 => "Layer III",
+	}
+}
+
+/// Channel mode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Mode {
 	Stereo,
 	JointStereo,
@@ -21,6 +49,71 @@ pub enum Mode {
 	Mono,
 }
 
+/// De-emphasis to apply on playback.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Emphasis {
+	None,
+	/// 50/15 microseconds
+	Ms50_15,
+	/// Value reserved by the specification
+	Reserved,
+	/// CCITT J.17
+	CcittJ17,
+}
+
+/// Sampling rates supported by MPEG audio.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SampleRate {
+	Hz8000,
+	Hz11025,
+	Hz12000,
+	Hz16000,
+	Hz22050,
+	Hz24000,
+	Hz32000,
+	Hz44100,
+	Hz48000,
+}
+
+impl SampleRate {
+	/// Sampling rate in Hz.
+	pub fn hz(&self) -> u32 {
+		match self {
+			SampleRate::Hz8000 => 8000,
+			SampleRate::Hz11025 => 11025,
+			SampleRate::Hz12000 => 12000,
+			SampleRate::Hz16000 => 16000,
+			SampleRate::Hz22050 => 22050,
+			SampleRate::Hz24000 => 24000,
+			SampleRate::Hz32000 => 32000,
+			SampleRate::Hz44100 => 44100,
+			SampleRate::Hz48000 => 48000,
+		}
+	}
+}
+
+impl TryFrom<u32> for SampleRate {
+	type Error = u32;
+
+	/// Fails with the original value if it is not a sampling rate supported by MPEG audio.
+	fn try_from(hz: u32) -> Result<Self, Self::Error> {
+		match hz {
+			8000 => Ok(SampleRate::Hz8000),
+			11025 => Ok(SampleRate::Hz11025),
+			12000 => Ok(SampleRate::Hz12000),
+			16000 => Ok(SampleRate::Hz16000),
+			22050 => Ok(SampleRate::Hz22050),
+			24000 => Ok(SampleRate::Hz24000),
+			32000 => Ok(SampleRate::Hz32000),
+			44100 => Ok(SampleRate::Hz44100),
+			48000 => Ok(SampleRate::Hz48000),
+			_ => Err(hz),
+		}
+	}
+}
+
 pub static BIT_RATES: [[[u32; 16]; 4]; 3] = [
 	[
 		[0; 16],
@@ -69,10 +162,10 @@ pub static BIT_RATES: [[[u32; 16]; 4]; 3] = [
 	],
 ];
 
-pub static SAMPLING_RATES: [[u32; 4]; 3] = [
-	[44100, 48000, 32000, 0], // Mpeg1
-	[22050, 24000, 16000, 0], // Mpeg2
-	[11025, 12000, 8000, 0],  // Mpeg25
+pub static SAMPLING_RATES: [[SampleRate; 3]; 3] = [
+	[SampleRate::Hz44100, SampleRate::Hz48000, SampleRate::Hz32000], // Mpeg1
+	[SampleRate::Hz22050, SampleRate::Hz24000, SampleRate::Hz16000], // Mpeg2
+	[SampleRate::Hz11025, SampleRate::Hz12000, SampleRate::Hz8000],  // Mpeg25
 ];
 
 pub static SAMPLES_PER_FRAME: [[u32; 4]; 3] = [
@@ -1,62 +1,418 @@
 use std::io;
 use std::io::prelude::*;
-use std::time::Duration;
 
 use crate::error::*;
+use crate::source::{sealed, ByteSource, Source};
 
+// Number of consecutive `io::ErrorKind::WouldBlock` errors after which a read gives up
+const MAX_WOULD_BLOCK_RETRIES: usize = 100_000;
+
+// Readers may fail spuriously when interrupted by a signal, or when they are non-blocking and have no data available
+// yet. Reading again is then expected to succeed eventually, although a non-blocking reader may never get more data:
+// it is only polled a bounded number of times before its error is surfaced. Note that readers signal the end of the
+// stream by returning 0 bytes, which is never retried. A new `Retries` is used for each read.
+#[derive(Default)]
+pub(crate) struct Retries {
+	would_block: usize,
+}
+
+impl Retries {
+	pub(crate) fn should_retry(&mut self, error: &io::Error) -> bool {
+		match error.kind() {
+			io::ErrorKind::Interrupted => true,
+			io::ErrorKind::WouldBlock if self.would_block < MAX_WOULD_BLOCK_RETRIES => {
+				self.would_block += 1;
+				std::thread::yield_now();
+				true
+			}
+			_ => false,
+		}
+	}
+}
+
+/// Source reading from any struct implementing Read.
+///
+/// Reads failing with `io::ErrorKind::Interrupted` or `io::ErrorKind::WouldBlock` are retried, while reads returning
+/// 0 bytes are taken as the end of the stream. Non-blocking readers are busy-polled: when a reader keeps failing with
+/// `io::ErrorKind::WouldBlock`, it is polled again many times in a row before the error is reported as
+/// `ErrorKind::IOError`. Blocking readers should be preferred.
+///
+/// Parsers built on top of it (see `ByteSource`) report errors at the same offsets as this crate by using
+/// `ByteSource::bytes_read` as the offset of their errors.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs::File;
+/// use std::time::Duration;
+/// use mp3_duration::{ByteSource, Context, ErrorKind, MP3DurationError};
+///
+/// let mut file = File::open("test/source.mp3").unwrap();
+/// let mut source = Context::new(&mut file);
+/// let mut header = [0; 4];
+/// source.read_exact(&mut header).unwrap();
+/// if header[0] != 0xFF {
+///     let error = MP3DurationError {
+///         kind: ErrorKind::UnexpectedFrame { header: u32::from_be_bytes(header) },
+///         offset: source.bytes_read(),
+///         at_duration: Duration::default(),
+///         first_header: None,
+///     };
+///     println!("{}", error);
+/// }
+/// ```
 pub struct Context<'r, T> {
 	reader: &'r mut T,
-	bytes_read: usize,
+	lookahead: Vec<u8>,
+	bytes_read: u64,
 	reached_eof: bool,
-	pub duration: Duration,
 }
 
 impl<'r, T: Read> Context<'r, T> {
+	/// Wraps a reader positioned at the start of the stream.
 	pub fn new(reader: &'r mut T) -> Self {
 		Context {
-			reader: reader,
+			reader,
+			lookahead: Vec::new(),
 			bytes_read: 0,
-			duration: Duration::from_secs(0),
 			reached_eof: false,
 		}
 	}
 
-	pub fn read_exact(&mut self, buffer: &mut [u8]) -> Result<(), MP3DurationError> {
-		let result = self.reader.read_exact(buffer);
-		if result.is_ok() {
-			self.bytes_read += buffer.len();
+	// Number of bytes taken from the reader, including those which were only peeked
+	pub(crate) fn bytes_taken(&self) -> u64 {
+		self.bytes_read + self.lookahead.len() as u64
+	}
+
+	fn consume_lookahead(&mut self, num_bytes: usize) -> usize {
+		let num_bytes = num_bytes.min(self.lookahead.len());
+		self.lookahead.drain(..num_bytes);
+		self.bytes_read += num_bytes as u64;
+		num_bytes
+	}
+
+	fn read_some(&mut self, buffer: &mut [u8]) -> Result<usize, ErrorKind> {
+		let mut retries = Retries::default();
+		loop {
+			match self.reader.read(buffer) {
+				Ok(n) => return Ok(n),
+				Err(e) if retries.should_retry(&e) => continue,
+				Err(e) => return Err(e.into()),
+			}
+		}
+	}
+
+	// Like `skip`, but stops without error at the end of the stream. Returns how many bytes were skipped.
+	fn skip_available(&mut self, num_bytes: usize) -> Result<usize, ErrorKind> {
+		let mut skipped = self.consume_lookahead(num_bytes);
+		let mut chunk = [0; 4096];
+		while skipped < num_bytes {
+			let wanted = (num_bytes - skipped).min(chunk.len());
+			match self.read_some(&mut chunk[..wanted])? {
+				0 => {
+					self.reached_eof = true;
+					return Ok(skipped);
+				}
+				n => {
+					skipped += n;
+					self.bytes_read += n as u64;
+				}
+			}
 		}
-		self.reached_eof = match &result {
-			Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => true,
-			_ => false,
-		};
-		result.map_err(|e| self.error(e.into()))
+		self.reached_eof = false;
+		Ok(skipped)
+	}
+}
+
+impl<'r, T: Read> ByteSource for Context<'r, T> {
+	fn read_exact(&mut self, buffer: &mut [u8]) -> Result<(), ErrorKind> {
+		let from_lookahead = buffer.len().min(self.lookahead.len());
+		buffer[..from_lookahead].copy_from_slice(&self.lookahead[..from_lookahead]);
+		self.consume_lookahead(from_lookahead);
+
+		let mut filled = from_lookahead;
+		while filled < buffer.len() {
+			match self.read_some(&mut buffer[filled..])? {
+				0 => {
+					self.reached_eof = true;
+					return Err(ErrorKind::UnexpectedEOF);
+				}
+				n => {
+					filled += n;
+					self.bytes_read += n as u64;
+				}
+			}
+		}
+		self.reached_eof = false;
+		Ok(())
+	}
+
+	fn skip(&mut self, num_bytes: usize) -> Result<(), ErrorKind> {
+		if self.skip_available(num_bytes)? < num_bytes {
+			return Err(ErrorKind::UnexpectedEOF);
+		}
+		Ok(())
+	}
+
+	fn peek(&mut self, buffer: &mut [u8]) -> Result<usize, ErrorKind> {
+		let mut chunk = [0; 4096];
+		while self.lookahead.len() < buffer.len() {
+			let wanted = (buffer.len() - self.lookahead.len()).min(chunk.len());
+			match self.read_some(&mut chunk[..wanted])? {
+				0 => break,
+				n => self.lookahead.extend_from_slice(&chunk[..n]),
+			}
+		}
+		let available = buffer.len().min(self.lookahead.len());
+		buffer[..available].copy_from_slice(&self.lookahead[..available]);
+		Ok(available)
+	}
+
+	fn bytes_read(&self) -> u64 {
+		self.bytes_read
+	}
+
+	fn reached_eof(&self) -> bool {
+		self.reached_eof
+	}
+}
+
+impl<'r, T: Read> sealed::Sealed for Context<'r, T> {}
+
+impl<'r, T: Read> Source for Context<'r, T> {
+	fn unread(&mut self, bytes: &[u8]) {
+		self.lookahead.splice(0..0, bytes.iter().copied());
+		self.bytes_read -= bytes.len() as u64;
+		self.reached_eof = false;
+	}
+
+	// Bytes are read in chunks once a zero is found, and whatever follows the run is kept in the lookahead buffer.
+	fn skip_zeros(&mut self, max_bytes: usize) -> Result<usize, ErrorKind> {
+		let mut skipped = 0;
+		if self.lookahead.is_empty() && max_bytes > 0 {
+			let mut byte = [0; 1];
+			match self.read_some(&mut byte)? {
+				0 => return Ok(0),
+				_ if byte[0] != 0 => {
+					self.lookahead.push(byte[0]);
+					return Ok(0);
+				}
+				_ => {
+					self.bytes_read += 1;
+					skipped += 1;
+				}
+			}
+		}
+
+		let mut chunk = [0; 4096];
+		while skipped < max_bytes {
+			if self.lookahead.is_empty() {
+				let wanted = (max_bytes - skipped).min(chunk.len());
+				match self.read_some(&mut chunk[..wanted])? {
+					0 => break,
+					n => self.lookahead.extend_from_slice(&chunk[..n]),
+				}
+			}
+			let num_zeros = self.lookahead.iter().take(max_bytes - skipped).take_while(|b| **b == 0).count();
+			skipped += self.consume_lookahead(num_zeros);
+			if !self.lookahead.is_empty() {
+				break;
+			}
+		}
+		Ok(skipped)
+	}
+}
+
+// BufRead implementation buffering reads into a buffer provided by the caller, instead of allocating one like
+// BufReader does.
+pub(crate) struct ScratchReader<'b, R> {
+	inner: R,
+	buffer: &'b mut [u8],
+	// Range of `buffer` holding bytes read from `inner` but not consumed yet
+	position: usize,
+	filled: usize,
+}
+
+impl<'b, R: Read> ScratchReader<'b, R> {
+	pub(crate) fn new(inner: R, buffer: &'b mut [u8]) -> Self {
+		assert!(!buffer.is_empty(), "Scratch buffer must not be empty");
+		ScratchReader {
+			inner,
+			buffer,
+			position: 0,
+			filled: 0,
+		}
+	}
+}
+
+impl<'b, R: Read> Read for ScratchReader<'b, R> {
+	fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+		let available = self.fill_buf()?;
+		let num_bytes = available.len().min(buffer.len());
+		buffer[..num_bytes].copy_from_slice(&available[..num_bytes]);
+		self.consume(num_bytes);
+		Ok(num_bytes)
+	}
+}
+
+impl<'b, R: Read> BufRead for ScratchReader<'b, R> {
+	fn fill_buf(&mut self) -> io::Result<&[u8]> {
+		if self.position == self.filled {
+			self.filled = self.inner.read(self.buffer)?;
+			self.position = 0;
+		}
+		Ok(&self.buffer[self.position..self.filled])
+	}
+
+	fn consume(&mut self, amount: usize) {
+		self.position = (self.position + amount).min(self.filled);
+	}
+}
+
+// Once a call returned data, calling `fill_buf` again returns the same buffer without reading anything.
+pub(crate) fn fill_buf<T: BufRead>(reader: &mut T) -> Result<&[u8], ErrorKind> {
+	let mut retries = Retries::default();
+	loop {
+		match reader.fill_buf() {
+			Ok([]) => return Ok(&[]),
+			Ok(_) => break,
+			Err(e) if retries.should_retry(&e) => continue,
+			Err(e) => return Err(e.into()),
+		}
+	}
+	Ok(reader.fill_buf()?)
+}
+
+/// Source reading from any struct implementing BufRead, scanning its buffer in place instead of copying bytes out of
+/// it one read at a time.
+pub struct BufContext<'r, T> {
+	reader: &'r mut T,
+	// Bytes put back with `unread` or read ahead by `peek`, returned before the content of `reader`
+	lookahead: Vec<u8>,
+	bytes_read: u64,
+	reached_eof: bool,
+}
+
+impl<'r, T: BufRead> BufContext<'r, T> {
+	/// Wraps a reader positioned at the start of the stream.
+	pub fn new(reader: &'r mut T) -> Self {
+		BufContext {
+			reader,
+			lookahead: Vec::new(),
+			bytes_read: 0,
+			reached_eof: false,
+		}
+	}
+
+	fn consume_lookahead(&mut self, num_bytes: usize) -> usize {
+		let num_bytes = num_bytes.min(self.lookahead.len());
+		self.lookahead.drain(..num_bytes);
+		self.bytes_read += num_bytes as u64;
+		num_bytes
 	}
 
-	pub fn skip(&mut self, num_bytes: usize) -> Result<(), MP3DurationError> {
-		let num_bytes_skipped = io::copy(&mut self.reader.take(num_bytes as u64), &mut io::sink());
-		match num_bytes_skipped {
-			Err(e) => Err(self.error(e.into())),
-			Ok(n) if n < num_bytes as u64 => {
+	fn consume(&mut self, num_bytes: usize) {
+		self.reader.consume(num_bytes);
+		self.bytes_read += num_bytes as u64;
+	}
+
+	// Like `skip`, but stops without error at the end of the stream. Returns how many bytes were skipped.
+	fn skip_available(&mut self, num_bytes: usize) -> Result<usize, ErrorKind> {
+		let mut skipped = self.consume_lookahead(num_bytes);
+		while skipped < num_bytes {
+			let available = fill_buf(self.reader)?.len();
+			if available == 0 {
 				self.reached_eof = true;
-				Err(self.error(ErrorKind::UnexpectedEOF))
+				return Ok(skipped);
 			}
-			_ => {
-				self.bytes_read += num_bytes;
-				Ok(())
+			let num_bytes = available.min(num_bytes - skipped);
+			self.consume(num_bytes);
+			skipped += num_bytes;
+		}
+		self.reached_eof = false;
+		Ok(skipped)
+	}
+}
+
+impl<'r, T: BufRead> ByteSource for BufContext<'r, T> {
+	fn read_exact(&mut self, buffer: &mut [u8]) -> Result<(), ErrorKind> {
+		let mut filled = buffer.len().min(self.lookahead.len());
+		buffer[..filled].copy_from_slice(&self.lookahead[..filled]);
+		self.consume_lookahead(filled);
+
+		while filled < buffer.len() {
+			let available = fill_buf(self.reader)?;
+			if available.is_empty() {
+				self.reached_eof = true;
+				return Err(ErrorKind::UnexpectedEOF);
+			}
+			let num_bytes = available.len().min(buffer.len() - filled);
+			buffer[filled..filled + num_bytes].copy_from_slice(&available[..num_bytes]);
+			self.consume(num_bytes);
+			filled += num_bytes;
+		}
+		self.reached_eof = false;
+		Ok(())
+	}
+
+	fn skip(&mut self, num_bytes: usize) -> Result<(), ErrorKind> {
+		if self.skip_available(num_bytes)? < num_bytes {
+			return Err(ErrorKind::UnexpectedEOF);
+		}
+		Ok(())
+	}
+
+	fn peek(&mut self, buffer: &mut [u8]) -> Result<usize, ErrorKind> {
+		while self.lookahead.len() < buffer.len() {
+			let wanted = buffer.len() - self.lookahead.len();
+			let available = fill_buf(self.reader)?;
+			if available.is_empty() {
+				break;
 			}
+			let num_bytes = available.len().min(wanted);
+			self.lookahead.extend_from_slice(&available[..num_bytes]);
+			// Bytes moved to the lookahead buffer are not consumed from the point of view of the parser
+			self.reader.consume(num_bytes);
 		}
+		let available = buffer.len().min(self.lookahead.len());
+		buffer[..available].copy_from_slice(&self.lookahead[..available]);
+		Ok(available)
+	}
+
+	fn bytes_read(&self) -> u64 {
+		self.bytes_read
 	}
 
-	pub fn reached_eof(&self) -> bool {
+	fn reached_eof(&self) -> bool {
 		self.reached_eof
 	}
+}
+
+impl<'r, T: BufRead> sealed::Sealed for BufContext<'r, T> {}
+
+impl<'r, T: BufRead> Source for BufContext<'r, T> {
+	fn unread(&mut self, bytes: &[u8]) {
+		self.lookahead.splice(0..0, bytes.iter().copied());
+		self.bytes_read -= bytes.len() as u64;
+		self.reached_eof = false;
+	}
 
-	pub fn error(&self, e: ErrorKind) -> MP3DurationError {
-		MP3DurationError {
-			kind: e,
-			offset: self.bytes_read,
-			at_duration: self.duration,
+	fn skip_zeros(&mut self, max_bytes: usize) -> Result<usize, ErrorKind> {
+		let num_zeros = self.lookahead.iter().take(max_bytes).take_while(|b| **b == 0).count();
+		let mut skipped = self.consume_lookahead(num_zeros);
+		if !self.lookahead.is_empty() {
+			return Ok(skipped);
+		}
+		while skipped < max_bytes {
+			let available = fill_buf(self.reader)?;
+			let num_zeros = available.iter().take(max_bytes - skipped).take_while(|b| **b == 0).count();
+			let done = num_zeros < available.len() || available.is_empty();
+			self.consume(num_zeros);
+			skipped += num_zeros;
+			if done {
+				break;
+			}
 		}
+		Ok(skipped)
 	}
 }
@@ -0,0 +1,11 @@
+// CRC protecting MPEG audio frames: CRC-16 with polynomial 0x8005 and initial value 0xFFFF, without reflection.
+pub fn crc16(data: &[u8]) -> u16 {
+	let mut crc = 0xFFFF;
+	for byte in data {
+		crc ^= (*byte as u16) << 8;
+		for _ in 0..8 {
+			crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x8005 } else { crc << 1 };
+		}
+	}
+	crc
+}
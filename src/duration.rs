@@ -0,0 +1,97 @@
+use std::time::Duration;
+
+use crate::header::FrameHeader;
+
+/// Sums the durations of audio frames, each described by its number of samples and its sampling rate in Hz.
+///
+/// Samples are added up per sampling rate before being converted to a duration, so the result is not skewed by
+/// rounding the duration of every frame. Frames with a sampling rate of 0 are ignored.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use mp3_duration;
+///
+/// // Three seconds of MPEG-1 Layer III frames at 48kHz
+/// let frames = vec![(1152, 48000); 125];
+/// assert_eq!(Duration::from_secs(3), mp3_duration::duration_from_frames(&frames));
+/// ```
+pub fn duration_from_frames(frames: &[(u32, u32)]) -> Duration {
+	let mut accumulator = DurationAccumulator::default();
+	for (samples, sampling_rate) in frames {
+		accumulator.add(*samples as u64, *sampling_rate);
+	}
+	accumulator.duration()
+}
+
+/// Formats a duration as a `HH:MM:SS.mmm` timecode, or `MM:SS.mmm` when it is shorter than an hour.
+///
+/// Durations are truncated to the millisecond, the same way this crate truncates them to the nanosecond when
+/// converting samples. Hours are not wrapped around.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use mp3_duration;
+///
+/// assert_eq!("06:38.243", mp3_duration::format_hms(Duration::from_micros(398_243_900)));
+/// assert_eq!("27:46:40.000", mp3_duration::format_hms(Duration::from_secs(100_000)));
+/// ```
+pub fn format_hms(duration: Duration) -> String {
+	let seconds = duration.as_secs();
+	let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+	let milliseconds = duration.subsec_millis();
+	if hours > 0 {
+		format!("{:02}:{:02}:{:02}.{:03}", hours, minutes, seconds, milliseconds)
+	} else {
+		format!("{:02}:{:02}.{:03}", minutes, seconds, milliseconds)
+	}
+}
+
+// Number of samples measured so far, for each sampling rate encountered
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DurationAccumulator {
+	samples: Vec<(u32, u64)>,
+}
+
+impl DurationAccumulator {
+	pub fn add(&mut self, samples: u64, sampling_rate: u32) {
+		if sampling_rate == 0 {
+			return;
+		}
+		match self.samples.iter_mut().find(|(rate, _)| *rate == sampling_rate) {
+			Some((_, total)) => *total += samples,
+			None => self.samples.push((sampling_rate, samples)),
+		}
+	}
+
+	// Removes samples previously added at the given sampling rate, down to none
+	pub fn remove(&mut self, samples: u64, sampling_rate: u32) {
+		if let Some((_, total)) = self.samples.iter_mut().find(|(rate, _)| *rate == sampling_rate) {
+			*total = total.saturating_sub(samples);
+		}
+	}
+
+	pub fn add_frame(&mut self, header: &FrameHeader) {
+		self.add(header.samples_per_frame() as u64, header.sampling_rate.hz());
+	}
+
+	// Whether no frame was measured
+	pub fn is_empty(&self) -> bool {
+		self.samples.is_empty()
+	}
+
+	pub fn duration(&self) -> Duration {
+		let billion = 1_000_000_000;
+		self.samples
+			.iter()
+			.map(|(sampling_rate, samples)| {
+				let nanoseconds = *samples as u128 * billion / *sampling_rate as u128;
+				Duration::new((nanoseconds / billion) as u64, (nanoseconds % billion) as u32)
+			})
+			.sum()
+	}
+}
@@ -0,0 +1,67 @@
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::context::{BufContext, ScratchReader};
+use crate::error::MP3DurationError;
+use crate::options::ParseOptions;
+use crate::parser::Parser;
+
+// Same as the default capacity of `BufReader`
+const BUFFER_SIZE: usize = 8 * 1024;
+
+/// Measures the durations of many mp3 files one after the other, reusing the same settings and read buffer.
+///
+/// This is the counterpart of `from_read_with_options` for workloads measuring large numbers of files, e.g. with one
+/// parser per worker thread: files are read through a buffer allocated once, rather than once per file.
+///
+/// # Examples
+///
+/// ```
+/// use mp3_duration::{DurationParser, ParseOptions};
+///
+/// let mut parser = DurationParser::new(&ParseOptions::default());
+/// for path in &["test/source.mp3", "test/ID3v2.mp3"] {
+///     let duration = parser.measure_from_path(path).unwrap();
+///     println!("{}: {:?}", path, duration);
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct DurationParser {
+	options: ParseOptions,
+	buffer: Vec<u8>,
+}
+
+impl DurationParser {
+	/// Creates a parser measuring files according to `options`.
+	pub fn new(options: &ParseOptions) -> Self {
+		DurationParser {
+			options: options.clone(),
+			buffer: vec![0; BUFFER_SIZE],
+		}
+	}
+
+	/// Settings files are parsed with.
+	pub fn options(&self) -> &ParseOptions {
+		&self.options
+	}
+
+	/// Measures the duration of a mp3 file contained in any struct implementing Read, like `from_read_with_options`.
+	pub fn measure_from_read<T>(&mut self, reader: &mut T) -> Result<Duration, MP3DurationError>
+	where
+		T: Read,
+	{
+		let mut reader = ScratchReader::new(reader, &mut self.buffer);
+		Parser::new(BufContext::new(&mut reader), &self.options).measure()
+	}
+
+	/// Measures the duration of a file.
+	pub fn measure_from_path<P>(&mut self, path: P) -> Result<Duration, MP3DurationError>
+	where
+		P: AsRef<Path>,
+	{
+		let mut file = File::open(path).map_err(|e| MP3DurationError::at_start(e.into()))?;
+		self.measure_from_read(&mut file)
+	}
+}
@@ -0,0 +1,93 @@
+use std::io::prelude::*;
+
+use crate::context::Context;
+use crate::error::MP3DurationError;
+use crate::options::ParseOptions;
+use crate::parser::{Measured, Parser, Progress};
+
+// Number of frames searched for the version string LAME writes in the ancillary data of frames
+const ANCILLARY_DATA_FRAMES: usize = 20;
+
+/// Best guess of the encoder which produced a mp3 file, see `detect_encoder_from_read`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EncoderGuess {
+	/// LAME, with its version such as "3.99r" (which may be empty or truncated).
+	Lame(String),
+	/// FFmpeg (libavcodec or libavformat), with its name and version such as "Lavc58.35".
+	Ffmpeg(String),
+	/// Fraunhofer encoder writing a VBRI header, which it only does for VBR files.
+	FraunhoferVbr,
+	/// No encoder could be identified.
+	Unknown,
+}
+
+/// Guesses which encoder produced a mp3 file contained in any struct implementing Read, from the start of the stream.
+///
+/// The encoder is identified from the LAME extension of the Xing/Info header (written by LAME and FFmpeg), or from a
+/// VBRI header (written by Fraunhofer encoders). For files without such headers, such as CBR files from older LAME
+/// versions, the ancillary data of the first frames is searched for the version string LAME pads frames with. This is
+/// a best effort: files from other encoders, or whose headers were rewritten by other tools, are misidentified or
+/// reported as `EncoderGuess::Unknown`.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use std::fs::File;
+/// use std::io::BufReader;
+/// use mp3_duration;
+///
+/// let path = Path::new("test/source.mp3");
+/// let file = File::open(path).unwrap();
+/// let mut reader = BufReader::new(file);
+/// let encoder = mp3_duration::detect_encoder_from_read(&mut reader).unwrap();
+/// println!("Encoded with {:?}", encoder);
+/// ```
+pub fn detect_encoder_from_read<T>(reader: &mut T) -> Result<EncoderGuess, MP3DurationError>
+where
+	T: Read,
+{
+	let options = ParseOptions {
+		trust_first_xing: false,
+		..Default::default()
+	};
+	let mut parser = Parser::new(Context::new(reader), &options);
+	parser.keep_frame_bytes = true;
+	let mut frames = 0;
+	let mut guess = None;
+
+	while guess.is_none() && frames < ANCILLARY_DATA_FRAMES {
+		let progress = parser.measure_next_block_with(|_, block| {
+			let measured = match block {
+				Measured::Frame(measured) => measured,
+				Measured::Tag(_) => return,
+			};
+			if frames == 0 {
+				if let Some(lame) = &measured.frame.lame {
+					guess = Some(match lame.encoder.strip_prefix("LAME") {
+						Some(version) => EncoderGuess::Lame(version.to_owned()),
+						None => EncoderGuess::Ffmpeg(lame.encoder.clone()),
+					});
+				} else if measured.frame.vbri.is_some() {
+					guess = Some(EncoderGuess::FraunhoferVbr);
+				}
+			}
+			if guess.is_none() && measured.complete {
+				guess = find_lame_version(measured.bytes).map(EncoderGuess::Lame);
+			}
+			frames += 1;
+		})?;
+		if let Progress::Finished(_) = progress {
+			break;
+		}
+	}
+
+	Ok(guess.unwrap_or(EncoderGuess::Unknown))
+}
+
+// Looks for "LAME" followed by a version, such as "LAME3.91"
+fn find_lame_version(bytes: &[u8]) -> Option<String> {
+	let start = bytes.windows(5).position(|window| window.starts_with(b"LAME") && window[4].is_ascii_digit())? + 4;
+	let version = bytes[start..].iter().take(5).take_while(|byte| byte.is_ascii_alphanumeric() || **byte == b'.');
+	Some(version.map(|byte| *byte as char).collect())
+}
@@ -2,14 +2,52 @@ use std::io;
 use std::time::Duration;
 use thiserror::Error;
 
+use crate::header::FrameHeader;
+use crate::tags::TagKind;
+
 #[derive(Debug, Error)]
 #[error("{} at offset {} (0x{1:X}); measured duration up to here: {:?}",
         .kind, .offset, .at_duration)]
 pub struct MP3DurationError {
+    /// What went wrong.
     #[source]
     pub kind: ErrorKind,
-    pub offset: usize,
+    /// Position in the stream where the error was detected.
+    pub offset: u64,
+    /// Best-effort duration of the stream up to the error: the sum of the durations of the frames which were fully
+    /// read before it occurred, whatever the kind of error. This is zero for errors occurring before any frame was
+    /// read, such as failing to open a file.
     pub at_duration: Duration,
+    /// Header of the first audio frame of the stream, if one was found before the error, to tell which format the
+    /// stream was using.
+    pub first_header: Option<FrameHeader>,
+}
+
+impl MP3DurationError {
+    // Error occurring before anything was read
+    pub(crate) fn at_start(kind: ErrorKind) -> Self {
+        MP3DurationError {
+            kind,
+            offset: 0,
+            at_duration: Duration::from_secs(0),
+            first_header: None,
+        }
+    }
+}
+
+impl From<MP3DurationError> for io::Error {
+    /// The `io::Error` of `ErrorKind::IOError` is returned as is. Other errors are wrapped, with the
+    /// `io::ErrorKind::InvalidData` kind except for `ErrorKind::UnexpectedEOF` and `ErrorKind::Timeout`, which map to
+    /// `io::ErrorKind::UnexpectedEof` and `io::ErrorKind::TimedOut`.
+    fn from(error: MP3DurationError) -> Self {
+        let kind = match error.kind {
+            ErrorKind::IOError(e) => return e,
+            ErrorKind::UnexpectedEOF => io::ErrorKind::UnexpectedEof,
+            ErrorKind::Timeout => io::ErrorKind::TimedOut,
+            _ => io::ErrorKind::InvalidData,
+        };
+        io::Error::new(kind, error)
+    }
 }
 
 #[derive(Debug, Error)]
@@ -20,14 +58,43 @@ pub enum ErrorKind {
     ForbiddenLayer,
     #[error("Invalid bitrate bits: {0} (0b{0:b})", .bitrate)]
     InvalidBitrate { bitrate: u8 },
+    /// Bitrate bits all set, which usually means sync was lost rather than that the file is damaged.
+    #[error("Bad bitrate index (0b1111)")]
+    BadBitrateIndex,
     #[error("Invalid sampling rate bits: {0} (0b{0:b})", .sampling_rate)]
     InvalidSamplingRate { sampling_rate: u8 },
+    #[error("No Xing/Info header")]
+    NoXingHeader,
+    #[error("Reserved emphasis value")]
+    ReservedEmphasis,
     #[error("Unexpected frame, header 0x{:X}", .header)]
     UnexpectedFrame { header: u32 },
+    #[error("Tag size too large: {0} bytes", .declared)]
+    TagTooLarge { declared: usize },
     #[error("Unexpected end of file")]
     UnexpectedEOF,
     #[error("MPEG frame too short")]
     MPEGFrameTooShort,
+    /// Free-format frames (bitrate index 0), whose length cannot be computed from their header, are not supported. In
+    /// lenient mode, this is only reported for streams where such frames were found but no other audio frame.
+    #[error("Free-format MPEG frames are not supported")]
+    FreeFormatUnsupported,
+    /// The CRC of a frame does not match its content, see `ParseOptions::validate_crc`.
+    #[error("CRC mismatch")]
+    CrcMismatch,
+    #[error("Deadline exceeded")]
+    Timeout,
+    /// The stream only contains tags, and `ParseOptions::require_audio` is set.
+    #[error("No audio frames")]
+    NoAudioFrames,
+    /// A tag was found while `ParseOptions::reject_tags` is set.
+    #[error("Unexpected {kind} tag")]
+    UnexpectedTag { kind: TagKind },
+    /// The stream contains, or a Xing/Info or VBRI header declares, more frames than `ParseOptions::max_frames`.
+    #[error("More than {max_frames} frames")]
+    FrameLimitExceeded { max_frames: usize },
+    /// Reading the stream failed. The `io::Error` is also the source of this error, and can be reached from a
+    /// `MP3DurationError` by walking the `Error::source` chain.
     #[error("Unexpected IO Error: {0}")]
     IOError(#[source] io::Error),
 }
@@ -0,0 +1,142 @@
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::io::{BufReader, SeekFrom};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::context::Context;
+use crate::error::MP3DurationError;
+use crate::options::ParseOptions;
+use crate::parser::{Measured, Parser, Progress};
+
+// Number of frames with the same bitrate after which a stream without Xing/Info header is taken to be constant bitrate
+const CBR_DETECTION_FRAMES: usize = 10;
+
+/// Estimates the duration of a constant bitrate mp3 file from its size in bytes, without reading it.
+///
+/// This is useful when only the length of the file is known, for example from the `Content-Length` of an HTTP
+/// response. Tags and Xing/Info frames are counted as audio, so the estimate is slightly too long for files which
+/// contain them. Returns a zero duration if `bitrate_bps` is 0.
+///
+/// # Examples
+///
+/// ```
+/// use mp3_duration;
+///
+/// let duration = mp3_duration::estimate_from_size(4_000_000, 128_000);
+/// assert_eq!(250, duration.as_secs());
+/// ```
+pub fn estimate_from_size(content_length: u64, bitrate_bps: u32) -> Duration {
+	if bitrate_bps == 0 {
+		return Duration::from_secs(0);
+	}
+	let bits = content_length as u128 * 8;
+	let rate = bitrate_bps as u128;
+	let billion = 1_000_000_000;
+	let seconds = bits / rate;
+	let nanoseconds = (billion * bits) / rate - billion * seconds;
+	Duration::new(seconds as u64, nanoseconds as u32)
+}
+
+// Size of the ID3v1 tag at the end of the file, if any
+fn trailing_tag_size(file: &mut File, file_size: u64) -> io::Result<u64> {
+	if file_size < 128 {
+		return Ok(0);
+	}
+	file.seek(SeekFrom::Start(file_size - 128))?;
+	let mut magic = [0; 3];
+	file.read_exact(&mut magic)?;
+	Ok(if &magic == b"TAG" { 128 } else { 0 })
+}
+
+/// Estimates the duration of a file from its size and its first frame, without scanning the rest of it.
+///
+/// Files with a Xing/Info header report the duration it declares. Otherwise, the size of the file minus its leading
+/// tags and trailing ID3v1 tag is divided by the bitrate of the first frame. This is exact for constant bitrate
+/// files, but only a rough approximation for variable bitrate files without a Xing/Info header, whose first frame is
+/// not representative of the whole file. Use `from_path` when an exact duration is needed.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use mp3_duration;
+///
+/// let path = Path::new("test/source.mp3");
+/// let duration = mp3_duration::quick_estimate_from_path(&path).unwrap();
+/// println!("File duration: about {:?}", duration);
+/// ```
+pub fn quick_estimate_from_path<P>(path: P) -> Result<Duration, MP3DurationError>
+where
+	P: AsRef<Path>,
+{
+	let mut file = File::open(path).map_err(|e| MP3DurationError::at_start(e.into()))?;
+	let file_size = file
+		.metadata()
+		.map_err(|e| MP3DurationError::at_start(e.into()))?
+		.len();
+
+	let probe = crate::probe_from_read(&mut BufReader::new(&mut file))?;
+	if let Some(duration) = probe.xing_duration {
+		return Ok(duration);
+	}
+
+	let trailing_tag_size =
+		trailing_tag_size(&mut file, file_size).map_err(|e| MP3DurationError::at_start(e.into()))?;
+	let content_length = file_size.saturating_sub(probe.offset + trailing_tag_size);
+	Ok(estimate_from_size(content_length, probe.header.bitrate))
+}
+
+/// Measures the duration of a mp3 stream whose total size in bytes is known, without reading all of it when possible.
+///
+/// This is meant for non-seekable streams whose size is known out-of-band, for example from the `Content-Length` of
+/// an HTTP response. Streams with a Xing/Info header report the duration it declares. Otherwise, when the first
+/// frames all have the same bitrate, the stream is taken to be constant bitrate and its size minus the tags preceding
+/// the first frame is divided by that bitrate, like `estimate_from_size`. A trailing ID3v1 tag cannot be detected
+/// without reading the end of the stream, and is counted as audio. Variable bitrate streams without Xing/Info header
+/// are scanned entirely, like with `from_read`.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Cursor;
+/// use mp3_duration;
+///
+/// let data = std::fs::read("test/CBR320.mp3").unwrap();
+/// let mut reader = Cursor::new(&data);
+/// let duration = mp3_duration::from_read_with_size(&mut reader, data.len() as u64).unwrap();
+/// println!("File duration: about {:?}", duration);
+/// ```
+pub fn from_read_with_size<T>(reader: &mut T, total_bytes: u64) -> Result<Duration, MP3DurationError>
+where
+	T: Read,
+{
+	let options = ParseOptions::default();
+	let mut parser = Parser::new(Context::new(reader), &options);
+	// Offset and bitrate of the first frame, and number of frames with that bitrate
+	let mut first_frame: Option<(u64, u32)> = None;
+	let mut cbr_frames = 0;
+	loop {
+		let mut audio_frame = None;
+		let progress = parser.measure_next_block_with(|_, block| match block {
+			Measured::Frame(measured) if measured.is_audio => {
+				audio_frame = Some((measured.frame.offset, measured.frame.header.bitrate))
+			}
+			_ => (),
+		})?;
+		let (frame_offset, frame_bitrate) = match (progress, audio_frame) {
+			(Progress::Finished(duration), _) => return Ok(duration),
+			(Progress::Continue, None) => continue,
+			(Progress::Continue, Some(frame)) => frame,
+		};
+		let (offset, bitrate) = *first_frame.get_or_insert((frame_offset, frame_bitrate));
+		if frame_bitrate != bitrate {
+			return parser.measure();
+		}
+		cbr_frames += 1;
+		if cbr_frames == CBR_DETECTION_FRAMES {
+			return Ok(estimate_from_size(total_bytes.saturating_sub(offset), bitrate));
+		}
+	}
+}
@@ -0,0 +1,47 @@
+use std::io;
+use std::io::prelude::*;
+
+use flate2::read::GzDecoder;
+
+use crate::context::Retries;
+use crate::error::ErrorKind;
+
+const MAGIC: [u8; 2] = [0x1F, 0x8B];
+
+// Reader which decompresses its content if it starts with the gzip magic number, and returns it as is otherwise.
+pub enum MaybeGzip<T> {
+	Plain(T),
+	Gzip(GzDecoder<T>),
+}
+
+impl<T: Read> MaybeGzip<io::Chain<io::Cursor<Vec<u8>>, T>> {
+	// The bytes read to look for the magic number are put back in front of the reader.
+	pub fn new(mut reader: T) -> Result<Self, ErrorKind> {
+		let mut magic = [0; 2];
+		let mut filled = 0;
+		let mut retries = Retries::default();
+		while filled < magic.len() {
+			match reader.read(&mut magic[filled..]) {
+				Ok(0) => break,
+				Ok(n) => filled += n,
+				Err(e) if retries.should_retry(&e) => continue,
+				Err(e) => return Err(e.into()),
+			}
+		}
+		let reader = io::Cursor::new(magic[..filled].to_vec()).chain(reader);
+		if magic == MAGIC {
+			Ok(MaybeGzip::Gzip(GzDecoder::new(reader)))
+		} else {
+			Ok(MaybeGzip::Plain(reader))
+		}
+	}
+}
+
+impl<T: Read> Read for MaybeGzip<T> {
+	fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+		match self {
+			MaybeGzip::Plain(reader) => reader.read(buffer),
+			MaybeGzip::Gzip(reader) => reader.read(buffer),
+		}
+	}
+}
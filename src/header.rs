@@ -0,0 +1,183 @@
+use std::time::Duration;
+
+use crate::constants::*;
+use crate::error::ErrorKind;
+
+/// Decoded fields of an MPEG audio frame header.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FrameHeader {
+	pub version: Version,
+	pub layer: Layer,
+	/// Bitrate in bits per second (0 for free-format frames).
+	pub bitrate: u32,
+	pub sampling_rate: SampleRate,
+	pub padding: bool,
+	pub mode: Mode,
+	pub emphasis: Emphasis,
+}
+
+fn get_bitrate(version: Version, layer: Layer, encoded_bitrate: u8) -> Result<u32, ErrorKind> {
+	if encoded_bitrate == 0b1111 {
+		return Err(ErrorKind::BadBitrateIndex);
+	}
+	if encoded_bitrate > 0b1111 {
+		return Err(ErrorKind::InvalidBitrate {
+			bitrate: encoded_bitrate,
+		});
+	}
+	Ok(1000 * BIT_RATES[version as usize][layer as usize][encoded_bitrate as usize])
+}
+
+fn get_sampling_rate(version: Version, encoded_sampling_rate: u8) -> Result<SampleRate, ErrorKind> {
+	if encoded_sampling_rate >= 3 {
+		return Err(ErrorKind::InvalidSamplingRate {
+			sampling_rate: encoded_sampling_rate,
+		});
+	}
+	Ok(SAMPLING_RATES[version as usize][encoded_sampling_rate as usize])
+}
+
+/// Size in bytes of the side information which follows the header (and CRC) of a Layer III frame.
+///
+/// It is 32 bytes for MPEG-1 and 17 bytes for MPEG-2/2.5, except for mono frames which use 17 and 9 bytes
+/// respectively.
+pub fn side_information_size(version: Version, mode: Mode) -> usize {
+	SIDE_INFORMATION_SIZES[version as usize][mode as usize] as usize
+}
+
+// Whether the given 32 bits are a sync word followed by a frame header which could be parsed and measured
+pub(crate) fn is_plausible_header(header: u32) -> bool {
+	header >> 21 == 0x7FF
+		&& FrameHeader::parse(header).is_ok_and(|header| header.is_allowed_combination() && header.frame_length() >= 4)
+}
+
+/// Finds the first frame header at or after position `from` in `bytes`, and returns its position.
+///
+/// Only sync words followed by a valid header are considered: the MPEG version, layer, bitrate and sampling rate must
+/// be allowed (see `FrameHeader::is_allowed_combination`), and free-format frames are skipped. The four bytes of the
+/// header must be within `bytes`.
+///
+/// # Examples
+///
+/// ```
+/// let bytes = [0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFB, 0x90, 0x00];
+/// assert_eq!(Some(4), mp3_duration::find_next_sync(&bytes, 0));
+/// assert_eq!(None, mp3_duration::find_next_sync(&bytes, 5));
+/// ```
+pub fn find_next_sync(bytes: &[u8], from: usize) -> Option<usize> {
+	let end = bytes.len().checked_sub(3)?;
+	(from..end).find(|&position| {
+		bytes[position] == 0xFF && {
+			let header = &bytes[position..position + 4];
+			is_plausible_header(u32::from_be_bytes([header[0], header[1], header[2], header[3]]))
+		}
+	})
+}
+
+impl FrameHeader {
+	/// Decodes a 32-bit frame header. The sync word (11 high bits) is expected to be set.
+	pub fn parse(header: u32) -> Result<FrameHeader, ErrorKind> {
+		let version = match (header >> 19) & 0b11 {
+			0 => Version::Mpeg25,
+			1 => return Err(ErrorKind::ForbiddenVersion),
+			2 => Version::Mpeg2,
+			3 => Version::Mpeg1,
+			_ => unreachable!(),
+		};
+
+		let layer = match (header >> 17) & 0b11 {
+			0 => Layer::NotDefined,
+			1 => Layer::Layer3,
+			2 => Layer::Layer2,
+			3 => Layer::Layer1,
+			_ => unreachable!(),
+		};
+
+		let encoded_bitrate = (header >> 12) & 0b1111;
+		let encoded_sampling_rate = (header >> 10) & 0b11;
+		let padding = 0 != ((header >> 9) & 1);
+
+		let mode = match (header >> 6) & 0b11 {
+			0 => Mode::Stereo,
+			1 => Mode::JointStereo,
+			2 => Mode::DualChannel,
+			3 => Mode::Mono,
+			_ => unreachable!(),
+		};
+
+		let emphasis = match header & 0b11 {
+			0 => Emphasis::None,
+			1 => Emphasis::Ms50_15,
+			2 => Emphasis::Reserved,
+			3 => Emphasis::CcittJ17,
+			_ => unreachable!(),
+		};
+
+		let sampling_rate = get_sampling_rate(version, encoded_sampling_rate as u8)?;
+		if layer == Layer::NotDefined {
+			return Err(ErrorKind::ForbiddenLayer);
+		}
+		let bitrate = get_bitrate(version, layer, encoded_bitrate as u8)?;
+
+		Ok(FrameHeader {
+			version,
+			layer,
+			bitrate,
+			sampling_rate,
+			padding,
+			mode,
+			emphasis,
+		})
+	}
+
+	/// Whether the specification allows this combination of fields, each of which is valid on its own.
+	///
+	/// MPEG-1 Layer II restricts which channel modes can be used at each bitrate: 32, 48, 56 and 80 kbps are only
+	/// allowed for mono, while 224 kbps and above are not allowed for mono. Such frames are more likely random data
+	/// than audio.
+	pub fn is_allowed_combination(&self) -> bool {
+		if self.version != Version::Mpeg1 || self.layer != Layer::Layer2 {
+			return true;
+		}
+		let is_mono = self.mode == Mode::Mono;
+		match self.bitrate {
+			32_000 | 48_000 | 56_000 | 80_000 => is_mono,
+			224_000..=384_000 => !is_mono,
+			_ => true,
+		}
+	}
+
+	/// Whether both frames use the same MPEG version, layer and sampling rate, which are not expected to change within
+	/// a stream.
+	pub fn has_same_format(&self, other: &FrameHeader) -> bool {
+		self.version == other.version && self.layer == other.layer && self.sampling_rate == other.sampling_rate
+	}
+
+	/// Number of audio samples (per channel) encoded in the frame.
+	pub fn samples_per_frame(&self) -> u32 {
+		SAMPLES_PER_FRAME[self.version as usize][self.layer as usize]
+	}
+
+	/// Length of the frame in bytes, header included.
+	///
+	/// Layer I frames are made of 4-byte slots, so both their length and their padding are multiples of 4 bytes.
+	pub fn frame_length(&self) -> usize {
+		let padding = if self.padding { 1 } else { 0 };
+		if self.layer == Layer::Layer1 {
+			return ((self.samples_per_frame() / 32 * self.bitrate / self.sampling_rate.hz() + padding) * 4) as usize;
+		}
+		(self.samples_per_frame() / 8 * self.bitrate / self.sampling_rate.hz() + padding) as usize
+	}
+
+	/// Playback duration of the frame.
+	pub fn duration(&self) -> Duration {
+		let nanoseconds = (self.samples_per_frame() as u64 * 1_000_000_000) / (self.sampling_rate.hz() as u64);
+		Duration::new(0, nanoseconds as u32)
+	}
+
+	/// Size of the side information following the header (and CRC).
+	pub fn side_information_size(&self) -> usize {
+		side_information_size(self.version, self.mode)
+	}
+}
@@ -0,0 +1,77 @@
+// LAME extension of Xing/Info headers, written by LAME and by encoders based on libavcodec right after the Xing/Info
+// fields.
+
+/// ReplayGain values stored in the LAME extension of a Xing/Info header.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ReplayGain {
+	/// Gain to apply for track normalization, in dB.
+	pub track_gain: Option<f32>,
+	/// Gain to apply for album normalization, in dB.
+	pub album_gain: Option<f32>,
+	/// Peak signal amplitude, where 1.0 is full scale.
+	pub peak: Option<f32>,
+}
+
+#[derive(Clone, Debug)]
+pub struct LameTag {
+	// Name and version of the encoder, such as "LAME3.99r" or "Lavc58.35"
+	pub encoder: String,
+	pub replay_gain: Option<ReplayGain>,
+	// Number of samples added by the encoder before the audio (not counting the decoder delay)
+	pub encoder_delay: u16,
+	// Number of samples added by the encoder after the audio, to fill the last frame
+	pub encoder_padding: u16,
+}
+
+impl LameTag {
+	pub const SIZE: usize = 36;
+
+	// Returns `None` if the bytes following the Xing/Info fields are not a LAME extension.
+	pub fn parse(bytes: &[u8; Self::SIZE]) -> Option<Self> {
+		let encoder = &bytes[0..4];
+		if encoder != b"LAME" && encoder != b"Lavc" && encoder != b"Lavf" {
+			return None;
+		}
+
+		// Fixed-point value with 23 fractional bits, 0 when not computed
+		let peak = u32::from_be_bytes([bytes[11], bytes[12], bytes[13], bytes[14]]);
+		let peak = if peak == 0 { None } else { Some(peak as f32 / (1 << 23) as f32) };
+		let track_gain = parse_gain(u16::from_be_bytes([bytes[15], bytes[16]]), 1);
+		let album_gain = parse_gain(u16::from_be_bytes([bytes[17], bytes[18]]), 2);
+		let replay_gain = if track_gain.is_none() && album_gain.is_none() && peak.is_none() {
+			None
+		} else {
+			Some(ReplayGain {
+				track_gain,
+				album_gain,
+				peak,
+			})
+		};
+
+		// Two 12-bit values
+		let encoder_delay = (bytes[21] as u16) << 4 | (bytes[22] as u16) >> 4;
+		let encoder_padding = (bytes[22] as u16 & 0x0F) << 8 | bytes[23] as u16;
+
+		let encoder = String::from_utf8_lossy(&bytes[0..9]);
+		Some(LameTag {
+			encoder: encoder.trim_end_matches(['\0', ' ']).to_owned(),
+			replay_gain,
+			encoder_delay,
+			encoder_padding,
+		})
+	}
+}
+
+// Gain fields start with 3 bits identifying the kind of gain (0 when not set), followed by 3 bits identifying who set
+// it, a sign bit and the absolute value of the gain in tenths of dB.
+fn parse_gain(field: u16, kind: u16) -> Option<f32> {
+	if field >> 13 != kind {
+		return None;
+	}
+	let gain = (field & 0x1FF) as f32 / 10.0;
+	if field & 0x200 != 0 {
+		Some(-gain)
+	} else {
+		Some(gain)
+	}
+}
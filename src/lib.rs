@@ -1,66 +1,358 @@
 use std::fs::File;
 use std::io::prelude::*;
 use std::io::BufReader;
+use std::io::SeekFrom;
 use std::path::Path;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
+// Declared first so that its macros are available to the other modules
+#[macro_use]
+mod logging;
+
+mod analysis;
+#[cfg(feature = "capi")]
+pub mod capi;
 mod constants;
 mod context;
+mod crc;
+mod duration;
+mod duration_parser;
+mod encoder;
 mod error;
+mod estimate;
+#[cfg(feature = "gzip")]
+mod gzip;
+mod header;
+mod lame;
+mod measured;
+mod options;
+#[cfg(feature = "rayon")]
+mod parallel;
+mod parser;
+mod scanner;
+mod seek_index;
+mod segments;
+mod source;
+mod tags;
+#[cfg(feature = "tar")]
+mod tarball;
+mod timestamps;
+mod validation;
+mod vbri;
+mod visit;
+mod warning;
+#[cfg(feature = "zip")]
+mod zipfile;
 #[cfg(test)]
 mod test;
+#[cfg(any(test, feature = "testutil"))]
+pub mod testutil;
 
-use crate::constants::*;
-use crate::context::Context;
+use crate::context::ScratchReader;
+use crate::parser::{Block, Parser, Progress};
+use crate::source::SliceSource;
 
+pub use crate::analysis::{analyze_from_path, analyze_from_read, Analysis, Gap};
+pub use crate::constants::{layer_name, version_name, Emphasis, Layer, Mode, SampleRate, Version};
+pub use crate::context::{BufContext, Context};
+pub use crate::duration::{duration_from_frames, format_hms};
+pub use crate::duration_parser::DurationParser;
+pub use crate::encoder::{detect_encoder_from_read, EncoderGuess};
 pub use crate::error::{ErrorKind, MP3DurationError};
+pub use crate::estimate::{estimate_from_size, from_read_with_size, quick_estimate_from_path};
+pub use crate::header::{find_next_sync, side_information_size, FrameHeader};
+pub use crate::lame::ReplayGain;
+pub use crate::measured::{from_read_with_metadata, MeasuredDuration, Metadata};
+pub use crate::options::ParseOptions;
+#[cfg(feature = "rayon")]
+pub use crate::parallel::measure_many;
+pub use crate::parser::XingInfo;
+pub use crate::scanner::DurationScanner;
+pub use crate::seek_index::{
+    build_seek_index_from_read, build_seek_index_from_read_with_stride, vbri_seek_index_from_read, SeekIndex,
+};
+pub use crate::segments::{segments_from_read, SegmentInfo};
+pub use crate::source::ByteSource;
+pub use crate::tags::{APEv2Handler, ID3v1Handler, ID3v1Tag, ID3v2Handler, TagHandler, TagKind};
+#[cfg(feature = "tar")]
+pub use crate::tarball::from_tar;
+pub use crate::timestamps::frame_timestamps_from_read;
+pub use crate::validation::{validate_from_read, Diagnostic, DiagnosticKind, ValidationReport};
+pub use crate::vbri::VbriInfo;
+#[cfg(feature = "md5")]
+pub use crate::visit::audio_md5_from_read;
+pub use crate::visit::{visit_frames_from_read, FrameView};
+pub use crate::warning::{Warning, WarningKind};
+#[cfg(feature = "zip")]
+pub use crate::zipfile::from_zip;
 
-fn get_bitrate<T: Read>(
-    context: &Context<T>,
-    version: Version,
-    layer: Layer,
-    encoded_bitrate: u8,
-) -> Result<u32, MP3DurationError> {
-    if encoded_bitrate >= 15 {
-        return Err(context.error(ErrorKind::InvalidBitrate {
-            bitrate: encoded_bitrate,
-        }));
-    }
-    if layer == Layer::NotDefined {
-        return Err(context.error(ErrorKind::ForbiddenLayer));
-    }
-    Ok(1000 * BIT_RATES[version as usize][layer as usize][encoded_bitrate as usize])
+/// Measures the duration of a mp3 file contained in any struct implementing Read.
+///
+/// Durations are computed from the exact number of samples in the file, whether they come from a Xing/Info header
+/// or from scanning every frame, and truncated to the nanosecond. They are therefore accurate to within a nanosecond
+/// of the true duration of the samples, way below the period of a single sample.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use std::fs::File;
+/// use std::io::BufReader;
+/// use mp3_duration;
+///
+/// let path = Path::new("test/source.mp3");
+/// let file = File::open(path).unwrap();
+/// let mut reader = BufReader::new(file);
+/// let duration = mp3_duration::from_read(&mut reader).unwrap();
+/// println!("File duration: {:?}", duration);
+/// ```
+///
+/// With the `gzip` feature, gzip-compressed content is decompressed on the fly, here and in `measure_from_read`,
+/// `from_read_with_options` and `from_read_with_tag_handlers` (and so in `from_file`, `from_path`, `from_fd` and
+/// `from_stdin`). Error offsets are then positions in the decompressed stream. The other functions reading from a
+/// struct implementing Read, such as `at_least_from_read` or `probe_from_read`, never decompress their input.
+pub fn from_read<T>(reader: &mut T) -> Result<Duration, MP3DurationError>
+where
+    T: Read,
+{
+    from_read_with_options(reader, &ParseOptions::default())
 }
 
-fn get_sampling_rate<T: Read>(
-    context: &Context<T>,
-    version: Version,
-    encoded_sampling_rate: u8,
-) -> Result<u32, MP3DurationError> {
-    if encoded_sampling_rate >= 3 {
-        return Err(context.error(ErrorKind::InvalidSamplingRate {
-            sampling_rate: encoded_sampling_rate,
-        }));
-    }
-    Ok(SAMPLING_RATES[version as usize][encoded_sampling_rate as usize])
+/// Measures the duration of a mp3 file contained in any struct implementing Read like `from_read`, and returns it
+/// along with the number of bytes consumed from the stream.
+///
+/// This is the whole stream, unless a Xing/Info header declares the duration: reading then stops after the frame
+/// holding the header and the header of the next frame, and the rest of the stream is left unread. For
+/// gzip-compressed content (see `from_read`), this is the number of decompressed bytes.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use std::fs::File;
+/// use std::io::BufReader;
+/// use mp3_duration;
+///
+/// let path = Path::new("test/source.mp3");
+/// let file = File::open(path).unwrap();
+/// let mut reader = BufReader::new(file);
+/// let (duration, bytes_read) = mp3_duration::measure_from_read(&mut reader).unwrap();
+/// println!("File duration: {:?}, measured from {} bytes", duration, bytes_read);
+/// ```
+pub fn measure_from_read<T>(reader: &mut T) -> Result<(Duration, u64), MP3DurationError>
+where
+    T: Read,
+{
+    measure_with_tag_handlers(reader, &ParseOptions::default(), &[])
+}
+
+/// Measures the duration of a mp3 file contained in any struct implementing Read, using custom parsing options.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use std::fs::File;
+/// use std::io::BufReader;
+/// use mp3_duration::{self, ParseOptions};
+///
+/// let path = Path::new("test/source.mp3");
+/// let file = File::open(path).unwrap();
+/// let mut reader = BufReader::new(file);
+/// let options = ParseOptions {
+///     lenient: true,
+///     ..Default::default()
+/// };
+/// let duration = mp3_duration::from_read_with_options(&mut reader, &options).unwrap();
+/// println!("File duration: {:?}", duration);
+/// ```
+pub fn from_read_with_options<T>(
+    reader: &mut T,
+    options: &ParseOptions,
+) -> Result<Duration, MP3DurationError>
+where
+    T: Read,
+{
+    from_read_with_tag_handlers(reader, options, &[])
+}
+
+/// Measures the duration of a mp3 file contained in any struct implementing Read, using custom tag handlers to skip
+/// over blocks this crate does not recognize.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Cursor;
+/// use mp3_duration::{self, ByteSource, ErrorKind, ParseOptions, TagHandler};
+///
+/// // Skips 16-byte blocks starting with "JUNK"
+/// struct JunkHandler;
+///
+/// impl TagHandler for JunkHandler {
+///     fn try_handle(&self, magic: &[u8], source: &mut dyn ByteSource) -> Option<Result<usize, ErrorKind>> {
+///         if magic != b"JUNK" {
+///             return None;
+///         }
+///         Some(source.skip(12).map(|_| 16))
+///     }
+/// }
+///
+/// let mut reader = Cursor::new(b"JUNK------------");
+/// let options = ParseOptions::default();
+/// let duration = mp3_duration::from_read_with_tag_handlers(&mut reader, &options, &[&JunkHandler]).unwrap();
+/// assert_eq!(0, duration.as_secs());
+/// ```
+pub fn from_read_with_tag_handlers<T>(
+    reader: &mut T,
+    options: &ParseOptions,
+    tag_handlers: &[&dyn TagHandler],
+) -> Result<Duration, MP3DurationError>
+where
+    T: Read,
+{
+    measure_with_tag_handlers(reader, options, tag_handlers).map(|(duration, _)| duration)
+}
+
+// Entry point shared by the functions measuring a whole stream read from a struct implementing Read, which returns
+// the duration along with the number of bytes consumed. gzip-compressed content is detected and decompressed here.
+fn measure_with_tag_handlers<T>(
+    reader: &mut T,
+    options: &ParseOptions,
+    tag_handlers: &[&dyn TagHandler],
+) -> Result<(Duration, u64), MP3DurationError>
+where
+    T: Read,
+{
+    #[cfg(feature = "gzip")]
+    let reader = &mut gzip::MaybeGzip::new(reader).map_err(MP3DurationError::at_start)?;
+    let mut parser = Parser::with_tag_handlers(Context::new(reader), options, tag_handlers);
+    let duration = parser.measure()?;
+    Ok((duration, parser.source.bytes_taken()))
+}
+
+/// Measures the duration of a mp3 file contained in any struct implementing Read, using a custom predicate to decide
+/// which frame headers are valid.
+///
+/// `is_valid` replaces the built-in check rejecting combinations of bitrate and channel mode which MPEG-1 Layer II
+/// does not allow (see `FrameHeader::is_allowed_combination`), e.g. to accept frames some encoder wrote anyway or to
+/// reject frames using certain bitrates. Rejected headers are handled like other invalid headers: they are reported
+/// as `ErrorKind::UnexpectedFrame`, or skipped over in lenient mode. Headers which cannot be decoded at all (e.g.
+/// using a forbidden MPEG version) and frames whose length cannot be measured are still rejected beforehand.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use std::fs::File;
+/// use std::io::BufReader;
+/// use mp3_duration::{self, ParseOptions};
+///
+/// let path = Path::new("test/source.mp3");
+/// let file = File::open(path).unwrap();
+/// let mut reader = BufReader::new(file);
+/// let options = ParseOptions {
+///     lenient: true,
+///     ..Default::default()
+/// };
+/// // Only measure frames of at least 64kbps
+/// let duration = mp3_duration::from_read_with_validator(&mut reader, &options, |header| header.bitrate >= 64_000);
+/// println!("File duration: {:?}", duration.unwrap());
+/// ```
+pub fn from_read_with_validator<T, F>(
+    reader: &mut T,
+    options: &ParseOptions,
+    is_valid: F,
+) -> Result<Duration, MP3DurationError>
+where
+    T: Read,
+    F: Fn(&FrameHeader) -> bool,
+{
+    let mut parser = Parser::new(Context::new(reader), options);
+    parser.validator = Some(&is_valid);
+    parser.measure()
 }
 
-fn get_samples_per_frame<T: Read>(
-    context: &Context<T>,
-    version: Version,
-    layer: Layer,
-) -> Result<u32, MP3DurationError> {
-    if layer == Layer::NotDefined {
-        return Err(context.error(ErrorKind::ForbiddenLayer));
+/// Measures the duration of a mp3 file contained in any struct implementing Read, giving up once `deadline` has
+/// passed.
+///
+/// The deadline is checked before each frame or tag. When it has passed, parsing fails with `ErrorKind::Timeout`
+/// and the error reports the duration measured so far.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use std::fs::File;
+/// use std::io::BufReader;
+/// use std::time::{Duration, Instant};
+/// use mp3_duration;
+///
+/// let path = Path::new("test/source.mp3");
+/// let file = File::open(path).unwrap();
+/// let mut reader = BufReader::new(file);
+/// let deadline = Instant::now() + Duration::from_millis(50);
+/// match mp3_duration::from_read_with_deadline(&mut reader, deadline) {
+///     Ok(duration) => println!("File duration: {:?}", duration),
+///     Err(e) => println!("File is at least {:?} long", e.at_duration),
+/// }
+/// ```
+pub fn from_read_with_deadline<T>(reader: &mut T, deadline: Instant) -> Result<Duration, MP3DurationError>
+where
+    T: Read,
+{
+    let options = ParseOptions::default();
+    let mut parser = Parser::new(Context::new(reader), &options);
+    loop {
+        if Instant::now() >= deadline {
+            return Err(parser.error(ErrorKind::Timeout));
+        }
+        if let Progress::Finished(duration) = parser.measure_next_block()? {
+            return Ok(duration);
+        }
     }
-    Ok(SAMPLES_PER_FRAME[version as usize][layer as usize])
 }
 
-fn get_side_information_size(version: Version, mode: Mode) -> usize {
-    SIDE_INFORMATION_SIZES[version as usize][mode as usize] as usize
+/// Checks whether a mp3 file contained in any struct implementing Read lasts at least `threshold`, reading no more of
+/// it than needed.
+///
+/// Reading stops as soon as the frames measured so far add up to `threshold`, or when a Xing/Info header declares the
+/// duration of the whole file. This is much faster than measuring the duration when filtering many files by length.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use std::fs::File;
+/// use std::io::BufReader;
+/// use std::time::Duration;
+/// use mp3_duration;
+///
+/// let path = Path::new("test/source.mp3");
+/// let file = File::open(path).unwrap();
+/// let mut reader = BufReader::new(file);
+/// let is_long = mp3_duration::at_least_from_read(&mut reader, Duration::from_secs(180)).unwrap();
+/// println!("Longer than 3 minutes: {}", is_long);
+/// ```
+pub fn at_least_from_read<T>(reader: &mut T, threshold: Duration) -> Result<bool, MP3DurationError>
+where
+    T: Read,
+{
+    let options = ParseOptions::default();
+    let mut parser = Parser::new(Context::new(reader), &options);
+    loop {
+        if parser.state.duration() >= threshold {
+            return Ok(true);
+        }
+        if let Progress::Finished(duration) = parser.measure_next_block()? {
+            return Ok(duration >= threshold);
+        }
+    }
 }
 
-/// Measures the duration of a mp3 file contained in any struct implementing Read.
+/// Measures the duration of a mp3 file contained in any struct implementing BufRead.
+///
+/// Unlike `from_read`, this scans the internal buffer of the reader directly, which avoids buffering the data a
+/// second time and makes skipping over padding and tags faster.
 ///
 /// # Examples
 ///
@@ -73,170 +365,494 @@ fn get_side_information_size(version: Version, mode: Mode) -> usize {
 /// let path = Path::new("test/source.mp3");
 /// let file = File::open(path).unwrap();
 /// let mut reader = BufReader::new(file);
-/// let duration = mp3_duration::from_read(&mut reader).unwrap();
+/// let duration = mp3_duration::from_buf_read(&mut reader).unwrap();
 /// println!("File duration: {:?}", duration);
 /// ```
-pub fn from_read<T>(reader: &mut T) -> Result<Duration, MP3DurationError>
+pub fn from_buf_read<T>(reader: &mut T) -> Result<Duration, MP3DurationError>
+where
+    T: BufRead,
+{
+    let options = ParseOptions::default();
+    Parser::new(BufContext::new(reader), &options).measure()
+}
+
+/// Measures the duration of a mp3 file held in memory.
+///
+/// # Examples
+///
+/// ```
+/// use mp3_duration;
+///
+/// let data = std::fs::read("test/source.mp3").unwrap();
+/// let duration = mp3_duration::from_bytes(&data).unwrap();
+/// println!("File duration: {:?}", duration);
+/// ```
+pub fn from_bytes(data: &[u8]) -> Result<Duration, MP3DurationError> {
+    let options = ParseOptions::default();
+    Parser::new(SliceSource::new(data, 0, true), &options).measure()
+}
+
+/// Measures the duration of a mp3 file held in memory in two parts, `front` followed by `back`, without copying them
+/// into a contiguous buffer. This fits the two slices returned by `VecDeque::as_slices`.
+///
+/// Data still being buffered usually ends in the middle of a frame, which fails with `ErrorKind::UnexpectedEOF`: the
+/// `at_duration` of the error is then the duration of the complete frames.
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::VecDeque;
+/// use mp3_duration;
+///
+/// let buffer: VecDeque<u8> = std::fs::read("test/source.mp3").unwrap().into();
+/// let (front, back) = buffer.as_slices();
+/// let duration = mp3_duration::from_two_slices(front, back).unwrap();
+/// println!("Buffered duration: {:?}", duration);
+/// ```
+pub fn from_two_slices(front: &[u8], back: &[u8]) -> Result<Duration, MP3DurationError> {
+    from_buf_read(&mut front.chain(back))
+}
+
+/// Measures the duration of a mp3 file held in memory, without ever failing, for untrusted inputs where robustness
+/// matters more than exactness.
+///
+/// Parsing is lenient (see `ParseOptions::lenient`). If it fails anyway, the duration measured up to the error is
+/// returned along with the error. The duration is clamped to how long `data` could possibly last: the duration of the
+/// same number of bytes at 4 kbps, half the lowest bitrate of MPEG audio. This guards against crafted Xing/Info
+/// headers declaring absurd numbers of frames.
+///
+/// # Examples
+///
+/// ```
+/// use mp3_duration;
+///
+/// let data = std::fs::read("test/source.mp3").unwrap();
+/// let (duration, error) = mp3_duration::from_bytes_saturating(&data);
+/// println!("File duration: {:?}", duration);
+/// assert!(error.is_none());
+/// ```
+pub fn from_bytes_saturating(data: &[u8]) -> (Duration, Option<ErrorKind>) {
+    let options = ParseOptions {
+        lenient: true,
+        ..Default::default()
+    };
+    let (duration, error) = match Parser::new(SliceSource::new(data, 0, true), &options).measure() {
+        Ok(duration) => (duration, None),
+        Err(error) => (error.at_duration, Some(error.kind)),
+    };
+    (duration.min(estimate_from_size(data.len() as u64, 4_000)), error)
+}
+
+/// Measures the duration of a mp3 file embedded in a larger stream, such as an asset bundle, which occupies the next
+/// `length` bytes of any struct implementing Read.
+///
+/// The caller is expected to position the reader at the start of the file. Its end is treated like the end of the
+/// stream: data following it is never read, even if it looks like MPEG frames, and a frame crossing it is reported as
+/// `ErrorKind::UnexpectedEOF`.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Cursor;
+/// use mp3_duration;
+///
+/// let file = std::fs::read("test/source.mp3").unwrap();
+/// let mut bundle = vec![0x55; 100];
+/// bundle.extend_from_slice(&file);
+/// bundle.extend_from_slice(&file);
+/// let mut reader = Cursor::new(bundle);
+/// reader.set_position(100);
+/// let duration = mp3_duration::from_read_region(&mut reader, file.len()).unwrap();
+/// assert_eq!(mp3_duration::from_bytes(&file).unwrap(), duration);
+/// ```
+pub fn from_read_region<T>(reader: &mut T, length: usize) -> Result<Duration, MP3DurationError>
 where
     T: Read,
 {
-    let mut header_buffer = [0; 4];
+    from_read(&mut reader.take(length as u64))
+}
 
-    let mut context = Context::new(reader);
+/// Measures the duration of a mp3 file contained in any struct implementing `futures::io::AsyncRead`.
+///
+/// This function is only available with the `async` feature.
+///
+/// # Examples
+///
+/// ```
+/// use mp3_duration;
+///
+/// # futures::executor::block_on(async {
+/// let data = std::fs::read("test/source.mp3").unwrap();
+/// let mut reader = futures::io::Cursor::new(data);
+/// let duration = mp3_duration::from_async_read(&mut reader).await.unwrap();
+/// println!("File duration: {:?}", duration);
+/// # });
+/// ```
+#[cfg(feature = "async")]
+pub async fn from_async_read<T>(reader: &mut T) -> Result<Duration, MP3DurationError>
+where
+    T: futures::io::AsyncRead + Unpin,
+{
+    use futures::io::AsyncReadExt;
 
+    let mut scanner = DurationScanner::new(&ParseOptions::default());
+    let mut buffer = vec![0; 64 * 1024];
     loop {
-        // Skip over all 0x00 bytes (these are probably incorrectly added padding bytes for id3v2)
-        header_buffer[0] = 0;
-        while header_buffer[0] == 0 {
-            match context.read_exact(&mut header_buffer[0..1]) {
-                Ok(_) => (),
-                Err(_) if context.reached_eof() => break,
-                Err(e) => return Err(e),
-            };
+        let num_bytes = match reader.read(&mut buffer).await {
+            Ok(0) => return scanner.finish(),
+            Ok(num_bytes) => num_bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => {
+                return Err(MP3DurationError {
+                    kind: e.into(),
+                    offset: scanner.bytes_fed(),
+                    at_duration: scanner.duration(),
+                    first_header: scanner.first_header(),
+                })
+            }
+        };
+        if let Some(duration) = scanner.feed(&buffer[..num_bytes])? {
+            return Ok(duration);
         }
+    }
+}
 
-        match context.read_exact(&mut header_buffer[1..]) {
-            Ok(_) => (),
-            Err(_) if context.reached_eof() => break,
-            Err(e) => return Err(e),
-        };
+/// Measures the total duration of several mp3 files concatenated in any struct implementing Read.
+///
+/// Unlike `from_read`, Xing/Info headers are not trusted to describe the whole stream: every audio frame is measured
+/// until the end of the stream, and tags found between the concatenated files are skipped.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use std::fs::File;
+/// use std::io::prelude::*;
+/// use std::io::BufReader;
+/// use mp3_duration;
+///
+/// let first = File::open(Path::new("test/source.mp3")).unwrap();
+/// let second = File::open(Path::new("test/source.mp3")).unwrap();
+/// let mut reader = BufReader::new(first.chain(second));
+/// let duration = mp3_duration::from_read_multistream(&mut reader).unwrap();
+/// println!("Total duration: {:?}", duration);
+/// ```
+pub fn from_read_multistream<T>(reader: &mut T) -> Result<Duration, MP3DurationError>
+where
+    T: Read,
+{
+    let options = ParseOptions {
+        trust_first_xing: false,
+        ..Default::default()
+    };
+    Parser::new(Context::new(reader), &options).measure()
+}
 
-        // MPEG frame
-        let header = (header_buffer[0] as u32) << 24
-            | (header_buffer[1] as u32) << 16
-            | (header_buffer[2] as u32) << 8
-            | header_buffer[3] as u32;
-        let is_mp3 = header >> 21 == 0x7FF;
-        if is_mp3 {
-            let version = match (header >> 19) & 0b11 {
-                0 => Version::Mpeg25,
-                1 => return Err(context.error(ErrorKind::ForbiddenVersion)),
-                2 => Version::Mpeg2,
-                3 => Version::Mpeg1,
-                _ => unreachable!(),
-            };
-
-            let layer = match (header >> 17) & 0b11 {
-                0 => Layer::NotDefined,
-                1 => Layer::Layer3,
-                2 => Layer::Layer2,
-                3 => Layer::Layer1,
-                _ => unreachable!(),
-            };
-
-            let encoded_bitrate = (header >> 12) & 0b1111;
-            let encoded_sampling_rate = (header >> 10) & 0b11;
-            let padding = if 0 != ((header >> 9) & 1) { 1 } else { 0 };
-
-            let mode = match (header >> 6) & 0b11 {
-                0 => Mode::Stereo,
-                1 => Mode::JointStereo,
-                2 => Mode::DualChannel,
-                3 => Mode::Mono,
-                _ => unreachable!(),
-            };
-
-            let sampling_rate = get_sampling_rate(&context, version, encoded_sampling_rate as u8)?;
-            let num_samples = get_samples_per_frame(&context, version, layer)?;
-
-            let xing_offset = get_side_information_size(version, mode);
-            let mut xing_buffer = [0; 12];
-
-            context.skip(xing_offset)?;
-            context.read_exact(&mut xing_buffer)?;
-
-            let is_xing = xing_buffer[0] == 'X' as u8
-                && xing_buffer[1] == 'i' as u8
-                && xing_buffer[2] == 'n' as u8
-                && xing_buffer[3] == 'g' as u8;
-            let is_info = xing_buffer[0] == 'I' as u8
-                && xing_buffer[1] == 'n' as u8
-                && xing_buffer[2] == 'f' as u8
-                && xing_buffer[3] == 'o' as u8;
-            if is_xing || is_info {
-                let has_frames = 0 != (xing_buffer[7] & 1);
-                if has_frames {
-                    let num_frames = (xing_buffer[8] as u32) << 24
-                        | (xing_buffer[9] as u32) << 16
-                        | (xing_buffer[10] as u32) << 8
-                        | xing_buffer[11] as u32;
-                    let rate = sampling_rate as u64;
-                    let billion = 1_000_000_000;
-                    let frames_x_samples = num_frames as u64 * num_samples as u64;
-                    let seconds = frames_x_samples / rate;
-                    let nanoseconds = (billion * frames_x_samples) / rate - billion * seconds;
-                    return Ok(Duration::new(seconds, nanoseconds as u32));
-                }
-            }
+/// Measures the duration of a mp3 file received as a stream of byte chunks, such as the body of an HTTP request.
+///
+/// Frames and tags may be split across chunks of any size. The stream is only consumed until the duration is known,
+/// which can be early for files with a Xing/Info header. Errors produced by the stream are reported as
+/// `ErrorKind::IOError`.
+///
+/// This function is only available with the `stream` feature.
+///
+/// # Examples
+///
+/// ```
+/// use mp3_duration;
+///
+/// # futures::executor::block_on(async {
+/// let data = std::fs::read("test/source.mp3").unwrap();
+/// let chunks = data.chunks(1024).map(|chunk| Ok::<_, std::io::Error>(chunk.to_vec()));
+/// let duration = mp3_duration::from_byte_stream(futures::stream::iter(chunks)).await.unwrap();
+/// println!("File duration: {:?}", duration);
+/// # });
+/// ```
+#[cfg(feature = "stream")]
+pub async fn from_byte_stream<S, B, E>(stream: S) -> Result<Duration, MP3DurationError>
+where
+    S: futures::Stream<Item = Result<B, E>>,
+    B: AsRef<[u8]>,
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    use futures::StreamExt;
 
-            let bitrate = get_bitrate(&context, version, layer, encoded_bitrate as u8)?;
-            let frame_length = (num_samples / 8 * bitrate / sampling_rate + padding) as usize;
+    let mut stream = Box::pin(stream);
+    let mut scanner = DurationScanner::new(&ParseOptions::default());
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| MP3DurationError {
+            kind: std::io::Error::other(e).into(),
+            offset: scanner.bytes_fed(),
+            at_duration: scanner.duration(),
+            first_header: scanner.first_header(),
+        })?;
+        if let Some(duration) = scanner.feed(chunk.as_ref())? {
+            return Ok(duration);
+        }
+    }
+    scanner.finish()
+}
 
-            let bytes_to_next_frame = frame_length
-                .checked_sub(header_buffer.len() + xing_offset + xing_buffer.len())
-                .ok_or(context.error(ErrorKind::MPEGFrameTooShort))?;
+/// Measures the duration of a mp3 file both from its Xing/Info header and by scanning all of its frames.
+///
+/// Returns `(xing_duration, scanned_duration)`, which can be compared to detect files with inaccurate headers. The
+/// reader is seeked back to its initial position between the two measurements. Files without a Xing/Info header
+/// declaring a frame count fail with `ErrorKind::NoXingHeader`.
+///
+/// `from_read_with_metadata` with `ParseOptions::verify_xing` performs both measurements in a single pass, without
+/// requiring Seek.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use std::fs::File;
+/// use std::io::BufReader;
+/// use mp3_duration;
+///
+/// let path = Path::new("test/VBR0.mp3");
+/// let file = File::open(path).unwrap();
+/// let mut reader = BufReader::new(file);
+/// let (xing, scanned) = mp3_duration::cross_check_from_seek(&mut reader).unwrap();
+/// println!("Xing header is off by {:?}", if xing > scanned { xing - scanned } else { scanned - xing });
+/// ```
+pub fn cross_check_from_seek<T>(reader: &mut T) -> Result<(Duration, Duration), MP3DurationError>
+where
+    T: Read + Seek,
+{
+    let (xing_duration, scanned_duration, _) = cross_check_with_header(reader)?;
+    Ok((xing_duration, scanned_duration))
+}
 
-            context.skip(bytes_to_next_frame)?;
+/// Checks whether the duration declared by the Xing/Info header of a mp3 file contained in any struct implementing
+/// Read and Seek matches the duration of its frames, measured like `cross_check_from_seek`.
+///
+/// Durations differing by up to one frame are taken to match, as encoders disagree on whether the frame holding the
+/// header is part of the declared frame count. Files without a Xing/Info header declaring a frame count fail with
+/// `ErrorKind::NoXingHeader`.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use std::fs::File;
+/// use std::io::BufReader;
+/// use mp3_duration;
+///
+/// let path = Path::new("test/VBR0.mp3");
+/// let file = File::open(path).unwrap();
+/// let mut reader = BufReader::new(file);
+/// if !mp3_duration::xing_is_accurate_from_seek(&mut reader).unwrap() {
+///     println!("The Xing header of {:?} should be rewritten", path);
+/// }
+/// ```
+pub fn xing_is_accurate_from_seek<T>(reader: &mut T) -> Result<bool, MP3DurationError>
+where
+    T: Read + Seek,
+{
+    let (xing_duration, scanned_duration, header) = cross_check_with_header(reader)?;
+    Ok(xing_duration.abs_diff(scanned_duration) < header.duration() * 3 / 2)
+}
 
-            let frame_duration = (num_samples as u64 * 1_000_000_000) / (sampling_rate as u64);
-            context.duration += Duration::new(0, frame_duration as u32);
+// Same as `cross_check_from_seek`, also returning the header of the first audio frame
+fn cross_check_with_header<T>(reader: &mut T) -> Result<(Duration, Duration, FrameHeader), MP3DurationError>
+where
+    T: Read + Seek,
+{
+    let start = reader
+        .stream_position()
+        .map_err(|e| MP3DurationError::at_start(e.into()))?;
 
-            continue;
-        }
+    let probe = probe_from_read(reader)?;
+    let xing_duration = probe
+        .xing_duration
+        .ok_or_else(|| MP3DurationError::at_start(ErrorKind::NoXingHeader))?;
 
-        // ID3v2 frame
-        let is_id3v2 = header_buffer[0] == 'I' as u8
-            && header_buffer[1] == 'D' as u8
-            && header_buffer[2] == '3' as u8;
-        if is_id3v2 {
-            let mut id3v2 = [0; 6]; // 4 bytes already read
-            context.read_exact(&mut id3v2)?;
-            let flags = id3v2[1];
-            let footer_size: usize = if 0 != (flags & 0b0001_0000) { 10 } else { 0 };
-            let tag_size: usize = ((id3v2[5] as u32)
-                | ((id3v2[4] as u32) << 7)
-                | ((id3v2[3] as u32) << 14)
-                | ((id3v2[2] as u32) << 21)) as usize;
-            context.skip(tag_size + footer_size)?;
-            continue;
-        }
+    reader
+        .seek(SeekFrom::Start(start))
+        .map_err(|e| MP3DurationError::at_start(e.into()))?;
+    let options = ParseOptions {
+        trust_first_xing: false,
+        ..Default::default()
+    };
+    let scanned_duration = Parser::new(Context::new(reader), &options).measure()?;
 
-        // ID3v1 frame
-        let is_id3v1 = header_buffer[0] == 'T' as u8
-            && header_buffer[1] == 'A' as u8
-            && header_buffer[2] == 'G' as u8;
-        if is_id3v1 {
-            context.skip(128 - header_buffer.len())?;
-            continue;
-        }
+    Ok((xing_duration, scanned_duration, probe.header))
+}
 
-        // APEv2 frame
-        let maybe_is_ape_v2 = header_buffer[0] == 'A' as u8
-            && header_buffer[1] == 'P' as u8
-            && header_buffer[2] == 'E' as u8
-            && header_buffer[3] == 'T' as u8;
-        if maybe_is_ape_v2 {
-            let mut ape_header = [0; 12];
-            context.read_exact(&mut ape_header)?;
-            let is_really_ape_v2 = ape_header[0] == 'A' as u8
-                && ape_header[1] == 'G' as u8
-                && ape_header[2] == 'E' as u8
-                && ape_header[3] == 'X' as u8;
-            if is_really_ape_v2 {
-                let tag_size: usize = ((ape_header[8] as u32)
-                    | ((ape_header[9] as u32) << 8)
-                    | ((ape_header[10] as u32) << 16)
-                    | ((ape_header[11] as u32) << 24))
-                    as usize;
-                context.skip(tag_size + 16)?;
-                continue;
-            }
-        }
+/// Reads and decodes the header of the frame starting at `offset` in any struct implementing Read and Seek, e.g. to
+/// check an entry of a seek index. The length of the frame is available through `FrameHeader::frame_length`.
+///
+/// Fails with `ErrorKind::UnexpectedFrame` if there is no frame header at `offset`. Errors are located at `offset`.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use std::fs::File;
+/// use mp3_duration;
+///
+/// let path = Path::new("test/source.mp3");
+/// let mut file = File::open(path).unwrap();
+/// let probe = mp3_duration::probe_from_read(&mut file).unwrap();
+/// let header = mp3_duration::parse_frame_at(&mut file, probe.offset).unwrap();
+/// println!("First frame is {} bytes long", header.frame_length());
+/// ```
+pub fn parse_frame_at<T>(reader: &mut T, offset: u64) -> Result<FrameHeader, MP3DurationError>
+where
+    T: Read + Seek,
+{
+    let error = |kind| MP3DurationError {
+        kind,
+        offset,
+        at_duration: Duration::from_secs(0),
+        first_header: None,
+    };
+    reader.seek(SeekFrom::Start(offset)).map_err(|e| error(e.into()))?;
+    let mut header_buffer = [0; 4];
+    Context::new(reader).read_exact(&mut header_buffer).map_err(error)?;
+    let header = u32::from_be_bytes(header_buffer);
+    if header >> 21 != 0x7FF {
+        return Err(error(ErrorKind::UnexpectedFrame { header }));
+    }
+    FrameHeader::parse(header).map_err(error)
+}
 
-        return Err(context.error(ErrorKind::UnexpectedFrame { header }));
+/// Information about a mp3 file, gathered without scanning its entire content.
+#[derive(Clone, Debug)]
+pub struct Probe {
+    /// Position of the first audio frame in the stream, which is also the size of the tags preceding it.
+    pub offset: u64,
+    /// Header of the first audio frame.
+    pub header: FrameHeader,
+    /// Whether the first audio frame contains a Xing/Info header.
+    pub has_xing: bool,
+    /// Content of the Xing/Info header of the first audio frame, if any.
+    pub xing: Option<XingInfo>,
+    /// Duration of the file as declared by its Xing/Info header, if available.
+    pub xing_duration: Option<Duration>,
+    /// ReplayGain values found in the LAME extension of the Xing/Info header, if any.
+    pub replay_gain: Option<ReplayGain>,
+    /// Content of the VBRI header of the first audio frame, if any.
+    pub vbri: Option<VbriInfo>,
+}
+
+/// Reads the tags at the start of a mp3 file contained in any struct implementing Read, and stops after the first
+/// audio frame header.
+///
+/// When the file has a Xing/Info header, this is enough to know its duration. Otherwise, only the format of the
+/// first frame is reported and a full scan (e.g. `from_read`) is needed to measure the duration.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use std::fs::File;
+/// use std::io::BufReader;
+/// use mp3_duration;
+///
+/// let path = Path::new("test/source.mp3");
+/// let file = File::open(path).unwrap();
+/// let mut reader = BufReader::new(file);
+/// let probe = mp3_duration::probe_from_read(&mut reader).unwrap();
+/// println!("Sampling rate: {}Hz", probe.header.sampling_rate.hz());
+/// ```
+pub fn probe_from_read<T>(reader: &mut T) -> Result<Probe, MP3DurationError>
+where
+    T: Read,
+{
+    let options = ParseOptions::default();
+    let mut parser = Parser::new(Context::new(reader), &options);
+
+    while let Some(block) = parser.next_block()? {
+        if let Block::Frame(frame) = block {
+            return Ok(Probe {
+                offset: frame.offset,
+                header: frame.header,
+                has_xing: frame.xing.is_some(),
+                xing_duration: frame.xing_duration(),
+                replay_gain: frame.lame.and_then(|lame| lame.replay_gain),
+                xing: frame.xing,
+                vbri: frame.vbri,
+            });
+        }
     }
 
-    Ok(context.duration)
+    Err(parser.error(ErrorKind::UnexpectedEOF))
+}
+
+/// Measures the duration of a mp3 stream read from a Unix file descriptor, such as a pipe.
+///
+/// The descriptor is duplicated, so it stays open and owned by the caller, but the data read from it is consumed. The
+/// stream is read sequentially like with `from_read`: as it may not be seekable, functions relying on seeking or on
+/// the size of the file (e.g. `quick_estimate_from_path`) have no equivalent for file descriptors.
+///
+/// # Examples
+///
+/// ```
+/// use std::process::{Command, Stdio};
+/// use std::os::unix::io::AsFd;
+/// use mp3_duration;
+///
+/// let mut child = Command::new("cat").arg("test/source.mp3").stdout(Stdio::piped()).spawn().unwrap();
+/// let duration = mp3_duration::from_fd(child.stdout.as_ref().unwrap().as_fd()).unwrap();
+/// println!("File duration: {:?}", duration);
+/// # child.wait().unwrap();
+/// ```
+#[cfg(unix)]
+pub fn from_fd(fd: std::os::unix::io::BorrowedFd) -> Result<Duration, MP3DurationError> {
+    let file = fd
+        .try_clone_to_owned()
+        .map(File::from)
+        .map_err(|e| MP3DurationError::at_start(e.into()))?;
+    from_read(&mut BufReader::new(file))
+}
+
+/// Measures the duration of a mp3 stream read from the standard input, e.g. when data is piped into a command line
+/// tool.
+///
+/// The standard input is buffered and read sequentially until the duration is known, which can be early for files
+/// with a Xing/Info header: the rest of the stream is then left unread. With the `gzip` feature, gzip-compressed
+/// content is decompressed on the fly.
+///
+/// # Examples
+///
+/// ```no_run
+/// use mp3_duration;
+///
+/// let duration = mp3_duration::from_stdin().unwrap();
+/// println!("Duration: {:?}", duration);
+/// ```
+pub fn from_stdin() -> Result<Duration, MP3DurationError> {
+    let stdin = std::io::stdin();
+    let mut reader = stdin.lock();
+    from_read(&mut reader)
+}
+
+/// Measures the duration of a file, buffering reads into `buffer` instead of allocating a buffer.
+///
+/// Reusing the same buffer to measure many small files avoids allocating memory for each of them. Unlike `from_file`,
+/// gzip-compressed files are never decompressed.
+///
+/// # Panics
+///
+/// Panics if `buffer` is empty.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs::File;
+/// use mp3_duration;
+///
+/// let mut buffer = [0; 4096];
+/// for path in &["test/source.mp3", "test/ID3v2.mp3"] {
+///     let file = File::open(path).unwrap();
+///     let duration = mp3_duration::from_file_with_buffer(&file, &mut buffer).unwrap();
+///     println!("{}: {:?}", path, duration);
+/// }
+/// ```
+pub fn from_file_with_buffer(file: &File, buffer: &mut [u8]) -> Result<Duration, MP3DurationError> {
+    from_buf_read(&mut ScratchReader::new(file, buffer))
 }
 
 /// Measures the duration of a file.
@@ -253,6 +869,9 @@ where
 /// let duration = mp3_duration::from_file(&file).unwrap();
 /// println!("File duration: {:?}", duration);
 /// ```
+///
+/// With the `gzip` feature, gzip-compressed files are decompressed on the fly. Error offsets are then positions in
+/// the decompressed stream.
 pub fn from_file(file: &File) -> Result<Duration, MP3DurationError> {
     let mut reader = BufReader::new(file);
     from_read(&mut reader)
@@ -270,15 +889,35 @@ pub fn from_file(file: &File) -> Result<Duration, MP3DurationError> {
 /// let duration = mp3_duration::from_path(&path).unwrap();
 /// println!("File duration: {:?}", duration);
 /// ```
+///
+/// With the `gzip` feature, gzip-compressed files are decompressed on the fly (see `from_file`).
 pub fn from_path<P>(path: P) -> Result<Duration, MP3DurationError>
 where
     P: AsRef<Path>,
 {
     File::open(path)
-        .map_err(|e| MP3DurationError {
-            kind: e.into(),
-            offset: 0,
-            at_duration: Duration::from_secs(0),
-        })
+        .map_err(|e| MP3DurationError::at_start(e.into()))
         .and_then(|file| from_file(&file))
 }
+
+/// Checks whether a file lasts at least `threshold`, reading no more of it than needed (see `at_least_from_read`).
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use std::time::Duration;
+/// use mp3_duration;
+///
+/// let path = Path::new("test/source.mp3");
+/// let is_long = mp3_duration::at_least_from_path(&path, Duration::from_secs(180)).unwrap();
+/// println!("Longer than 3 minutes: {}", is_long);
+/// ```
+pub fn at_least_from_path<P>(path: P, threshold: Duration) -> Result<bool, MP3DurationError>
+where
+    P: AsRef<Path>,
+{
+    File::open(path)
+        .map_err(|e| MP3DurationError::at_start(e.into()))
+        .and_then(|file| at_least_from_read(&mut BufReader::new(file), threshold))
+}
@@ -0,0 +1,30 @@
+// Logging macros forwarding to the `log` crate when the `log` feature is enabled. Otherwise, messages are type-checked
+// but never formatted.
+
+#[cfg(feature = "log")]
+macro_rules! debug {
+	($($arg:tt)+) => { log::debug!($($arg)+) };
+}
+
+#[cfg(not(feature = "log"))]
+macro_rules! debug {
+	($($arg:tt)+) => {{
+		if false {
+			let _ = format_args!($($arg)+);
+		}
+	}};
+}
+
+#[cfg(feature = "log")]
+macro_rules! trace {
+	($($arg:tt)+) => { log::trace!($($arg)+) };
+}
+
+#[cfg(not(feature = "log"))]
+macro_rules! trace {
+	($($arg:tt)+) => {{
+		if false {
+			let _ = format_args!($($arg)+);
+		}
+	}};
+}
@@ -0,0 +1,331 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io::prelude::*;
+use std::ops::Deref;
+use std::time::Duration;
+
+use crate::constants::{layer_name, version_name, Mode};
+use crate::context::Context;
+use crate::duration::DurationAccumulator;
+use crate::error::MP3DurationError;
+use crate::header::FrameHeader;
+use crate::lame::ReplayGain;
+use crate::options::ParseOptions;
+use crate::parser::{Measured, Parser, Progress};
+use crate::source::{ByteSource, SourceExt};
+use crate::tags::ID3v1Tag;
+use crate::warning::Warning;
+
+/// Details about how a duration was measured.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Metadata {
+	/// Whether the duration was declared by a Xing/Info header, as opposed to measured by scanning every frame.
+	pub from_xing_header: bool,
+	/// Number of audio frames, as declared by the Xing/Info header or counted while scanning. Files which only contain
+	/// tags have none, and a duration of zero.
+	pub audio_frames: u64,
+	/// Number of audio frames declared by the Xing/Info header, if any.
+	pub xing_frames: Option<u64>,
+	/// Duration declared by the Xing/Info header, if any. This is the measured duration unless the header is not
+	/// trusted (see `ParseOptions::scan_past_xing`).
+	pub xing_duration: Option<Duration>,
+	/// Whether the stream ends right after the Xing/Info frame, although its header declares audio frames. The
+	/// declared duration is then not backed by any audio, as in placeholder files (or files truncated after it).
+	pub only_xing_frame: bool,
+	/// Number of audio frames counted by scanning the stream, which is only done when there is no Xing/Info header
+	/// declaring the duration, or with `ParseOptions::verify_xing` or `ParseOptions::scan_past_xing`.
+	pub scanned_frames: Option<u64>,
+	/// Duration measured by scanning the stream, see `scanned_frames`.
+	pub scanned_duration: Option<Duration>,
+	/// Number of scanned frames (see `scanned_frames`) with their padding bit set, which lengthens them by one slot.
+	/// Constant bitrate encoders pad some frames to keep the average bitrate exact, in a pattern typical of each one.
+	pub padded_frames: Option<u64>,
+	/// ReplayGain values found in the LAME extension of the Xing/Info header, if any.
+	pub replay_gain: Option<ReplayGain>,
+	/// Number of samples of silence added by the encoder before the audio (priming), as declared by the LAME
+	/// extension of the Xing/Info header, if any.
+	pub encoder_delay: Option<u32>,
+	/// Number of samples of silence added by the encoder after the audio to fill the last frame, as declared by the
+	/// LAME extension of the Xing/Info header, if any.
+	pub encoder_padding: Option<u32>,
+	/// Channel mode of the first audio frame, if any. Dual channel streams (`Mode::DualChannel`) carry two independent
+	/// mono programs, such as two languages, rather than the left and right channels of a stereo program.
+	pub mode: Option<Mode>,
+	/// Header of the first frame, if any, which tells the format of the stream.
+	pub first_header: Option<FrameHeader>,
+	/// Whether the bitrate of the stream varies, if known. This is declared by Xing/Info headers (see
+	/// `XingInfo::is_vbr`), or found while scanning the audio frames.
+	pub variable_bitrate: Option<bool>,
+	/// Average bitrate of the audio in bits per second, if known: this is computed from the size of the stream declared
+	/// by the Xing/Info header, or from the size of the scanned audio frames.
+	pub average_bitrate: Option<u32>,
+	/// Lowest bitrate of the scanned frames (see `scanned_frames`) in bits per second, if any. Files whose duration is
+	/// declared by a Xing/Info header are only scanned with `ParseOptions::verify_xing`, or when
+	/// `ParseOptions::trust_first_xing` is unset.
+	pub min_bitrate: Option<u32>,
+	/// Highest bitrate of the scanned frames in bits per second, if any, see `min_bitrate`.
+	pub max_bitrate: Option<u32>,
+	/// Number of bytes read to measure the duration.
+	pub bytes_read: u64,
+	/// Anomalies which were worked around.
+	pub warnings: Vec<Warning>,
+	/// Items of the APEv2 tags, by key, when `ParseOptions::parse_ape_items` is set. Values are UTF-8 text or binary
+	/// data, depending on the flags of each item. When several tags have an item with the same key, the first one is
+	/// kept.
+	pub ape_items: HashMap<String, Vec<u8>>,
+	/// Fields of the ID3v1 tag, when `ParseOptions::parse_id3v1` is set and the stream has one.
+	pub id3v1: Option<ID3v1Tag>,
+	/// Whether an ID3v2 tag has an attached picture, such as a front cover, when `ParseOptions::detect_album_art` is
+	/// set.
+	pub has_album_art: bool,
+	/// Total size in bytes of the pictures attached to ID3v2 tags (see `has_album_art`), including their MIME type and
+	/// description.
+	pub album_art_bytes: u64,
+}
+
+impl Metadata {
+	/// Whether the stream is made of two independent mono programs (see `mode`).
+	pub fn is_dual_channel(&self) -> bool {
+		self.mode == Some(Mode::DualChannel)
+	}
+}
+
+// Totals over the audio frames which were scanned
+#[derive(Default)]
+struct ScanStats {
+	frames: u64,
+	padded_frames: u64,
+	bytes: u64,
+	// Bitrate of the first frame
+	bitrate: Option<u32>,
+	variable_bitrate: bool,
+	min_bitrate: Option<u32>,
+	max_bitrate: Option<u32>,
+}
+
+impl ScanStats {
+	fn add(&mut self, header: &FrameHeader) {
+		self.frames += 1;
+		self.padded_frames += header.padding as u64;
+		self.bytes += header.frame_length() as u64;
+		let bitrate = *self.bitrate.get_or_insert(header.bitrate);
+		self.variable_bitrate |= bitrate != header.bitrate;
+		self.min_bitrate = Some(self.min_bitrate.map_or(header.bitrate, |min| min.min(header.bitrate)));
+		self.max_bitrate = Some(self.max_bitrate.map_or(header.bitrate, |max| max.max(header.bitrate)));
+	}
+}
+
+// Bitrate in bits per second of `bytes` of audio playing for `duration`
+fn average_bitrate(bytes: u64, duration: Duration) -> Option<u32> {
+	if duration.is_zero() {
+		return None;
+	}
+	Some((bytes as f64 * 8.0 / duration.as_secs_f64()).round() as u32)
+}
+
+fn mode_name(mode: Mode) -> &'static str {
+	match mode {
+		Mode::Stereo => "stereo",
+		Mode::JointStereo => "joint stereo",
+		Mode::DualChannel => "dual channel",
+		Mode::Mono => "mono",
+	}
+}
+
+/// Duration of a mp3 file along with details about how it was measured.
+///
+/// It dereferences to the `Duration`, so that it can mostly be used like one. It is not a drop-in replacement though
+/// (e.g. it cannot be compared to or stored as a `Duration` without conversion), which is why `from_read` and the
+/// other entry points keep returning a plain `Duration`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MeasuredDuration {
+	duration: Duration,
+	content_duration: Duration,
+	metadata: Metadata,
+}
+
+impl MeasuredDuration {
+	/// The measured duration.
+	pub fn duration(&self) -> Duration {
+		self.duration
+	}
+
+	/// Duration of the audio content, which excludes the silence added by the encoder at the start and end of the
+	/// stream (see `Metadata::encoder_delay` and `Metadata::encoder_padding`), as gapless players do.
+	///
+	/// This is the same as `duration` for files without a LAME extension in their Xing/Info header.
+	pub fn content_duration(&self) -> Duration {
+		self.content_duration
+	}
+
+	/// Details about how the duration was measured.
+	pub fn metadata(&self) -> &Metadata {
+		&self.metadata
+	}
+}
+
+impl Deref for MeasuredDuration {
+	type Target = Duration;
+
+	fn deref(&self) -> &Duration {
+		&self.duration
+	}
+}
+
+/// Summary of the stream on a single line, such as
+/// `3:58, MPEG-1 Layer III, 44100 Hz, joint stereo, VBR ~256 kbps (Xing)`.
+impl fmt::Display for MeasuredDuration {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let seconds = self.duration.as_secs();
+		let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+		if hours > 0 {
+			write!(f, "{}:{:02}:{:02}", hours, minutes, seconds)?;
+		} else {
+			write!(f, "{}:{:02}", minutes, seconds)?;
+		}
+		let metadata = &self.metadata;
+		if let Some(header) = &metadata.first_header {
+			write!(f, ", {} {}", version_name(header.version), layer_name(header.layer))?;
+			write!(f, ", {} Hz, {}", header.sampling_rate.hz(), mode_name(header.mode))?;
+		}
+		match (metadata.variable_bitrate, metadata.average_bitrate) {
+			(Some(true), Some(bitrate)) => write!(f, ", VBR ~{} kbps", (bitrate + 500) / 1000)?,
+			(Some(true), None) => write!(f, ", VBR")?,
+			(Some(false), Some(bitrate)) => write!(f, ", CBR {} kbps", (bitrate + 500) / 1000)?,
+			(Some(false), None) => write!(f, ", CBR")?,
+			(None, _) => (),
+		}
+		if metadata.from_xing_header {
+			write!(f, " (Xing)")?;
+		}
+		Ok(())
+	}
+}
+
+impl From<MeasuredDuration> for Duration {
+	fn from(measured: MeasuredDuration) -> Self {
+		measured.duration
+	}
+}
+
+/// Measures the duration of a mp3 file contained in any struct implementing Read like `from_read_with_options`, and
+/// reports details about how it was measured.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use std::fs::File;
+/// use std::io::BufReader;
+/// use mp3_duration::{self, ParseOptions};
+///
+/// let path = Path::new("test/VBR0.mp3");
+/// let file = File::open(path).unwrap();
+/// let mut reader = BufReader::new(file);
+/// let measured = mp3_duration::from_read_with_metadata(&mut reader, &ParseOptions::default()).unwrap();
+/// println!("File duration: {}s", measured.as_secs_f64());
+/// println!("Declared by a Xing/Info header: {}", measured.metadata().from_xing_header);
+/// ```
+pub fn from_read_with_metadata<T>(reader: &mut T, options: &ParseOptions) -> Result<MeasuredDuration, MP3DurationError>
+where
+	T: Read,
+{
+	// Streams are scanned past a trusted Xing/Info header to verify it
+	let scan_options = ParseOptions {
+		scan_past_xing: options.scan_past_xing || options.verify_xing,
+		..options.clone()
+	};
+	let mut parser = Parser::new(Context::new(reader), &scan_options);
+	let mut metadata = Metadata::default();
+	// Number of samples added by the encoder, and their sampling rate
+	let mut encoder_samples = None;
+	// Duration declared by the first Xing/Info header, and the samples it is made of
+	let mut declared: Option<(Duration, DurationAccumulator)> = None;
+	// End of the frame holding this header
+	let mut xing_frame_end = None;
+	let mut scanned = ScanStats::default();
+
+	let scanned_duration = loop {
+		let progress = parser.measure_next_block_with(|_, block| {
+			let measured = match block {
+				Measured::Frame(measured) => measured,
+				Measured::Tag(_) => return,
+			};
+			let frame = measured.frame;
+			metadata.first_header = metadata.first_header.or(Some(frame.header));
+			metadata.mode = metadata.mode.or(Some(frame.header.mode));
+			if let Some(lame) = &frame.lame {
+				metadata.replay_gain = metadata.replay_gain.or(lame.replay_gain);
+				if encoder_samples.is_none() {
+					metadata.encoder_delay = Some(lame.encoder_delay as u32);
+					metadata.encoder_padding = Some(lame.encoder_padding as u32);
+					let samples = lame.encoder_delay as u32 + lame.encoder_padding as u32;
+					encoder_samples = Some((samples, frame.header.sampling_rate.hz()));
+				}
+			}
+			let xing_duration = frame.xing_duration_with(options.count_info_frame);
+			if declared.is_none() && options.trust_first_xing && xing_duration.is_some() {
+				let frames = frame.xing.as_ref().and_then(|xing| xing.frames).unwrap_or_default() as u64;
+				metadata.xing_frames = Some(frames);
+				if let Some(duration) = measured.declared {
+					let xing = frame.xing.as_ref().unwrap();
+					metadata.variable_bitrate = Some(xing.is_vbr);
+					metadata.average_bitrate = xing.bytes.and_then(|bytes| average_bitrate(bytes as u64, duration));
+					metadata.xing_duration = Some(duration);
+					let mut samples = DurationAccumulator::default();
+					let frames = frames + options.count_info_frame as u64;
+					samples.add(frames * frame.header.samples_per_frame() as u64, frame.header.sampling_rate.hz());
+					declared = Some((duration, samples));
+					xing_frame_end = Some(frame.offset + frame.header.frame_length() as u64);
+				}
+			}
+			if measured.complete && measured.is_audio {
+				scanned.add(&frame.header);
+			}
+		})?;
+		if let Progress::Finished(duration) = progress {
+			break duration;
+		}
+	};
+	if let Some(frame_end) = xing_frame_end {
+		metadata.only_xing_frame = metadata.xing_frames.unwrap_or_default() > 0
+			&& parser.source.bytes_read() == frame_end
+			&& parser.source.at_eof().map_err(|e| parser.error(e))?;
+	}
+
+	if declared.is_none() || options.verify_xing || options.scan_past_xing {
+		metadata.scanned_frames = Some(scanned.frames);
+		metadata.scanned_duration = Some(scanned_duration);
+		metadata.padded_frames = Some(scanned.padded_frames);
+		metadata.min_bitrate = scanned.min_bitrate;
+		metadata.max_bitrate = scanned.max_bitrate;
+	}
+	let (duration, mut samples) = match declared {
+		Some((duration, samples)) if !options.scan_past_xing => {
+			metadata.from_xing_header = true;
+			metadata.audio_frames = metadata.xing_frames.unwrap_or_default();
+			(duration, samples)
+		}
+		_ => {
+			metadata.audio_frames = scanned.frames;
+			metadata.variable_bitrate = scanned.bitrate.map(|_| scanned.variable_bitrate);
+			metadata.average_bitrate = average_bitrate(scanned.bytes, scanned_duration);
+			(scanned_duration, parser.state.measured.clone())
+		}
+	};
+	if let Some((encoder_samples, sampling_rate)) = encoder_samples {
+		samples.remove(encoder_samples as u64, sampling_rate);
+	}
+	let content_duration = samples.duration();
+	metadata.bytes_read = parser.source.bytes_read();
+	metadata.warnings = parser.state.warnings;
+	metadata.ape_items = parser.state.ape_items;
+	metadata.id3v1 = parser.state.id3v1;
+	metadata.has_album_art = !parser.state.pictures.is_empty();
+	metadata.album_art_bytes = parser.state.pictures.iter().map(|size| *size as u64).sum();
+	Ok(MeasuredDuration {
+		duration,
+		content_duration,
+		metadata,
+	})
+}
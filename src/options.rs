@@ -0,0 +1,145 @@
+use crate::header::FrameHeader;
+
+/// Settings controlling how strictly the mp3 stream is parsed.
+///
+/// The default options reproduce the behavior of `from_read`: any unexpected data is reported as an error, Xing/Info
+/// headers are trusted and tags are skipped. Options added in the future will default to this behavior, so that
+/// `ParseOptions { ..Default::default() }` keeps measuring the same durations.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ParseOptions {
+	/// When set, unrecognized or invalid data between frames is skipped over by scanning forward for the next
+	/// plausible frame or tag instead of aborting with an error. This includes the end of tags whose declared size is
+	/// too small, as written by some tools for ID3v2 tags holding chapters with embedded images.
+	///
+	/// Frames which are too short to be valid (`ErrorKind::MPEGFrameTooShort`) are skipped over the same way and do
+	/// not count towards the duration. A frame cut short by the end of the stream ends parsing, and the duration
+	/// measured up to that point is returned.
+	pub lenient: bool,
+	/// When set, frames using the reserved emphasis value are rejected with `ErrorKind::ReservedEmphasis`.
+	pub strict_emphasis: bool,
+	/// Largest tag size accepted, in bytes. Tags declaring a larger size are rejected with `ErrorKind::TagTooLarge`
+	/// instead of being skipped. Defaults to 256 MiB.
+	pub max_tag_bytes: usize,
+	/// When set, unrecognized data found after audio frames is taken as a trailer (such as an embedded cue sheet)
+	/// running until the end of the stream. It is skipped over and the duration measured so far is returned.
+	///
+	/// This takes precedence over `lenient` for unrecognized data, but not for invalid frame headers. Unrecognized
+	/// data before the first audio frame is still reported as an error.
+	pub skip_trailing_unknown: bool,
+	/// Number of consecutive frames which must line up, each starting exactly where the previous one ends, before the
+	/// first frame of the stream is trusted. Otherwise, the first sync word is rejected with
+	/// `ErrorKind::UnexpectedFrame`, which helps telling apart mp3 data from other data containing sync-like bytes.
+	///
+	/// A chain of frames ending with the stream, a tag or padding is accepted. Values of 0 and 1 (the default) disable
+	/// the check.
+	pub min_sync_frames: usize,
+	/// Longest run of 0x00 bytes skipped before a frame or tag (such as padding after an ID3v2 tag). Longer runs are
+	/// rejected with `ErrorKind::UnexpectedFrame`, even in lenient mode, so that large inputs made of zeros are not
+	/// scanned to the end. Defaults to `usize::MAX` (no limit), like `from_read`: set it to a few KiB when parsing
+	/// untrusted input.
+	pub max_leading_zeros: usize,
+	/// Format of the stream when it is known beforehand, such as for raw fragments starting in the middle of a frame.
+	/// Only frames with the same MPEG version, layer and sampling rate are accepted (see
+	/// `FrameHeader::has_same_format`), and data preceding the first of them is skipped over even in strict mode.
+	pub assume_format: Option<FrameHeader>,
+	/// When set, streams without any audio frame (such as files only containing tags) are rejected with
+	/// `ErrorKind::NoAudioFrames` instead of measuring a duration of zero.
+	pub require_audio: bool,
+	/// When set, the frame holding the Xing/Info header is counted as part of the duration, as it is part of the
+	/// stream's timeline even though it only contains silence. Otherwise (the default), only the audio frames
+	/// declared by the header are counted, which is how most players report the duration.
+	///
+	/// When Xing/Info headers are not trusted and every frame is measured, this also decides whether such frames are
+	/// measured.
+	pub count_info_frame: bool,
+	/// When set, streams whose duration is declared by a Xing/Info header are scanned until the end anyway, so that
+	/// the declared and scanned frame counts and durations can be compared, e.g. to find files with bad headers. The
+	/// declared duration is still the one returned.
+	///
+	/// Both measurements are only reported by `from_read_with_metadata` (see `Metadata::scanned_frames`), other entry
+	/// points ignore this option.
+	pub verify_xing: bool,
+	/// When set, streams with a Xing/Info header are scanned until the end, and the duration of their frames is
+	/// returned instead of the declared one. Unlike `trust_first_xing`, the header is still parsed, so that
+	/// `from_read_with_metadata` reports both durations and frame counts (see `Metadata::xing_duration` and
+	/// `Metadata::scanned_frames`) to validate the header without seeking. This takes precedence over `verify_xing`.
+	pub scan_past_xing: bool,
+	/// When set, ID3v1, ID3v2, APEv2 and Lyrics3 tags (as well as blocks recognized by custom tag handlers) are
+	/// rejected with `ErrorKind::UnexpectedTag` instead of being skipped, to check that a stream only contains MPEG
+	/// frames.
+	pub reject_tags: bool,
+	/// When set, the CRC of Layer III frames which have one is checked against their header and side information. A
+	/// mismatch is reported as `ErrorKind::CrcMismatch`, or as `WarningKind::CrcMismatch` in lenient mode, where the
+	/// frame is still measured. The CRC of Layer I and II frames is not checked.
+	pub validate_crc: bool,
+	/// When set (the default), parsing stops at the first Xing/Info header, whose declared duration is returned without
+	/// reading the rest of the stream. This is the fastest way to measure files with such a header, and stays the
+	/// default even for files with trailing data that is not covered by the header.
+	///
+	/// Otherwise, every audio frame is measured until the end of the stream and Xing/Info frames are not counted
+	/// (unless `count_info_frame` is set), like `from_read_multistream` does for concatenated files. `verify_xing`
+	/// then has no effect. Entry points dedicated to concatenated files (`from_read_multistream`,
+	/// `segments_from_read`) always measure every frame, whatever this option.
+	pub trust_first_xing: bool,
+	/// When set, the items of APEv2 tags with a header are read instead of skipped, and reported by
+	/// `from_read_with_metadata` (see `Metadata::ape_items`). Only the tags found before the duration is known are
+	/// read, which excludes tags at the end of files with a Xing/Info header unless `trust_first_xing` is unset or
+	/// `verify_xing` is set.
+	pub parse_ape_items: bool,
+	/// When set, the fields of ID3v1 tags are decoded instead of skipped, and reported by `from_read_with_metadata`
+	/// (see `Metadata::id3v1`). As ID3v1 tags are at the end of files, they are only read when the stream is scanned
+	/// until the end, which excludes files with a Xing/Info header unless `trust_first_xing` is unset or
+	/// `verify_xing` is set.
+	pub parse_id3v1: bool,
+	/// When set, the frames of ID3v2 tags are walked instead of skipped as a whole, to find attached pictures (APIC
+	/// frames) and report them with `from_read_with_metadata` (see `Metadata::has_album_art`). Pictures are not read.
+	/// Like other tags, only the ID3v2 tags found before the duration is known are walked, which includes the leading
+	/// one. Pictures of ID3v2.2 and ID3v2.3 tags using unsynchronisation are not detected.
+	pub detect_album_art: bool,
+	/// When set to `Some(n)` with `n` above 1, streams are assumed to have a constant bitrate: only one frame out of
+	/// `n` is parsed, and the frames between them are skipped without being read, assuming they have the same length
+	/// (up to their padding). This trades accuracy for speed on non-seekable sources which are known to be CBR but
+	/// have no Xing/Info header. For seekable sources, `from_read_with_size` avoids reading the frames altogether.
+	///
+	/// The duration of CBR streams is measured exactly, except that tags or other data within the last `n` frames of
+	/// the stream may be counted as audio. On VBR streams, the next frame is looked for close to where a frame of the
+	/// same length would end: the duration drifts by roughly the difference between the bitrate of the parsed frames
+	/// and the average bitrate, and parsing may fail (or resync in lenient mode) when no frame is found there.
+	///
+	/// Xing/Info headers are still trusted when `trust_first_xing` is set. Entry points reporting details about every
+	/// frame (such as `from_read_with_metadata` or `analyze_from_read`) ignore this option.
+	pub assume_cbr_sampling: Option<usize>,
+	/// Largest number of frames accepted. Streams containing more frames (including Xing/Info frames and frames skipped
+	/// with `assume_cbr_sampling`), or with a Xing/Info or VBRI header declaring more audio frames, are rejected with
+	/// `ErrorKind::FrameLimitExceeded`, which reports the duration measured up to there. This bounds the work done on
+	/// untrusted inputs, and rejects files which are implausibly long for their size or purpose. Defaults to `None`
+	/// (no limit).
+	pub max_frames: Option<usize>,
+}
+
+impl Default for ParseOptions {
+	fn default() -> Self {
+		ParseOptions {
+			lenient: false,
+			strict_emphasis: false,
+			max_tag_bytes: 256 * 1024 * 1024,
+			skip_trailing_unknown: false,
+			min_sync_frames: 1,
+			max_leading_zeros: usize::MAX,
+			assume_format: None,
+			require_audio: false,
+			count_info_frame: false,
+			verify_xing: false,
+			scan_past_xing: false,
+			reject_tags: false,
+			validate_crc: false,
+			trust_first_xing: true,
+			parse_ape_items: false,
+			parse_id3v1: false,
+			detect_album_art: false,
+			assume_cbr_sampling: None,
+			max_frames: None,
+		}
+	}
+}
@@ -0,0 +1,33 @@
+use std::io::prelude::*;
+use std::time::Duration;
+
+use rayon::prelude::*;
+
+use crate::error::MP3DurationError;
+
+/// Measures the duration of several mp3 files contained in any structs implementing Read, concurrently.
+///
+/// The sources are measured like `from_read`, on the global rayon thread pool, which runs as many threads as there
+/// are CPUs unless configured otherwise. Results are returned in the same order as the sources.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs::File;
+/// use std::io::BufReader;
+/// use mp3_duration;
+///
+/// let paths = ["test/source.mp3", "test/CBR320.mp3"];
+/// let readers = paths.iter().map(|path| BufReader::new(File::open(path).unwrap()));
+/// for (path, duration) in paths.iter().zip(mp3_duration::measure_many(readers)) {
+///     println!("{}: {:?}", path, duration);
+/// }
+/// ```
+pub fn measure_many<I, T>(sources: I) -> Vec<Result<Duration, MP3DurationError>>
+where
+	I: IntoIterator<Item = T>,
+	T: Read + Send,
+{
+	let mut sources: Vec<T> = sources.into_iter().collect();
+	sources.par_iter_mut().map(crate::from_read).collect()
+}
@@ -0,0 +1,875 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::constants::{Emphasis, Layer, Version};
+use crate::crc::crc16;
+use crate::duration::DurationAccumulator;
+use crate::error::*;
+use crate::header::{is_plausible_header, FrameHeader};
+use crate::lame::LameTag;
+use crate::options::ParseOptions;
+use crate::source::{Source, SourceExt};
+use crate::tags::*;
+use crate::vbri::VbriInfo;
+use crate::warning::{Warning, WarningKind};
+
+pub enum Block {
+	Frame(Frame),
+	// Tag which was skipped over, and its position in the stream
+	Tag(u64),
+}
+
+/// Content of a Xing/Info header, found in the first frame of most VBR files (Xing) and of files encoded by LAME
+/// (Info). Each field is only available when the header flags declare it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct XingInfo {
+	/// Number of audio frames in the stream.
+	pub frames: Option<u32>,
+	/// Size of the stream in bytes.
+	pub bytes: Option<u32>,
+	/// Whether the header contains a table of contents for seeking.
+	pub has_toc: bool,
+	/// Quality indicator, from 0 (best) to 100 (worst).
+	pub quality: Option<u32>,
+	/// Whether the header is tagged "Xing", as encoders do for VBR streams, rather than "Info" for CBR streams.
+	pub is_vbr: bool,
+}
+
+impl XingInfo {
+	const FRAMES_FLAG: u8 = 0b0001;
+	const BYTES_FLAG: u8 = 0b0010;
+	const TOC_FLAG: u8 = 0b0100;
+	const QUALITY_FLAG: u8 = 0b1000;
+	const TOC_SIZE: usize = 100;
+
+	// Size of the fields following the flags
+	fn fields_size(flags: u8) -> usize {
+		let mut size = 0;
+		for flag in &[Self::FRAMES_FLAG, Self::BYTES_FLAG, Self::QUALITY_FLAG] {
+			if flags & flag != 0 {
+				size += 4;
+			}
+		}
+		if flags & Self::TOC_FLAG != 0 {
+			size += Self::TOC_SIZE;
+		}
+		size
+	}
+
+	// Fields which do not fit in `fields` are reported as missing.
+	fn parse(is_vbr: bool, flags: u8, mut fields: &[u8]) -> Self {
+		fn next_field<'a>(fields: &mut &'a [u8], present: bool, size: usize) -> Option<&'a [u8]> {
+			if !present || fields.len() < size {
+				return None;
+			}
+			let (field, rest) = fields.split_at(size);
+			*fields = rest;
+			Some(field)
+		}
+		let to_u32 = |field: &[u8]| u32::from_be_bytes([field[0], field[1], field[2], field[3]]);
+
+		let frames = next_field(&mut fields, flags & Self::FRAMES_FLAG != 0, 4).map(to_u32);
+		let bytes = next_field(&mut fields, flags & Self::BYTES_FLAG != 0, 4).map(to_u32);
+		let has_toc = next_field(&mut fields, flags & Self::TOC_FLAG != 0, Self::TOC_SIZE).is_some();
+		let quality = next_field(&mut fields, flags & Self::QUALITY_FLAG != 0, 4).map(to_u32);
+		XingInfo {
+			frames,
+			bytes,
+			has_toc,
+			quality,
+			is_vbr,
+		}
+	}
+}
+
+pub struct Frame {
+	// Position of the first byte of the frame header in the stream
+	pub offset: u64,
+	pub header: FrameHeader,
+	pub xing: Option<XingInfo>,
+	// LAME extension of the Xing/Info header, if any
+	pub lame: Option<LameTag>,
+	// VBRI header, which Fraunhofer encoders write instead of a Xing/Info header
+	pub vbri: Option<VbriInfo>,
+	// Number of bytes of audio data stored in previous frames (bit reservoir), always 0 outside of Layer III
+	pub main_data_begin: u16,
+	consumed: usize,
+}
+
+impl Frame {
+	// Duration of the whole stream, as declared by the Xing/Info header of this frame
+	pub fn xing_duration(&self) -> Option<Duration> {
+		self.xing_duration_with(false)
+	}
+
+	// Same as `xing_duration`, optionally counting the Xing/Info frame itself as part of the stream
+	pub fn xing_duration_with(&self, count_info_frame: bool) -> Option<Duration> {
+		let num_frames = self.xing.as_ref()?.frames? as u64 + count_info_frame as u64;
+		let mut duration = DurationAccumulator::default();
+		duration.add(num_frames * self.header.samples_per_frame() as u64, self.header.sampling_rate.hz());
+		Some(duration.duration())
+	}
+}
+
+// Number of bytes left in a frame of `frame_length` bytes once `consumed` bytes of it were read. Frames which cannot
+// physically hold these bytes are rejected with `ErrorKind::MPEGFrameTooShort`: strict mode reports it, while lenient
+// mode resyncs past them.
+pub(crate) fn remaining_frame_bytes(frame_length: usize, consumed: usize) -> Result<usize, ErrorKind> {
+	frame_length.checked_sub(consumed).ok_or(ErrorKind::MPEGFrameTooShort)
+}
+
+// Checks whether the given bytes (up to 4) look like the start of a frame or of a well-known tag. The end of the
+// stream or zero padding are also accepted.
+fn is_plausible_block_start(bytes: &[u8]) -> bool {
+	if bytes.len() < 4 || bytes[0] == 0 {
+		return true;
+	}
+	if &bytes[0..3] == b"ID3" || &bytes[0..3] == b"TAG" || bytes == b"APET" {
+		return true;
+	}
+	is_plausible_header(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+// Checks the CRC of a Layer III frame, which covers the last two bytes of its header and its side information. As the
+// side information buffer is sized for frames without CRC, it lacks the two bytes which follow, at the start of the
+// Xing/Info buffer. Frames without CRC are valid.
+fn is_crc_valid(header: u32, frame_header: &FrameHeader, side_information: &[u8], xing_buffer: &[u8]) -> bool {
+	let has_crc = header & (1 << 16) == 0;
+	if !has_crc || frame_header.layer != Layer::Layer3 {
+		return true;
+	}
+	let mut protected = Vec::with_capacity(2 + side_information.len());
+	protected.extend_from_slice(&header.to_be_bytes()[2..]);
+	protected.extend_from_slice(&side_information[2..]);
+	protected.extend_from_slice(&xing_buffer[..2]);
+	crc16(&protected) == u16::from_be_bytes([side_information[0], side_information[1]])
+}
+
+// Reads the first field of the side information of a Layer III frame, which follows the CRC when there is one. It is
+// 9 bits long in MPEG-1 frames and 8 bits long otherwise.
+fn main_data_begin(header: u32, frame_header: &FrameHeader, side_information: &[u8]) -> u16 {
+	if frame_header.layer != Layer::Layer3 {
+		return 0;
+	}
+	let has_crc = header & (1 << 16) == 0;
+	let side_information = if has_crc { &side_information[2..] } else { side_information };
+	match frame_header.version {
+		Version::Mpeg1 => (side_information[0] as u16) << 1 | (side_information[1] as u16) >> 7,
+		_ => side_information[0] as u16,
+	}
+}
+
+// Progress of a measurement performed one block at a time (see `Parser::measure_next_block`)
+pub enum Progress {
+	Continue,
+	Finished(Duration),
+}
+
+// Block handed to the callback of `Parser::measure_next_block_with` once it was consumed
+pub enum Measured<'f> {
+	Frame(MeasuredFrame<'f>),
+	// Tag which was skipped over, and its position in the stream
+	Tag(u64),
+}
+
+pub struct MeasuredFrame<'f> {
+	pub frame: &'f Frame,
+	// All the bytes of the frame (header included) when `Parser::keep_frame_bytes` is set, none otherwise
+	pub bytes: &'f [u8],
+	// Whether the frame was read until its end. In lenient mode, a frame cut short by the end of the stream is not
+	// measured.
+	pub complete: bool,
+	// Whether the frame counts towards the duration. Xing/Info frames do not contain audio: they only count with
+	// `ParseOptions::count_info_frame`, or while a Xing/Info header could still declare the duration.
+	pub is_audio: bool,
+	// Duration declared by the Xing/Info header of the frame, when it was trusted
+	pub declared: Option<Duration>,
+}
+
+// Everything the parser has learned about the stream so far. Kept apart from the byte source so that parsing can be
+// rolled back and resumed over a different source.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ParserState {
+	// Frames measured so far
+	pub measured: DurationAccumulator,
+	// Header of the first frame found, reported along with errors
+	pub first_header: Option<FrameHeader>,
+	// Bytes dropped while looking for the next frame or tag in lenient mode
+	pub resynced_bytes: u64,
+	// Sync words found in lenient mode whose header was not valid
+	pub invalid_headers: usize,
+	// Set in lenient mode when a tag was not followed by a frame or tag, which suggests its declared size is wrong.
+	// The next frame found is then only trusted if another frame or tag follows it.
+	confirm_next_frame: bool,
+	// Anomalies which were worked around
+	pub warnings: Vec<Warning>,
+	// Items of the APEv2 tags found so far, see `ParseOptions::parse_ape_items`
+	pub ape_items: HashMap<String, Vec<u8>>,
+	// First ID3v1 tag found, see `ParseOptions::parse_id3v1`
+	pub id3v1: Option<ID3v1Tag>,
+	// Sizes of the pictures attached to the ID3v2 tags found so far, see `ParseOptions::detect_album_art`
+	pub pictures: Vec<usize>,
+	// Frames found so far, whether they were measured or not, see `ParseOptions::max_frames`
+	pub frames_found: u64,
+	// Set once a frame was found, see `ParseOptions::min_sync_frames`
+	synced: bool,
+	// Set when a free-format frame header was skipped over in lenient mode
+	found_free_format: bool,
+	// Duration declared by the first Xing/Info header which was trusted, see `ParseOptions::trust_first_xing`
+	pub xing_duration: Option<Duration>,
+}
+
+impl ParserState {
+	// Duration of the frames measured so far
+	pub fn duration(&self) -> Duration {
+		self.measured.duration()
+	}
+
+	// Takes a checkpoint to roll back to if the next block cannot be parsed yet, without copying what was collected
+	pub fn checkpoint(&self) -> Checkpoint {
+		Checkpoint {
+			state: ParserState {
+				measured: self.measured.clone(),
+				warnings: Vec::new(),
+				ape_items: HashMap::new(),
+				id3v1: None,
+				pictures: Vec::new(),
+				..*self
+			},
+			warnings: self.warnings.len(),
+			pictures: self.pictures.len(),
+			has_id3v1: self.id3v1.is_some(),
+		}
+	}
+
+	// Restores the state from before the block parsed since `checkpoint` was taken. APEv2 items are kept: they are
+	// only added once complete, and parsing the block again adds the same ones.
+	pub fn rollback(&mut self, checkpoint: Checkpoint) {
+		self.warnings.truncate(checkpoint.warnings);
+		self.pictures.truncate(checkpoint.pictures);
+		*self = ParserState {
+			warnings: std::mem::take(&mut self.warnings),
+			ape_items: std::mem::take(&mut self.ape_items),
+			id3v1: self.id3v1.take().filter(|_| checkpoint.has_id3v1),
+			pictures: std::mem::take(&mut self.pictures),
+			..checkpoint.state
+		};
+	}
+}
+
+// State of a parser before a block, see `ParserState::checkpoint`
+pub struct Checkpoint {
+	// State without the warnings, tag items and pictures collected until then
+	pub state: ParserState,
+	warnings: usize,
+	pictures: usize,
+	has_id3v1: bool,
+}
+
+pub struct Parser<'o, S> {
+	pub source: S,
+	options: &'o ParseOptions,
+	tag_handlers: &'o [&'o dyn TagHandler],
+	pub state: ParserState,
+	// When set, the bytes of frames are read and handed to the callback of `measure_next_block_with`. This is off by
+	// default so that merely measuring the duration does not pay for copying them.
+	pub keep_frame_bytes: bool,
+	// Bytes of the last frame returned by `next_block`, as far as they were read
+	frame_bytes: Vec<u8>,
+	// Replaces `FrameHeader::is_allowed_combination` to decide which frame headers are valid
+	pub validator: Option<&'o dyn Fn(&FrameHeader) -> bool>,
+}
+
+impl<'o, S: Source> Parser<'o, S> {
+	pub fn new(source: S, options: &'o ParseOptions) -> Self {
+		Self::with_tag_handlers(source, options, &[])
+	}
+
+	pub fn with_tag_handlers(source: S, options: &'o ParseOptions, tag_handlers: &'o [&'o dyn TagHandler]) -> Self {
+		Self::with_state(source, options, tag_handlers, ParserState::default())
+	}
+
+	pub fn with_state(
+		source: S,
+		options: &'o ParseOptions,
+		tag_handlers: &'o [&'o dyn TagHandler],
+		state: ParserState,
+	) -> Self {
+		Parser {
+			source,
+			options,
+			tag_handlers,
+			state,
+			keep_frame_bytes: false,
+			frame_bytes: Vec::new(),
+			validator: None,
+		}
+	}
+
+	// Error located at the current position in the stream
+	pub fn error(&self, kind: ErrorKind) -> MP3DurationError {
+		MP3DurationError {
+			first_header: self.state.first_header,
+			..self.source.error(kind, self.state.duration())
+		}
+	}
+
+	// Reads the next frame or tag. Tags are skipped in their entirety, while frames are only read up to the end of
+	// their Xing/Info header (see `skip_rest_of_frame`). Returns `None` at the end of the stream.
+	pub fn next_block(&mut self) -> Result<Option<Block>, MP3DurationError> {
+		let mut header_buffer = [0; 4];
+		let lenient = self.options.lenient;
+
+		// In lenient mode, bytes which could not be parsed are put back in the stream (minus their first byte)
+		// so that parsing resumes one byte further. So are bytes preceding the first frame when its format is known.
+		macro_rules! resync_or_fail {
+			($error:expr, $consumed:expr) => {{
+				let error = $error;
+				if !lenient && (self.options.assume_format.is_none() || self.state.synced) {
+					debug!("Parsing failed: {}", error);
+					return Err(error);
+				}
+				trace!("Resyncing: {}", error);
+				self.source.unread($consumed);
+				self.source.unread(&header_buffer[1..]);
+				self.state.resynced_bytes += 1;
+				continue;
+			}};
+		}
+
+		if self.source.bytes_read() == 0 {
+			self.skip_text_preamble()?;
+		}
+
+		loop {
+			// Skip over 0x00 bytes (these are probably incorrectly added padding bytes for id3v2), within limits
+			let max_zeros = self.options.max_leading_zeros;
+			let zeros = self.source.skip_zeros(max_zeros.saturating_add(1)).map_err(|e| self.error(e))?;
+			if zeros > max_zeros {
+				debug!("More than {} 0x00 bytes at offset {}", max_zeros, self.source.bytes_read());
+				return Err(self.error(ErrorKind::UnexpectedFrame { header: 0 }));
+			}
+			if zeros > 0 {
+				trace!("Skipped {} 0x00 bytes before offset {}", zeros, self.source.bytes_read());
+			}
+
+			match self.source.read_exact(&mut header_buffer) {
+				Ok(_) => (),
+				Err(_) if self.source.reached_eof() => return Ok(None),
+				Err(e) => return Err(self.error(e)),
+			};
+
+			// MPEG frame
+			let header = u32::from_be_bytes(header_buffer);
+			let is_mp3 = header >> 21 == 0x7FF;
+			if is_mp3 {
+				let frame_header = match FrameHeader::parse(header) {
+					Ok(frame_header) => frame_header,
+					Err(e) => {
+						self.state.invalid_headers += 1;
+						resync_or_fail!(self.error(e), &[])
+					}
+				};
+				let is_valid = match self.validator {
+					Some(is_valid) => is_valid(&frame_header),
+					None => frame_header.is_allowed_combination(),
+				};
+				if !is_valid {
+					self.state.invalid_headers += 1;
+					resync_or_fail!(self.error(ErrorKind::UnexpectedFrame { header }), &[]);
+				}
+				// Free-format frames have no bitrate, so their length cannot be computed from their header
+				if frame_header.bitrate == 0 {
+					self.state.invalid_headers += 1;
+					self.state.found_free_format = true;
+					resync_or_fail!(self.error(ErrorKind::FreeFormatUnsupported), &[]);
+				}
+				// Frames must at least hold the header, side information and start of a Xing/Info header which are read
+				// below. This also guarantees that parsing makes progress.
+				let xing_offset = frame_header.side_information_size();
+				let mut xing_buffer = [0; 12];
+				let read_size = header_buffer.len() + xing_offset + xing_buffer.len();
+				if let Err(e) = remaining_frame_bytes(frame_header.frame_length(), read_size) {
+					self.state.invalid_headers += 1;
+					resync_or_fail!(self.error(e), &[]);
+				}
+				if let Some(format) = &self.options.assume_format {
+					if !format.has_same_format(&frame_header) {
+						resync_or_fail!(self.error(ErrorKind::UnexpectedFrame { header }), &[]);
+					}
+				}
+
+				if self.options.strict_emphasis && frame_header.emphasis == Emphasis::Reserved {
+					resync_or_fail!(self.error(ErrorKind::ReservedEmphasis), &[]);
+				}
+
+				if self.state.confirm_next_frame {
+					let frame_length = frame_header.frame_length();
+					let rest_of_frame = frame_length - header_buffer.len();
+					let mut frame_and_next_header = vec![0; frame_length];
+					let available = self.source.peek(&mut frame_and_next_header).map_err(|e| self.error(e))?;
+					if available < rest_of_frame
+						|| !is_plausible_block_start(&frame_and_next_header[rest_of_frame..available])
+					{
+						resync_or_fail!(self.error(ErrorKind::UnexpectedFrame { header }), &[]);
+					}
+					self.state.confirm_next_frame = false;
+				}
+
+				if !self.state.synced {
+					if !self.is_sync_confirmed(&frame_header).map_err(|e| self.error(e))? {
+						resync_or_fail!(self.error(ErrorKind::UnexpectedFrame { header }), &[]);
+					}
+					self.state.synced = true;
+				}
+
+				// A frame cut short before the end of the first Xing/Info fields cannot be measured. In lenient mode, it
+				// ends the stream.
+				let mut side_information = [0; 32];
+				let side_information = &mut side_information[..xing_offset];
+				let read = self.source.read_exact(side_information).and_then(|_| self.source.read_exact(&mut xing_buffer));
+				match read {
+					Ok(_) => (),
+					Err(_) if lenient && self.source.reached_eof() => return Ok(None),
+					Err(e) => return Err(self.error(e)),
+				}
+				let main_data_begin = main_data_begin(header, &frame_header, side_information);
+				if self.options.validate_crc && !is_crc_valid(header, &frame_header, side_information, &xing_buffer) {
+					if !lenient {
+						return Err(self.error(ErrorKind::CrcMismatch));
+					}
+					let offset = self.source.bytes_read() - (header_buffer.len() + xing_offset + xing_buffer.len()) as u64;
+					self.state.warnings.push(Warning {
+						kind: WarningKind::CrcMismatch,
+						offset,
+					});
+				}
+				if self.keep_frame_bytes {
+					self.frame_bytes.clear();
+					self.frame_bytes.extend_from_slice(&header_buffer);
+					self.frame_bytes.extend_from_slice(side_information);
+					self.frame_bytes.extend_from_slice(&xing_buffer);
+				}
+
+				let mut consumed = header_buffer.len() + xing_offset + xing_buffer.len();
+				let is_xing = &xing_buffer[0..4] == b"Xing";
+				let is_info = &xing_buffer[0..4] == b"Info";
+				let (xing, lame) = if is_xing || is_info {
+					// Fields beyond the ones already read are only looked for within the frame, and may be cut short by
+					// the end of the stream
+					let flags = xing_buffer[7];
+					let mut fields = xing_buffer[8..].to_vec();
+					let fields_size = XingInfo::fields_size(flags);
+					let missing = fields_size.saturating_sub(fields.len());
+					let frame_length = frame_header.frame_length();
+					if consumed + missing <= frame_length {
+						let rest_of_fields = self.read_within_frame(missing, &mut consumed)?;
+						fields.extend(rest_of_fields);
+					}
+					// So is the LAME extension following them, part of which may have been read already
+					let lame = match fields.get(fields_size..) {
+						Some(start) if consumed + LameTag::SIZE - start.len() <= frame_length => {
+							let mut lame_bytes = [0; LameTag::SIZE];
+							lame_bytes[..start.len()].copy_from_slice(start);
+							let rest = self.read_within_frame(LameTag::SIZE - start.len(), &mut consumed)?;
+							if start.len() + rest.len() == LameTag::SIZE {
+								lame_bytes[start.len()..].copy_from_slice(&rest);
+								LameTag::parse(&lame_bytes)
+							} else {
+								None
+							}
+						}
+						_ => None,
+					};
+					let xing = XingInfo::parse(is_xing, flags, &fields);
+					debug!(
+						"{} header found at offset {}: {:?} frames, {:?} bytes",
+						if is_xing { "Xing" } else { "Info" },
+						self.source.bytes_read() - consumed as u64,
+						xing.frames,
+						xing.bytes
+					);
+					(Some(xing), lame)
+				} else {
+					(None, None)
+				};
+				let vbri = match xing {
+					None => self.read_vbri(&frame_header, &xing_buffer, &mut consumed)?,
+					Some(_) => None,
+				};
+
+				let declared_frames = xing.as_ref().and_then(|xing| xing.frames).or(vbri.as_ref().map(|vbri| vbri.frames));
+				self.count_frames(1, declared_frames.unwrap_or_default() as u64)?;
+				self.state.first_header.get_or_insert(frame_header);
+				return Ok(Some(Block::Frame(Frame {
+					offset: self.source.bytes_read() - consumed as u64,
+					header: frame_header,
+					xing,
+					lame,
+					vbri,
+					main_data_begin,
+					consumed,
+				})));
+			}
+
+			// Tags
+			if self.options.reject_tags {
+				let mut magic = [0; 11];
+				magic[..4].copy_from_slice(&header_buffer);
+				let available = self.source.peek(&mut magic[4..]).map_err(|e| self.error(e))?;
+				if let Some(kind) = TagKind::detect(&magic[..4 + available]) {
+					return Err(self.error(ErrorKind::UnexpectedTag { kind }));
+				}
+			}
+			let builtin_handlers: [&dyn TagHandler; 3] = [
+				&ID3v2Handler::new(self.options),
+				&ID3v1Handler::new(self.options),
+				&APEv2Handler::new(self.options),
+			];
+			let (source, state, tag_handlers) = (&mut self.source, &mut self.state, self.tag_handlers);
+			let mut handled = None;
+			if self.options.parse_ape_items {
+				handled = APEv2Handler::new(self.options).try_read_items(&header_buffer, source, &mut state.ape_items);
+			}
+			if handled.is_none() && self.options.detect_album_art {
+				let (warnings, pictures) = (&mut state.warnings, &mut state.pictures);
+				handled = ID3v2Handler::new(self.options).try_find_pictures(&header_buffer, source, warnings, pictures);
+			}
+			if handled.is_none() && self.options.parse_id3v1 {
+				handled = ID3v1Handler::new(self.options).try_read_fields(&header_buffer, source, &mut state.id3v1);
+			}
+			let handled = handled.or_else(|| {
+				builtin_handlers
+					.iter()
+					.chain(tag_handlers.iter())
+					.find_map(|handler| handler.try_handle_with_warnings(&header_buffer, source, &mut state.warnings))
+			});
+			if let Some(result) = handled {
+				let size = result.map_err(|e| self.error(e))?;
+				if self.options.reject_tags {
+					return Err(self.error(ErrorKind::UnexpectedTag { kind: TagKind::Custom }));
+				}
+				let offset = self.source.bytes_read().saturating_sub(size as u64);
+				debug!(
+					"Skipped {} bytes tag starting with {:?} at offset {}",
+					size,
+					String::from_utf8_lossy(&header_buffer),
+					offset
+				);
+				if lenient {
+					let mut next_header = [0; 4];
+					let available = self.source.peek(&mut next_header).map_err(|e| self.error(e))?;
+					self.state.confirm_next_frame = !is_plausible_block_start(&next_header[..available]);
+				}
+				return Ok(Some(Block::Tag(offset)));
+			}
+
+			if self.options.skip_trailing_unknown && !self.state.measured.is_empty() {
+				debug!("Skipping trailing data from offset {}", self.source.bytes_read() - header_buffer.len() as u64);
+				self.source.skip_at_most(usize::MAX).map_err(|e| self.error(e))?;
+				return Ok(None);
+			}
+
+			resync_or_fail!(self.error(ErrorKind::UnexpectedFrame { header }), &[]);
+		}
+	}
+
+	// Reads part of the frame returned by the ongoing `next_block` call. Fewer bytes are returned if the stream ends
+	// before.
+	fn read_within_frame(&mut self, num_bytes: usize, consumed: &mut usize) -> Result<Vec<u8>, MP3DurationError> {
+		let mut bytes = vec![0; num_bytes];
+		let available = self.source.peek(&mut bytes).map_err(|e| self.error(e))?;
+		bytes.truncate(available);
+		self.source.skip(available).map_err(|e| self.error(e))?;
+		if self.keep_frame_bytes {
+			self.frame_bytes.extend_from_slice(&bytes);
+		}
+		*consumed += available;
+		Ok(bytes)
+	}
+
+	// Reads the VBRI header of the frame returned by the ongoing `next_block` call, if it has one. `xing_buffer` holds
+	// the last bytes read from the frame, which may already contain the start of the header.
+	fn read_vbri(
+		&mut self,
+		frame_header: &FrameHeader,
+		xing_buffer: &[u8],
+		consumed: &mut usize,
+	) -> Result<Option<VbriInfo>, MP3DurationError> {
+		let frame_length = frame_header.frame_length();
+		if frame_length < VbriInfo::OFFSET + VbriInfo::SIZE {
+			return Ok(None);
+		}
+		let known = &xing_buffer[xing_buffer.len() - consumed.saturating_sub(VbriInfo::OFFSET)..];
+		let gap = VbriInfo::OFFSET.saturating_sub(*consumed);
+		let is_vbri = if known.len() >= 4 {
+			known.starts_with(b"VBRI")
+		} else {
+			let mut peeked = vec![0; gap + 4 - known.len()];
+			let available = self.source.peek(&mut peeked).map_err(|e| self.error(e))?;
+			available == peeked.len() && [known, &peeked[gap..]].concat() == b"VBRI"
+		};
+		if !is_vbri {
+			return Ok(None);
+		}
+
+		let missing = gap + VbriInfo::SIZE - known.len();
+		let rest = self.read_within_frame(missing, consumed)?;
+		if rest.len() < missing {
+			return Ok(None);
+		}
+		let mut bytes = [0; VbriInfo::SIZE];
+		bytes[..known.len()].copy_from_slice(known);
+		bytes[known.len()..].copy_from_slice(&rest[gap..]);
+		let (mut vbri, toc_size) = match VbriInfo::parse(&bytes) {
+			Some(parsed) => parsed,
+			None => return Ok(None),
+		};
+		// The table of contents is only looked for within the frame
+		if toc_size > 0 && *consumed + toc_size <= frame_length {
+			let toc = self.read_within_frame(toc_size, consumed)?;
+			vbri.parse_toc(&bytes, &toc);
+		}
+		debug!(
+			"VBRI header found at offset {}: {} frames, {} bytes",
+			self.source.bytes_read() - *consumed as u64,
+			vbri.frames,
+			vbri.bytes
+		);
+		Ok(Some(vbri))
+	}
+
+	// Checks whether the frame whose header was just read is followed by enough frames to satisfy
+	// `ParseOptions::min_sync_frames`.
+	fn is_sync_confirmed(&mut self, frame_header: &FrameHeader) -> Result<bool, ErrorKind> {
+		let header_size = 4;
+		// Position of the next frame, relative to the end of the header which was just read
+		let mut next_frame = frame_header.frame_length().max(header_size) - header_size;
+		let mut bytes = Vec::new();
+		for _ in 1..self.options.min_sync_frames {
+			bytes.resize(next_frame + header_size, 0);
+			let available = self.source.peek(&mut bytes)?;
+			if available < next_frame {
+				return Ok(false);
+			}
+			let next_header = &bytes[next_frame..available];
+			if next_header.len() < header_size {
+				return Ok(true);
+			}
+			let header = u32::from_be_bytes([next_header[0], next_header[1], next_header[2], next_header[3]]);
+			if header >> 21 != 0x7FF {
+				return Ok(is_plausible_block_start(next_header));
+			}
+			match FrameHeader::parse(header) {
+				Ok(header) if header.is_allowed_combination() => next_frame += header.frame_length().max(header_size),
+				_ => return Ok(false),
+			}
+		}
+		Ok(true)
+	}
+
+	// Skips a UTF-8 byte order mark and ASCII whitespace at the start of the stream, which tools treating it as text
+	// sometimes prepend. This is done even in strict mode, as such bytes cannot be mistaken for audio.
+	fn skip_text_preamble(&mut self) -> Result<(), MP3DurationError> {
+		let mut bom = [0; 3];
+		let bom_size = match self.source.peek(&mut bom).map_err(|e| self.error(e))? {
+			3 if bom == [0xEF, 0xBB, 0xBF] => 3,
+			_ => 0,
+		};
+		self.source.skip(bom_size).map_err(|e| self.error(e))?;
+		let mut byte = [0; 1];
+		while self.source.peek(&mut byte).map_err(|e| self.error(e))? == 1 && byte[0].is_ascii_whitespace() {
+			self.source.skip(1).map_err(|e| self.error(e))?;
+		}
+		let size = self.source.bytes_read();
+		if size > 0 {
+			debug!("Skipped {} bytes of text preamble", size);
+			self.state.warnings.push(Warning {
+				kind: WarningKind::TextPreamble { size },
+				offset: 0,
+			});
+		}
+		Ok(())
+	}
+
+	fn bytes_to_next_frame(&self, frame: &Frame) -> Result<usize, MP3DurationError> {
+		remaining_frame_bytes(frame.header.frame_length(), frame.consumed).map_err(|e| self.error(e))
+	}
+
+	// Skips over the audio data of a frame returned by `next_block`, or reads it when `keep_frame_bytes` is set. When
+	// `lenient`, a frame cut short by the end of the stream is not an error, but `false` is returned to signal it is
+	// incomplete.
+	fn consume_rest_of_frame(&mut self, frame: &Frame, lenient: bool) -> Result<bool, MP3DurationError> {
+		let bytes_to_next_frame = self.bytes_to_next_frame(frame)?;
+		if self.keep_frame_bytes {
+			let start = self.frame_bytes.len();
+			self.frame_bytes.resize(start + bytes_to_next_frame, 0);
+			return match self.source.read_exact(&mut self.frame_bytes[start..]) {
+				Ok(_) => Ok(true),
+				Err(_) if lenient && self.source.reached_eof() => Ok(false),
+				Err(e) => Err(self.error(e)),
+			};
+		}
+		if lenient {
+			let skipped = self.source.skip_at_most(bytes_to_next_frame).map_err(|e| self.error(e))?;
+			return Ok(skipped == bytes_to_next_frame);
+		}
+		self.source.skip(bytes_to_next_frame).map_err(|e| self.error(e))?;
+		Ok(true)
+	}
+
+	// Checks whether the duration `declared` by the Xing/Info header of a frame which was consumed can be trusted. It
+	// cannot when the next frame uses another sampling rate, as the declared frame count was then computed for other
+	// frames (a muxing bug): this is reported as a warning.
+	fn is_xing_duration_trusted(&mut self, frame: &Frame, declared: Duration) -> Result<bool, MP3DurationError> {
+		let mut next_header = [0; 4];
+		if self.source.peek(&mut next_header).map_err(|e| self.error(e))? < next_header.len() {
+			return Ok(true);
+		}
+		let audio_rate = match FrameHeader::parse(u32::from_be_bytes(next_header)) {
+			Ok(header) if header.sampling_rate != frame.header.sampling_rate => header.sampling_rate.hz(),
+			_ => return Ok(true),
+		};
+		self.state.warnings.push(Warning {
+			kind: WarningKind::XingSamplingRateMismatch {
+				declared,
+				xing_rate: frame.header.sampling_rate.hz(),
+				audio_rate,
+			},
+			offset: frame.offset,
+		});
+		Ok(false)
+	}
+
+	// Duration of the stream once its end was reached
+	pub fn end_of_stream(&self) -> Result<Duration, MP3DurationError> {
+		if self.state.found_free_format && self.state.measured.is_empty() {
+			return Err(self.error(ErrorKind::FreeFormatUnsupported));
+		}
+		if self.options.require_audio && self.state.measured.is_empty() {
+			return Err(self.error(ErrorKind::NoAudioFrames));
+		}
+		Ok(self.state.duration())
+	}
+
+	// Reads the next block, hands it to `on_block` once it was consumed, and adds its duration to the total. Every scan
+	// of a stream goes through this. When `ParseOptions::trust_first_xing` is set, the first Xing/Info header declaring
+	// the duration of the stream is trusted, and measuring finishes with this duration unless
+	// `ParseOptions::scan_past_xing` is set. `on_block` finds the duration of the blocks before it in `state`.
+	pub fn measure_next_block_with<F>(&mut self, mut on_block: F) -> Result<Progress, MP3DurationError>
+	where
+		F: FnMut(&Self, Measured),
+	{
+		let frame = match self.next_block()? {
+			None => return Ok(Progress::Finished(self.end_of_stream()?)),
+			Some(Block::Tag(offset)) => {
+				on_block(self, Measured::Tag(offset));
+				return Ok(Progress::Continue);
+			}
+			Some(Block::Frame(frame)) => frame,
+		};
+		let checks_xing = self.options.trust_first_xing && self.state.xing_duration.is_none();
+		let (complete, is_audio, declared) = match frame.xing_duration_with(self.options.count_info_frame) {
+			// A Xing/Info frame cut short by the end of the stream is trusted
+			Some(duration) if checks_xing => {
+				let complete = self.consume_rest_of_frame(&frame, true)?;
+				let is_trusted = !complete || self.is_xing_duration_trusted(&frame, duration)?;
+				(complete, self.options.count_info_frame, Some(duration).filter(|_| is_trusted))
+			}
+			// Xing/Info frames do not contain audio, which matters when their headers are not trusted
+			_ => {
+				let is_audio = checks_xing || frame.xing.is_none() || self.options.count_info_frame;
+				(self.consume_rest_of_frame(&frame, self.options.lenient)?, is_audio, None)
+			}
+		};
+		on_block(
+			self,
+			Measured::Frame(MeasuredFrame {
+				frame: &frame,
+				bytes: &self.frame_bytes,
+				complete,
+				is_audio,
+				declared,
+			}),
+		);
+		if complete && is_audio {
+			self.state.measured.add_frame(&frame.header);
+		}
+		if let Some(duration) = declared {
+			self.state.xing_duration = Some(duration);
+			if !self.options.scan_past_xing {
+				return Ok(Progress::Finished(duration));
+			}
+		}
+		Ok(Progress::Continue)
+	}
+
+	// Reads the next block and adds its duration to the total (see `measure_next_block_with`). With
+	// `ParseOptions::assume_cbr_sampling`, the frames following an audio frame are then skipped without being read.
+	pub fn measure_next_block(&mut self) -> Result<Progress, MP3DurationError> {
+		let mut sampled_header = None;
+		let progress = self.measure_next_block_with(|_, block| match block {
+			Measured::Frame(measured) if measured.complete && measured.is_audio && measured.frame.xing.is_none() => {
+				sampled_header = Some(measured.frame.header)
+			}
+			_ => (),
+		})?;
+		match (self.options.assume_cbr_sampling, sampled_header) {
+			(Some(sampling), Some(header)) if sampling > 1 => self.skip_cbr_frames(&header, sampling - 1)?,
+			_ => (),
+		}
+		Ok(progress)
+	}
+
+	// Counts frames found towards `ParseOptions::max_frames`, along with the number of frames declared by a header
+	fn count_frames(&mut self, found: u64, declared: u64) -> Result<(), MP3DurationError> {
+		self.state.frames_found += found;
+		match self.options.max_frames {
+			Some(max_frames) if self.state.frames_found.max(declared) > max_frames as u64 => {
+				Err(self.error(ErrorKind::FrameLimitExceeded { max_frames }))
+			}
+			_ => Ok(()),
+		}
+	}
+
+	// Skips the `num_frames` frames following a frame with the given header without parsing them, assuming they have
+	// the same bitrate (see `ParseOptions::assume_cbr_sampling`), and measures them.
+	fn skip_cbr_frames(&mut self, header: &FrameHeader, num_frames: usize) -> Result<(), MP3DurationError> {
+		let unpadded_length = FrameHeader { padding: false, ..*header }.frame_length();
+		let skipped = self.source.skip_at_most(num_frames * unpadded_length).map_err(|e| self.error(e))?;
+		// Each skipped frame may be padded, so the next frame starts within `num_frames` slots
+		let slot_size = if header.layer == Layer::Layer1 { 4 } else { 1 };
+		let mut window = vec![0; num_frames * slot_size + 4];
+		let available = self.source.peek(&mut window).map_err(|e| self.error(e))?;
+		let next_frame = window[..available].windows(4).position(|bytes| {
+			let next_header = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+			is_plausible_header(next_header) && FrameHeader::parse(next_header).is_ok_and(|h| h.has_same_format(header))
+		});
+		let num_frames = match next_frame {
+			Some(position) => {
+				self.source.skip(position).map_err(|e| self.error(e))?;
+				num_frames
+			}
+			// The stream ended, or other data follows: only the frames which fit in the skipped bytes are measured
+			None => {
+				let samples_per_frame = header.samples_per_frame() as f64;
+				let average_length = samples_per_frame / 8.0 * header.bitrate as f64 / header.sampling_rate.hz() as f64;
+				((skipped as f64 / average_length).round() as usize).min(num_frames)
+			}
+		};
+		self.count_frames(num_frames as u64, 0)?;
+		let samples = num_frames as u64 * header.samples_per_frame() as u64;
+		self.state.measured.add(samples, header.sampling_rate.hz());
+		Ok(())
+	}
+
+	// Measures the stream until the end, or until a Xing/Info header declares its duration.
+	pub fn measure(&mut self) -> Result<Duration, MP3DurationError> {
+		loop {
+			if let Progress::Finished(duration) = self.measure_next_block()? {
+				return Ok(duration);
+			}
+		}
+	}
+}
@@ -0,0 +1,199 @@
+use std::time::Duration;
+
+use crate::error::{ErrorKind, MP3DurationError};
+use crate::header::FrameHeader;
+use crate::options::ParseOptions;
+use crate::parser::{Parser, ParserState, Progress};
+use crate::source::SliceSource;
+
+/// Measures the duration of a mp3 stream handed over in chunks of arbitrary size, for example as they are received
+/// from the network.
+///
+/// Once a stream is finished, `reset` prepares the scanner for the next one while keeping its buffers allocated.
+///
+/// # Examples
+///
+/// ```
+/// use mp3_duration::{DurationScanner, ParseOptions};
+///
+/// let data = std::fs::read("test/source.mp3").unwrap();
+/// let mut scanner = DurationScanner::new(&ParseOptions::default());
+/// let mut duration = None;
+/// for chunk in data.chunks(4096) {
+///     duration = scanner.feed(chunk).unwrap();
+///     if duration.is_some() {
+///         break;
+///     }
+/// }
+/// let duration = duration.unwrap_or_else(|| scanner.finish().unwrap());
+/// println!("File duration: {:?}", duration);
+/// ```
+///
+/// Scanners can be cloned, and serialized with the `serde` feature, so that measuring a stream can be paused and
+/// resumed later, e.g. as successive ranges of a remote file are fetched by different requests.
+// Bytes are buffered until a whole block can be parsed. A block which spans the end of the buffered data is parsed
+// again from its start once more data has been fed, except for the bytes skipped at the end of frames and tags, which
+// are dropped as they arrive.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DurationScanner {
+	options: ParseOptions,
+	// Bytes fed but not parsed yet
+	pending: Vec<u8>,
+	// Position of `pending` within the stream
+	offset: u64,
+	state: ParserState,
+	// Bytes still to be skipped before parsing resumes, and the state from before the block they belong to (without
+	// the warnings and tags collected until then, see `ParserState::checkpoint`)
+	skip_remaining: usize,
+	state_before_skip: ParserState,
+	// Number of pending bytes needed before parsing is attempted again. Waiting for the buffer to double after a
+	// block could not be parsed keeps long blocks (e.g. garbage being resynced over) from being parsed over and over.
+	retry_at: usize,
+	duration: Option<Duration>,
+}
+
+impl DurationScanner {
+	/// Creates a scanner for a stream parsed according to `options`.
+	pub fn new(options: &ParseOptions) -> Self {
+		DurationScanner {
+			options: options.clone(),
+			pending: Vec::new(),
+			offset: 0,
+			state: ParserState::default(),
+			skip_remaining: 0,
+			state_before_skip: ParserState::default(),
+			retry_at: 0,
+			duration: None,
+		}
+	}
+
+	/// Prepares the scanner for a new stream.
+	pub fn reset(&mut self) {
+		self.pending.clear();
+		self.offset = 0;
+		self.state = ParserState::default();
+		self.skip_remaining = 0;
+		self.state_before_skip = ParserState::default();
+		self.retry_at = 0;
+		self.duration = None;
+	}
+
+	/// Number of bytes fed so far.
+	pub fn bytes_fed(&self) -> u64 {
+		self.offset + self.pending.len() as u64
+	}
+
+	/// Duration of the frames measured so far.
+	pub fn duration(&self) -> Duration {
+		self.state.duration()
+	}
+
+	/// Header of the first audio frame found so far, if any.
+	pub fn first_header(&self) -> Option<FrameHeader> {
+		self.state.first_header
+	}
+
+	/// Duration of the frames measured so far, along with an estimate of the duration of the whole stream given its
+	/// total size in bytes, e.g. to display progress while a long stream is scanned.
+	///
+	/// The estimate extrapolates the measured duration to the whole stream from the number of bytes parsed so far. Once
+	/// the duration of the stream is known (e.g. from a Xing/Info header), both values are that duration.
+	pub fn estimate(&self, total_bytes: u64) -> (Duration, Duration) {
+		if let Some(duration) = self.duration {
+			return (duration, duration);
+		}
+		let measured = self.duration();
+		if self.offset == 0 || total_bytes <= self.offset {
+			return (measured, measured);
+		}
+		(measured, measured.mul_f64(total_bytes as f64 / self.offset as f64))
+	}
+
+	/// Parses as much of `data` as possible, keeping incomplete frames and tags until more data is fed.
+	///
+	/// Returns the duration of the stream if it is already known, which happens when a Xing/Info header declares it.
+	/// The rest of the stream then does not need to be fed.
+	pub fn feed(&mut self, data: &[u8]) -> Result<Option<Duration>, MP3DurationError> {
+		if self.duration.is_some() {
+			return Ok(self.duration);
+		}
+
+		let skipped = self.skip_remaining.min(data.len());
+		self.skip_remaining -= skipped;
+		self.offset += skipped as u64;
+		self.pending.extend_from_slice(&data[skipped..]);
+		if self.skip_remaining > 0 || self.pending.len() < self.retry_at {
+			return Ok(None);
+		}
+
+		self.parse_pending(false)
+	}
+
+	/// Parses the end of the stream, once all of it has been fed, and returns its duration.
+	pub fn finish(&mut self) -> Result<Duration, MP3DurationError> {
+		if let Some(duration) = self.duration {
+			return Ok(duration);
+		}
+
+		// The stream ended in the middle of a frame or tag
+		if self.skip_remaining > 0 {
+			if self.options.lenient {
+				return Ok(self.state_before_skip.duration());
+			}
+			return Err(MP3DurationError {
+				kind: ErrorKind::UnexpectedEOF,
+				offset: self.offset,
+				at_duration: self.state_before_skip.duration(),
+				first_header: self.state_before_skip.first_header,
+			});
+		}
+
+		let duration = self.parse_pending(true)?;
+		Ok(duration.expect("Parsing the end of the stream cannot require more data"))
+	}
+
+	// Parses blocks from the pending bytes until more data is needed or the duration is known, and drops the bytes
+	// which were parsed.
+	fn parse_pending(&mut self, is_final: bool) -> Result<Option<Duration>, MP3DurationError> {
+		let mut parsed = 0;
+		let result = loop {
+			match self.step(&mut parsed, is_final) {
+				None => break Ok(None),
+				Some(Ok(Progress::Continue)) => (),
+				Some(Ok(Progress::Finished(duration))) => {
+					self.duration = Some(duration);
+					break Ok(Some(duration));
+				}
+				Some(Err(e)) => break Err(e),
+			}
+		};
+		self.pending.drain(..parsed);
+		result
+	}
+
+	// Parses one block from the pending bytes following the `parsed` ones, and adds its size to `parsed`. Returns
+	// `None`, without consuming anything, if more data is needed.
+	fn step(&mut self, parsed: &mut usize, is_final: bool) -> Option<Result<Progress, MP3DurationError>> {
+		let source = SliceSource::new(&self.pending[*parsed..], self.offset, is_final);
+		let checkpoint = self.state.checkpoint();
+		let mut parser = Parser::with_state(source, &self.options, &[], std::mem::take(&mut self.state));
+		let result = parser.measure_next_block();
+		self.state = parser.state;
+		if parser.source.incomplete {
+			self.state.rollback(checkpoint);
+			self.retry_at = 2 * (self.pending.len() - *parsed);
+			return None;
+		}
+
+		let consumed = parser.source.position();
+		self.skip_remaining = parser.source.deferred_skip;
+		if self.skip_remaining > 0 {
+			self.state_before_skip = checkpoint.state;
+		}
+		self.retry_at = 0;
+		*parsed += consumed;
+		self.offset += consumed as u64;
+		Some(result)
+	}
+}
@@ -0,0 +1,187 @@
+use std::io::prelude::*;
+use std::time::Duration;
+
+use crate::context::Context;
+use crate::duration::DurationAccumulator;
+use crate::error::MP3DurationError;
+use crate::options::ParseOptions;
+use crate::parser::{Block, Measured, Parser, Progress};
+
+/// Mapping between byte offsets and timestamps of the audio frames of a mp3 file, for accurate seeking.
+///
+/// Unlike the table of contents of Xing headers, which only has 100 entries, the index can hold every frame. To bound
+/// its memory usage on long files, it can also be built with only one frame out of `stride`, in which case lookups
+/// are rounded down to the closest indexed frame.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SeekIndex {
+	// Byte offset and start time of the indexed frames, in stream order
+	points: Vec<(u64, Duration)>,
+	// Position of the end of the last frame
+	end: u64,
+	duration: Duration,
+}
+
+impl SeekIndex {
+	/// Byte offset of the indexed frame playing at time `t`, or of the closest indexed frame before it.
+	///
+	/// Returns `None` if `t` is not before the end of the audio.
+	pub fn time_to_byte(&self, t: Duration) -> Option<u64> {
+		if t >= self.duration {
+			return None;
+		}
+		let index = self.points.partition_point(|(_, timestamp)| *timestamp <= t);
+		index.checked_sub(1).map(|index| self.points[index].0)
+	}
+
+	/// Start time of the indexed frame containing the byte at `offset`, or of the closest indexed frame before it.
+	///
+	/// Returns `None` if `offset` is before the first frame or after the last one.
+	pub fn byte_to_time(&self, offset: u64) -> Option<Duration> {
+		if offset >= self.end {
+			return None;
+		}
+		let index = self.points.partition_point(|(frame_offset, _)| *frame_offset <= offset);
+		index.checked_sub(1).map(|index| self.points[index].1)
+	}
+
+	/// Byte offset and start time of the indexed frames, in stream order.
+	pub fn points(&self) -> &[(u64, Duration)] {
+		&self.points
+	}
+
+	/// Total duration of the audio frames.
+	pub fn duration(&self) -> Duration {
+		self.duration
+	}
+}
+
+/// Scans an entire mp3 file contained in any struct implementing Read and indexes every audio frame for seeking.
+///
+/// Xing/Info headers are not trusted, and Xing/Info frames do not contain audio so they are not indexed.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use std::fs::File;
+/// use std::io::BufReader;
+/// use std::time::Duration;
+/// use mp3_duration;
+///
+/// let path = Path::new("test/source.mp3");
+/// let file = File::open(path).unwrap();
+/// let mut reader = BufReader::new(file);
+/// let index = mp3_duration::build_seek_index_from_read(&mut reader).unwrap();
+/// println!("One minute in is at byte {:?}", index.time_to_byte(Duration::from_secs(60)));
+/// ```
+pub fn build_seek_index_from_read<T>(reader: &mut T) -> Result<SeekIndex, MP3DurationError>
+where
+	T: Read,
+{
+	build_seek_index_from_read_with_stride(reader, 1)
+}
+
+/// Like `build_seek_index_from_read`, but only indexes the first audio frame and every `stride`-th frame after it
+/// to limit the size of the index. A stride of 0 is the same as 1.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use std::fs::File;
+/// use std::io::BufReader;
+/// use mp3_duration;
+///
+/// let path = Path::new("test/source.mp3");
+/// let file = File::open(path).unwrap();
+/// let mut reader = BufReader::new(file);
+/// let index = mp3_duration::build_seek_index_from_read_with_stride(&mut reader, 38).unwrap();
+/// println!("Indexed {} frames", index.points().len());
+/// ```
+pub fn build_seek_index_from_read_with_stride<T>(reader: &mut T, stride: usize) -> Result<SeekIndex, MP3DurationError>
+where
+	T: Read,
+{
+	let stride = stride.max(1);
+	let options = ParseOptions {
+		trust_first_xing: false,
+		..Default::default()
+	};
+	let mut parser = Parser::new(Context::new(reader), &options);
+	let mut index = SeekIndex::default();
+	let mut frames = 0;
+
+	while let Progress::Continue = parser.measure_next_block_with(|parser, block| match block {
+		Measured::Frame(measured) if measured.is_audio => {
+			if frames % stride == 0 {
+				index.points.push((measured.frame.offset, parser.state.duration()));
+			}
+			frames += 1;
+			index.end = measured.frame.offset + measured.frame.header.frame_length() as u64;
+		}
+		_ => (),
+	})? {}
+
+	index.duration = parser.state.duration();
+	Ok(index)
+}
+
+/// Builds a seek index from the table of contents of the VBRI header of a mp3 file contained in any struct implementing
+/// Read, without scanning the rest of the file.
+///
+/// Fraunhofer encoders write a VBRI header in the first frame of VBR files instead of a Xing header, and its table of
+/// contents is then the only seek index available. Each of its entries becomes a point of the index, so lookups are
+/// rounded down to the start of the segment of `VbriInfo::frames_per_entry` frames they fall in. The duration of the
+/// index is the one declared by the header.
+///
+/// Returns `None` if the first audio frame has no VBRI header, or if its table of contents is empty.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use std::fs::File;
+/// use std::io::BufReader;
+/// use mp3_duration;
+///
+/// let path = Path::new("test/source.mp3");
+/// let file = File::open(path).unwrap();
+/// let mut reader = BufReader::new(file);
+/// if let Some(index) = mp3_duration::vbri_seek_index_from_read(&mut reader).unwrap() {
+///     println!("Indexed {} segments", index.points().len());
+/// }
+/// ```
+pub fn vbri_seek_index_from_read<T>(reader: &mut T) -> Result<Option<SeekIndex>, MP3DurationError>
+where
+	T: Read,
+{
+	let options = ParseOptions::default();
+	let mut parser = Parser::new(Context::new(reader), &options);
+
+	while let Some(block) = parser.next_block()? {
+		if let Block::Frame(frame) = block {
+			let vbri = match frame.vbri {
+				Some(vbri) if !vbri.toc.is_empty() => vbri,
+				_ => return Ok(None),
+			};
+			let samples_per_frame = frame.header.samples_per_frame() as u64;
+			let sampling_rate = frame.header.sampling_rate.hz();
+			let mut index = SeekIndex::default();
+			// The table of contents starts after the frame holding the header
+			let mut offset = frame.offset + frame.header.frame_length() as u64;
+			let mut timestamp = DurationAccumulator::default();
+			for size in &vbri.toc {
+				index.points.push((offset, timestamp.duration()));
+				offset += *size as u64;
+				timestamp.add(vbri.frames_per_entry as u64 * samples_per_frame, sampling_rate);
+			}
+			index.end = offset;
+			let mut duration = DurationAccumulator::default();
+			duration.add(vbri.frames as u64 * samples_per_frame, sampling_rate);
+			index.duration = duration.duration();
+			return Ok(Some(index));
+		}
+	}
+
+	Ok(None)
+}
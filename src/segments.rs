@@ -0,0 +1,108 @@
+use std::io::prelude::*;
+use std::time::Duration;
+
+use crate::context::Context;
+use crate::duration::DurationAccumulator;
+use crate::error::MP3DurationError;
+use crate::options::ParseOptions;
+use crate::parser::{Measured, Parser, Progress};
+use crate::source::ByteSource;
+
+/// Part of a stream made of several concatenated mp3 files, as reported by `segments_from_read`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SegmentInfo {
+	/// Position of the first byte of the segment in the stream.
+	pub start: u64,
+	/// Position of the first byte after the segment.
+	pub end: u64,
+	/// Sum of the durations of the audio frames of the segment.
+	pub duration: Duration,
+	/// Number of audio frames (excluding Xing/Info frames).
+	pub frames: usize,
+	/// Whether the segment contains a Xing/Info frame.
+	pub has_xing: bool,
+}
+
+impl SegmentInfo {
+	fn new(start: u64) -> Self {
+		SegmentInfo {
+			start,
+			end: start,
+			duration: Duration::default(),
+			frames: 0,
+			has_xing: false,
+		}
+	}
+}
+
+/// Splits a stream made of several concatenated mp3 files, contained in any struct implementing Read, and measures
+/// each of them.
+///
+/// A new segment starts whenever a Xing/Info frame or a tag follows audio frames. Tags found after the last audio
+/// frame of the stream belong to the last segment. Like in `from_read_multistream`, every audio frame is measured.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use std::fs::File;
+/// use std::io::prelude::*;
+/// use std::io::BufReader;
+/// use mp3_duration;
+///
+/// let first = File::open(Path::new("test/source.mp3")).unwrap();
+/// let second = File::open(Path::new("test/source.mp3")).unwrap();
+/// let mut reader = BufReader::new(first.chain(second));
+/// for segment in mp3_duration::segments_from_read(&mut reader).unwrap() {
+///     println!("{}..{}: {:?}", segment.start, segment.end, segment.duration);
+/// }
+/// ```
+pub fn segments_from_read<T>(reader: &mut T) -> Result<Vec<SegmentInfo>, MP3DurationError>
+where
+	T: Read,
+{
+	let options = ParseOptions {
+		trust_first_xing: false,
+		..Default::default()
+	};
+	let mut parser = Parser::new(Context::new(reader), &options);
+	let mut segments = Vec::new();
+	let mut current: Option<SegmentInfo> = None;
+	let mut measured = DurationAccumulator::default();
+
+	while let Progress::Continue = parser.measure_next_block_with(|parser, block| {
+		let (start, starts_segment) = match &block {
+			Measured::Tag(offset) => (*offset, true),
+			Measured::Frame(frame) => (frame.frame.offset, frame.frame.xing.is_some()),
+		};
+		let segment = match current.take() {
+			Some(mut segment) if starts_segment && segment.frames > 0 => {
+				segment.duration = measured.duration();
+				segments.push(segment);
+				measured = DurationAccumulator::default();
+				SegmentInfo::new(start)
+			}
+			Some(segment) => segment,
+			None => SegmentInfo::new(start),
+		};
+		let segment = current.get_or_insert(segment);
+
+		if let Measured::Frame(frame) = block {
+			segment.has_xing |= frame.frame.xing.is_some();
+			if frame.complete && frame.is_audio {
+				segment.frames += 1;
+				measured.add_frame(&frame.frame.header);
+			}
+		}
+		segment.end = parser.source.bytes_read();
+	})? {}
+
+	if let Some(mut segment) = current {
+		segment.duration = measured.duration();
+		match segments.last_mut() {
+			Some(last) if segment.frames == 0 => last.end = segment.end,
+			_ => segments.push(segment),
+		}
+	}
+	Ok(segments)
+}
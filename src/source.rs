@@ -0,0 +1,182 @@
+use std::time::Duration;
+
+use crate::error::{ErrorKind, MP3DurationError};
+
+/// Byte stream consumed by the parser, keeping track of how many bytes were consumed so that errors can report where
+/// they occurred: the `offset` of a `MP3DurationError` is the value of `bytes_read` when it was detected.
+///
+/// When the stream ends before a read or skip could complete, the remaining bytes are consumed and
+/// `ErrorKind::UnexpectedEOF` is returned.
+///
+/// This trait is implemented by `Context` and `BufContext`, and cannot be implemented outside of this crate.
+pub trait ByteSource: sealed::Sealed {
+	/// Fills `buffer` with the next bytes of the stream.
+	fn read_exact(&mut self, buffer: &mut [u8]) -> Result<(), ErrorKind>;
+
+	/// Consumes `num_bytes` bytes.
+	fn skip(&mut self, num_bytes: usize) -> Result<(), ErrorKind>;
+
+	/// Fills `buffer` with the next bytes of the stream without consuming them. Returns how many bytes were available,
+	/// which is less than the size of `buffer` only at the end of the stream.
+	fn peek(&mut self, buffer: &mut [u8]) -> Result<usize, ErrorKind>;
+
+	/// Number of bytes consumed so far.
+	fn bytes_read(&self) -> u64;
+
+	/// Whether the last read or skip failed because the stream ended.
+	fn reached_eof(&self) -> bool;
+}
+
+// Prevents implementing `ByteSource` outside of this crate, so that methods can be added to it
+pub(crate) mod sealed {
+	pub trait Sealed {}
+}
+
+// Operations the parser needs on top of `ByteSource`. They are kept out of the public API as they are not checked:
+// `unread` trusts that the bytes were just read.
+pub(crate) trait Source: ByteSource {
+	// Puts back bytes which were just read, so the next reads return them again.
+	fn unread(&mut self, bytes: &[u8]);
+
+	// Consumes a run of 0x00 bytes, stopping after `max_bytes` of them. Returns how many were consumed.
+	fn skip_zeros(&mut self, max_bytes: usize) -> Result<usize, ErrorKind>;
+}
+
+// Helpers built on the public methods of byte sources
+pub(crate) trait SourceExt: ByteSource {
+	// Like `ByteSource::skip`, but stops without error at the end of the stream. Returns how many bytes were skipped.
+	fn skip_at_most(&mut self, num_bytes: usize) -> Result<usize, ErrorKind> {
+		let start = self.bytes_read();
+		match self.skip(num_bytes) {
+			Ok(()) => Ok(num_bytes),
+			Err(ErrorKind::UnexpectedEOF) if self.reached_eof() => Ok((self.bytes_read() - start) as usize),
+			Err(e) => Err(e),
+		}
+	}
+
+	// Checks whether the stream is exhausted without consuming anything.
+	fn at_eof(&mut self) -> Result<bool, ErrorKind> {
+		Ok(self.peek(&mut [0; 1])? == 0)
+	}
+
+	// Error located at the current position in the stream. `at_duration` is the duration measured so far.
+	// `MP3DurationError::first_header` is left empty.
+	fn error(&self, kind: ErrorKind, at_duration: Duration) -> MP3DurationError {
+		MP3DurationError {
+			kind,
+			offset: self.bytes_read(),
+			at_duration,
+			first_header: None,
+		}
+	}
+}
+
+impl<S: ByteSource + ?Sized> SourceExt for S {}
+
+// Source reading from an in-memory buffer.
+//
+// A source which is not final only holds the beginning of the data still to come. Running out of data while reading
+// then flags the source as `incomplete`, and skipping past its end records the missing bytes in `deferred_skip`
+// instead of failing.
+pub struct SliceSource<'b> {
+	data: &'b [u8],
+	position: usize,
+	base_offset: u64,
+	is_final: bool,
+	reached_eof: bool,
+	pub incomplete: bool,
+	pub deferred_skip: usize,
+}
+
+impl<'b> SliceSource<'b> {
+	// `base_offset` is the position of `data` within the whole stream.
+	pub fn new(data: &'b [u8], base_offset: u64, is_final: bool) -> Self {
+		SliceSource {
+			data,
+			position: 0,
+			base_offset,
+			is_final,
+			reached_eof: false,
+			incomplete: false,
+			deferred_skip: 0,
+		}
+	}
+
+	// Number of bytes consumed from `data`.
+	pub fn position(&self) -> usize {
+		self.position
+	}
+
+	fn remaining(&self) -> usize {
+		self.data.len() - self.position
+	}
+
+	fn run_out(&mut self) -> ErrorKind {
+		if !self.is_final {
+			self.incomplete = true;
+		}
+		self.position = self.data.len();
+		self.reached_eof = true;
+		ErrorKind::UnexpectedEOF
+	}
+}
+
+impl<'b> ByteSource for SliceSource<'b> {
+	fn read_exact(&mut self, buffer: &mut [u8]) -> Result<(), ErrorKind> {
+		if buffer.len() > self.remaining() {
+			return Err(self.run_out());
+		}
+		buffer.copy_from_slice(&self.data[self.position..self.position + buffer.len()]);
+		self.position += buffer.len();
+		self.reached_eof = false;
+		Ok(())
+	}
+
+	fn skip(&mut self, num_bytes: usize) -> Result<(), ErrorKind> {
+		if num_bytes > self.remaining() {
+			if !self.is_final {
+				self.deferred_skip = num_bytes - self.remaining();
+				self.position = self.data.len();
+				return Ok(());
+			}
+			return Err(self.run_out());
+		}
+		self.position += num_bytes;
+		Ok(())
+	}
+
+	fn peek(&mut self, buffer: &mut [u8]) -> Result<usize, ErrorKind> {
+		let available = buffer.len().min(self.remaining());
+		if available < buffer.len() && !self.is_final {
+			self.incomplete = true;
+		}
+		buffer[..available].copy_from_slice(&self.data[self.position..self.position + available]);
+		Ok(available)
+	}
+
+	fn bytes_read(&self) -> u64 {
+		self.base_offset + self.position as u64
+	}
+
+	fn reached_eof(&self) -> bool {
+		self.reached_eof
+	}
+}
+
+impl<'b> sealed::Sealed for SliceSource<'b> {}
+
+impl<'b> Source for SliceSource<'b> {
+	fn unread(&mut self, bytes: &[u8]) {
+		self.position -= bytes.len();
+		self.reached_eof = false;
+	}
+
+	fn skip_zeros(&mut self, max_bytes: usize) -> Result<usize, ErrorKind> {
+		let num_zeros = self.data[self.position..].iter().take(max_bytes).take_while(|b| **b == 0).count();
+		self.position += num_zeros;
+		if self.remaining() == 0 && num_zeros < max_bytes && !self.is_final {
+			self.incomplete = true;
+		}
+		Ok(num_zeros)
+	}
+}
@@ -0,0 +1,509 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::error::ErrorKind;
+use crate::options::ParseOptions;
+use crate::source::{ByteSource, SourceExt};
+use crate::warning::{Warning, WarningKind};
+
+/// Extension point to recognize and skip over non-audio blocks.
+///
+/// Handlers are consulted when the four bytes at the current position are neither an MPEG frame header nor a tag
+/// already handled by an earlier handler.
+pub trait TagHandler {
+	/// Inspects the four bytes which were just read (`magic`) and, if they are the start of a block this handler
+	/// recognizes, consumes the rest of the block from `source` and returns its total size in bytes (magic included).
+	///
+	/// Returns `None` if the block is not recognized, in which case nothing must have been consumed from `source`
+	/// beyond `magic`: use `ByteSource::peek` to look at the bytes following it before recognizing the block.
+	fn try_handle(&self, magic: &[u8], source: &mut dyn ByteSource) -> Option<Result<usize, ErrorKind>>;
+
+	/// Like `try_handle`, but anomalies which the handler worked around can be reported in `warnings`. This is the
+	/// method called by the parser, and defaults to calling `try_handle`.
+	fn try_handle_with_warnings(
+		&self,
+		magic: &[u8],
+		source: &mut dyn ByteSource,
+		warnings: &mut Vec<Warning>,
+	) -> Option<Result<usize, ErrorKind>> {
+		let _ = warnings;
+		self.try_handle(magic, source)
+	}
+}
+
+/// Kinds of non-audio blocks, see `ErrorKind::UnexpectedTag`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TagKind {
+	ID3v1,
+	ID3v2,
+	APEv2,
+	Lyrics3,
+	/// Block recognized by a custom `TagHandler`.
+	Custom,
+}
+
+impl TagKind {
+	// Recognizes the start of a well-known tag from its first bytes, or returns `None` if more bytes are needed
+	pub(crate) fn detect(bytes: &[u8]) -> Option<TagKind> {
+		if bytes.starts_with(b"ID3") {
+			Some(TagKind::ID3v2)
+		} else if bytes.starts_with(b"APETAGEX") {
+			Some(TagKind::APEv2)
+		} else if bytes.starts_with(b"LYRICSBEGIN") {
+			Some(TagKind::Lyrics3)
+		} else if bytes.starts_with(b"TAG") {
+			Some(TagKind::ID3v1)
+		} else {
+			None
+		}
+	}
+}
+
+impl fmt::Display for TagKind {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			TagKind::ID3v1 => write!(f, "ID3v1"),
+			TagKind::ID3v2 => write!(f, "ID3v2"),
+			TagKind::APEv2 => write!(f, "APEv2"),
+			TagKind::Lyrics3 => write!(f, "Lyrics3"),
+			TagKind::Custom => write!(f, "custom"),
+		}
+	}
+}
+
+// Settings shared by the built-in tag handlers
+#[derive(Clone, Copy, Debug)]
+struct TagSettings {
+	lenient: bool,
+	max_tag_bytes: usize,
+}
+
+impl TagSettings {
+	fn new(options: &ParseOptions) -> Self {
+		TagSettings {
+			lenient: options.lenient,
+			max_tag_bytes: options.max_tag_bytes,
+		}
+	}
+
+	// In lenient mode, a tag cut short by the end of the stream is not an error.
+	fn skip(&self, source: &mut dyn ByteSource, num_bytes: usize) -> Result<usize, ErrorKind> {
+		if num_bytes > self.max_tag_bytes {
+			return Err(ErrorKind::TagTooLarge { declared: num_bytes });
+		}
+		if self.lenient {
+			return source.skip_at_most(num_bytes);
+		}
+		source.skip(num_bytes)?;
+		Ok(num_bytes)
+	}
+
+	// Reads the next bytes of a tag into `buffer`, and returns how many were read. Like `skip`, only lenient mode
+	// tolerates a tag cut short by the end of the stream.
+	fn read(&self, source: &mut dyn ByteSource, buffer: &mut [u8]) -> Result<usize, ErrorKind> {
+		let available = source.peek(buffer)?;
+		source.skip(available)?;
+		if available < buffer.len() && !self.lenient {
+			return Err(ErrorKind::UnexpectedEOF);
+		}
+		Ok(available)
+	}
+}
+
+/// Skips ID3v2 tags.
+///
+/// The footer of ID3v2.4 tags is skipped along with them. The extended header of ID3v2.3 and ID3v2.4 tags is part of
+/// the declared tag size, but a tag is never taken to be shorter than its extended header.
+///
+/// In lenient mode, a tag size which is not a valid synchsafe integer (a byte has its high bit set) is decoded as a
+/// plain 32-bit integer instead, as written by some buggy taggers, and `WarningKind::NonSynchsafeTagSize` is reported.
+///
+/// With `ParseOptions::detect_album_art`, the frames of tags are walked by the parser instead of skipped as a whole,
+/// to find attached pictures (see `Metadata::has_album_art`).
+#[derive(Clone, Copy, Debug)]
+pub struct ID3v2Handler {
+	settings: TagSettings,
+}
+
+impl ID3v2Handler {
+	/// Creates a handler honoring the tag related settings of `options`.
+	pub fn new(options: &ParseOptions) -> Self {
+		ID3v2Handler {
+			settings: TagSettings::new(options),
+		}
+	}
+
+	// Like `try_handle_with_warnings`, but the frames of the tag are walked instead of being skipped, and the sizes of
+	// its attached pictures are added to `pictures`.
+	pub(crate) fn try_find_pictures(
+		&self,
+		magic: &[u8],
+		source: &mut dyn ByteSource,
+		warnings: &mut Vec<Warning>,
+		pictures: &mut Vec<usize>,
+	) -> Option<Result<usize, ErrorKind>> {
+		if &magic[0..3] != b"ID3" {
+			return None;
+		}
+		Some(self.skip_tag(magic[3], source, warnings, Some(pictures)))
+	}
+
+	fn skip_tag(
+		&self,
+		version: u8,
+		source: &mut dyn ByteSource,
+		warnings: &mut Vec<Warning>,
+		pictures: Option<&mut Vec<usize>>,
+	) -> Result<usize, ErrorKind> {
+		let offset = source.bytes_read() - 4;
+		let mut id3v2 = [0; 6]; // 4 bytes already read
+		source.read_exact(&mut id3v2)?;
+		let flags = id3v2[1];
+		// The footer flag only exists since ID3v2.4, previous versions leave this bit unused
+		let has_footer = version >= 4 && 0 != (flags & 0b0001_0000);
+		let footer_size: usize = if has_footer { 10 } else { 0 };
+		// Before ID3v2.3, this bit is the compression flag and there is no extended header
+		let has_extended_header = version >= 3 && 0 != (flags & 0b0100_0000);
+		// Before ID3v2.4, unsynchronisation applies to the whole tag, frame headers included, which cannot be walked
+		let is_unsynchronised = version < 4 && 0 != (flags & 0b1000_0000);
+		let pictures = pictures.filter(|_| !is_unsynchronised);
+		let size_bytes = [id3v2[2], id3v2[3], id3v2[4], id3v2[5]];
+		let is_synchsafe = size_bytes.iter().all(|b| b & 0x80 == 0);
+		let tag_size: usize = if is_synchsafe || !self.settings.lenient {
+			((id3v2[5] as u32) | ((id3v2[4] as u32) << 7) | ((id3v2[3] as u32) << 14) | ((id3v2[2] as u32) << 21))
+				as usize
+		} else {
+			let size = u32::from_be_bytes(size_bytes);
+			warnings.push(Warning {
+				kind: WarningKind::NonSynchsafeTagSize { size },
+				offset,
+			});
+			size as usize
+		};
+		if !has_extended_header {
+			let skipped = self.skip_frames(version, source, tag_size, footer_size, pictures)?;
+			return Ok(4 + id3v2.len() + skipped);
+		}
+
+		let mut size_bytes = [0; 4];
+		source.read_exact(&mut size_bytes)?;
+		// The size of ID3v2.3 extended headers excludes its own 4 bytes, while ID3v2.4 uses a synchsafe integer
+		// including them
+		let extended_header_size = if version == 3 {
+			4 + u32::from_be_bytes(size_bytes) as usize
+		} else {
+			size_bytes.iter().fold(0, |size, b| (size << 7) | (b & 0x7F) as usize)
+		};
+		let rest = tag_size.max(extended_header_size).saturating_sub(size_bytes.len());
+		let skipped = self.skip_frames(version, source, rest, footer_size, pictures)?;
+		Ok(4 + id3v2.len() + size_bytes.len() + skipped)
+	}
+
+	// Skips the frames of a tag, taking `frames_size` bytes, followed by its footer. With `pictures`, frames are walked
+	// until the padding and the content size of attached pictures (APIC frames, or PIC frames before ID3v2.3) is added
+	// to it. Returns the number of bytes skipped.
+	fn skip_frames(
+		&self,
+		version: u8,
+		source: &mut dyn ByteSource,
+		frames_size: usize,
+		footer_size: usize,
+		pictures: Option<&mut Vec<usize>>,
+	) -> Result<usize, ErrorKind> {
+		let pictures = match pictures {
+			Some(pictures) => pictures,
+			None => return self.settings.skip(source, frames_size + footer_size),
+		};
+		if frames_size + footer_size > self.settings.max_tag_bytes {
+			return Err(ErrorKind::TagTooLarge { declared: frames_size + footer_size });
+		}
+		let (picture_id, header_size): (&[u8], usize) = if version < 3 { (b"PIC", 6) } else { (b"APIC", 10) };
+		let mut walked = 0;
+		while walked + header_size <= frames_size {
+			let mut header = [0; 10];
+			let header = &mut header[..header_size];
+			let skipped = self.settings.read(source, header)?;
+			walked += skipped;
+			if skipped < header_size || header[0] == 0 {
+				break;
+			}
+			let size = match version {
+				0..=2 => u32::from_be_bytes([0, header[3], header[4], header[5]]) as usize,
+				3 => u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as usize,
+				_ => header[4..8].iter().fold(0, |size, b| (size << 7) | (b & 0x7F) as usize),
+			};
+			let size = size.min(frames_size - walked);
+			if &header[..picture_id.len()] == picture_id {
+				pictures.push(size);
+			}
+			let skipped = self.settings.skip(source, size)?;
+			walked += skipped;
+			if skipped < size {
+				return Ok(walked);
+			}
+		}
+		let skipped = self.settings.skip(source, (frames_size - walked) + footer_size)?;
+		Ok(walked + skipped)
+	}
+}
+
+impl TagHandler for ID3v2Handler {
+	fn try_handle(&self, magic: &[u8], source: &mut dyn ByteSource) -> Option<Result<usize, ErrorKind>> {
+		self.try_handle_with_warnings(magic, source, &mut Vec::new())
+	}
+
+	fn try_handle_with_warnings(
+		&self,
+		magic: &[u8],
+		source: &mut dyn ByteSource,
+		warnings: &mut Vec<Warning>,
+	) -> Option<Result<usize, ErrorKind>> {
+		if &magic[0..3] != b"ID3" {
+			return None;
+		}
+		Some(self.skip_tag(magic[3], source, warnings, None))
+	}
+}
+
+/// Fields of an ID3v1 tag, decoded from Latin-1 with their padding removed.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ID3v1Tag {
+	pub title: String,
+	pub artist: String,
+	pub album: String,
+	pub year: String,
+	pub comment: String,
+	/// Track number, only present in ID3v1.1 tags.
+	pub track: Option<u8>,
+	/// Index of the genre in the list defined by ID3v1 and extended by Winamp, 255 when unset.
+	pub genre: u8,
+}
+
+impl ID3v1Tag {
+	const SIZE: usize = 128;
+
+	fn parse(bytes: &[u8; Self::SIZE]) -> Self {
+		// Fields are padded with zeros or spaces
+		let text = |field: &[u8]| -> String {
+			let field = field.split(|byte| *byte == 0).next().unwrap_or_default();
+			let chars = field.iter().map(|byte| *byte as char).collect::<String>();
+			chars.trim_end_matches(' ').to_owned()
+		};
+		let comment = &bytes[97..127];
+		// ID3v1.1 stores the track number in the last byte of the comment, after a zero
+		let (comment, track) = match (comment[28], comment[29]) {
+			(0, track) if track != 0 => (&comment[..28], Some(track)),
+			_ => (comment, None),
+		};
+		ID3v1Tag {
+			title: text(&bytes[3..33]),
+			artist: text(&bytes[33..63]),
+			album: text(&bytes[63..93]),
+			year: text(&bytes[93..97]),
+			comment: text(comment),
+			track,
+			genre: bytes[127],
+		}
+	}
+}
+
+/// Skips ID3v1 tags.
+///
+/// In lenient mode, `TAG` is only recognized as an ID3v1 tag if it is the last block of the stream, because it may
+/// otherwise be audio data which lost sync.
+///
+/// With `ParseOptions::parse_id3v1`, the fields of tags are decoded by the parser instead of skipped (see
+/// `Metadata::id3v1`).
+#[derive(Clone, Copy, Debug)]
+pub struct ID3v1Handler {
+	settings: TagSettings,
+}
+
+impl ID3v1Handler {
+	/// Creates a handler honoring the tag related settings of `options`.
+	pub fn new(options: &ParseOptions) -> Self {
+		ID3v1Handler {
+			settings: TagSettings::new(options),
+		}
+	}
+
+	// Like `try_handle`, but the fields of the tag are decoded into `tag` (unless it is already set) instead of being
+	// skipped.
+	pub(crate) fn try_read_fields(
+		&self,
+		magic: &[u8],
+		source: &mut dyn ByteSource,
+		tag: &mut Option<ID3v1Tag>,
+	) -> Option<Result<usize, ErrorKind>> {
+		self.handle(magic, source, Some(tag))
+	}
+
+	fn handle(
+		&self,
+		magic: &[u8],
+		source: &mut dyn ByteSource,
+		tag: Option<&mut Option<ID3v1Tag>>,
+	) -> Option<Result<usize, ErrorKind>> {
+		if &magic[0..3] != b"TAG" {
+			return None;
+		}
+		if !self.settings.lenient && tag.is_none() {
+			return Some(self.settings.skip(source, ID3v1Tag::SIZE - magic.len()).map(|_| ID3v1Tag::SIZE));
+		}
+		// In lenient mode, the tag is only recognized when the stream ends right after it
+		if self.settings.lenient {
+			let mut rest_and_next_byte = [0; ID3v1Tag::SIZE - 4 + 1];
+			match source.peek(&mut rest_and_next_byte) {
+				Ok(available) if available == rest_and_next_byte.len() => return None,
+				Ok(_) => (),
+				Err(e) => return Some(Err(e)),
+			}
+		}
+		let mut bytes = [0; ID3v1Tag::SIZE];
+		bytes[..4].copy_from_slice(magic);
+		match source.read_exact(&mut bytes[4..]) {
+			Ok(_) => (),
+			Err(_) if self.settings.lenient && source.reached_eof() => return Some(Ok(magic.len())),
+			Err(e) => return Some(Err(e)),
+		};
+		if let Some(tag) = tag {
+			tag.get_or_insert_with(|| ID3v1Tag::parse(&bytes));
+		}
+		Some(Ok(ID3v1Tag::SIZE))
+	}
+}
+
+impl TagHandler for ID3v1Handler {
+	fn try_handle(&self, magic: &[u8], source: &mut dyn ByteSource) -> Option<Result<usize, ErrorKind>> {
+		self.handle(magic, source, None)
+	}
+}
+
+/// Skips APEv2 tags.
+///
+/// Tags are recognized by their header, which is only present when they are written at the start of the file or
+/// before other tags, and are then skipped along with their items and footer (if any). A footer found on its own
+/// belongs to a tag without header nor items, and only the footer is skipped.
+///
+/// With `ParseOptions::parse_ape_items`, the items of tags with a header are read by the parser instead of skipped
+/// (see `Metadata::ape_items`).
+#[derive(Clone, Copy, Debug)]
+pub struct APEv2Handler {
+	settings: TagSettings,
+}
+
+impl APEv2Handler {
+	/// Creates a handler honoring the tag related settings of `options`.
+	pub fn new(options: &ParseOptions) -> Self {
+		APEv2Handler {
+			settings: TagSettings::new(options),
+		}
+	}
+
+	// Like `try_handle`, but the items of a tag with a header are added to `items` instead of being skipped. Items
+	// with a key which is already present are ignored.
+	pub(crate) fn try_read_items(
+		&self,
+		magic: &[u8],
+		source: &mut dyn ByteSource,
+		items: &mut HashMap<String, Vec<u8>>,
+	) -> Option<Result<usize, ErrorKind>> {
+		self.handle(magic, source, Some(items))
+	}
+
+	fn handle(
+		&self,
+		magic: &[u8],
+		source: &mut dyn ByteSource,
+		items: Option<&mut HashMap<String, Vec<u8>>>,
+	) -> Option<Result<usize, ErrorKind>> {
+		if magic != b"APET" {
+			return None;
+		}
+		let mut ape_header = [0; 12];
+		match source.peek(&mut ape_header) {
+			Ok(available) if available == ape_header.len() && &ape_header[0..4] != b"AGEX" => return None,
+			Ok(_) => (),
+			Err(e) => return Some(Err(e)),
+		}
+		if let Err(e) = source.read_exact(&mut ape_header) {
+			return Some(Err(e));
+		}
+		// Size of the items and footer, excluding the header
+		let tag_size: usize = ((ape_header[8] as u32)
+			| ((ape_header[9] as u32) << 8)
+			| ((ape_header[10] as u32) << 16)
+			| ((ape_header[11] as u32) << 24)) as usize;
+		let mut rest_of_header = [0; 16];
+		if let Err(e) = source.read_exact(&mut rest_of_header) {
+			return Some(Err(e));
+		}
+		let flags = u32::from_le_bytes([rest_of_header[4], rest_of_header[5], rest_of_header[6], rest_of_header[7]]);
+		let is_header = flags & (1 << 29) != 0;
+		let header_size = magic.len() + ape_header.len() + rest_of_header.len();
+		if let (Some(items), true) = (items, is_header) {
+			let read = self.read_items(source, tag_size, flags, items);
+			return Some(read.map(|read| header_size + read));
+		}
+		let rest_of_tag = if is_header { tag_size } else { 0 };
+		let skipped = self.settings.skip(source, rest_of_tag);
+		Some(skipped.map(|skipped| header_size + skipped))
+	}
+
+	// Reads the items and footer (if any) following a header, and returns their size
+	fn read_items(
+		&self,
+		source: &mut dyn ByteSource,
+		tag_size: usize,
+		flags: u32,
+		items: &mut HashMap<String, Vec<u8>>,
+	) -> Result<usize, ErrorKind> {
+		if tag_size > self.settings.max_tag_bytes {
+			return Err(ErrorKind::TagTooLarge { declared: tag_size });
+		}
+		let has_footer = flags & (1 << 30) == 0;
+		let footer_size = if has_footer { tag_size.min(32) } else { 0 };
+		let items_size = tag_size - footer_size;
+		// Items are read in chunks, so that memory is only allocated for the bytes found in the stream rather than for
+		// the declared size. In lenient mode, the items fitting before the end of the stream are kept.
+		let mut bytes = Vec::new();
+		let mut chunk = [0; 4096];
+		while bytes.len() < items_size {
+			let chunk = &mut chunk[..(items_size - bytes.len()).min(4096)];
+			let available = self.settings.read(source, chunk)?;
+			bytes.extend_from_slice(&chunk[..available]);
+			if available < chunk.len() {
+				break;
+			}
+		}
+		parse_ape_items(&bytes, items);
+		let skipped = self.settings.skip(source, footer_size)?;
+		Ok(bytes.len() + skipped)
+	}
+}
+
+// Adds APEv2 items to `items`, until one does not fit in `bytes`. Each item is made of the size of its value and of
+// flags (32-bit little endian integers), followed by its zero-terminated key and its value.
+fn parse_ape_items(mut bytes: &[u8], items: &mut HashMap<String, Vec<u8>>) {
+	while bytes.len() > 8 {
+		let size = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize;
+		let key_end = match bytes[8..].iter().position(|byte| *byte == 0) {
+			Some(key_size) => 8 + key_size,
+			None => return,
+		};
+		let value = match bytes[key_end + 1..].get(..size) {
+			Some(value) => value,
+			None => return,
+		};
+		let key = String::from_utf8_lossy(&bytes[8..key_end]).into_owned();
+		items.entry(key).or_insert_with(|| value.to_vec());
+		bytes = &bytes[key_end + 1 + size..];
+	}
+}
+
+impl TagHandler for APEv2Handler {
+	fn try_handle(&self, magic: &[u8], source: &mut dyn ByteSource) -> Option<Result<usize, ErrorKind>> {
+		self.handle(magic, source, None)
+	}
+}
@@ -0,0 +1,72 @@
+use std::io::prelude::*;
+use std::path::Path;
+use std::time::Duration;
+
+use tar::{Archive, EntryType};
+
+use crate::error::MP3DurationError;
+
+fn is_mp3(path: &Path) -> bool {
+	path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("mp3"))
+}
+
+/// Measures the duration of every `.mp3` file of a tar archive contained in any struct implementing Read, without
+/// extracting it.
+///
+/// Returns the path of each file within the archive along with its duration, in the order they appear in the archive.
+/// The whole archive is read before the first result is available. An error reading the archive itself is reported
+/// as a last item, with the path of the entry which could not be read if it is known.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::path::Path;
+/// use std::fs::File;
+/// use std::io::BufReader;
+/// use mp3_duration;
+///
+/// let path = Path::new("music.tar");
+/// let file = File::open(path).unwrap();
+/// for (name, duration) in mp3_duration::from_tar(BufReader::new(file)) {
+///     println!("{}: {:?}", name, duration);
+/// }
+/// ```
+pub fn from_tar<R>(reader: R) -> impl Iterator<Item = (String, Result<Duration, MP3DurationError>)>
+where
+	R: Read,
+{
+	let mut archive = Archive::new(reader);
+	let mut results = Vec::new();
+	let entries = match archive.entries() {
+		Ok(entries) => entries,
+		Err(e) => {
+			results.push((String::new(), Err(MP3DurationError::at_start(e.into()))));
+			return results.into_iter();
+		}
+	};
+
+	for entry in entries {
+		let mut entry = match entry {
+			Ok(entry) => entry,
+			Err(e) => {
+				results.push((String::new(), Err(MP3DurationError::at_start(e.into()))));
+				break;
+			}
+		};
+		let name = match entry.path() {
+			Ok(path) if is_mp3(&path) => path.to_string_lossy().into_owned(),
+			Ok(_) => continue,
+			Err(e) => {
+				results.push((String::new(), Err(MP3DurationError::at_start(e.into()))));
+				break;
+			}
+		};
+		if entry.header().entry_type() != EntryType::Regular {
+			continue;
+		}
+		let duration = crate::from_read(&mut entry);
+		results.push((name, duration));
+	}
+
+	results.into_iter()
+}
@@ -1,87 +1,188 @@
+use std::fs::File;
+use std::io::Cursor;
 use std::path::Path;
+use std::time::Duration;
 
-use crate::error::ErrorKind;
-use crate::from_path;
+use crate::testutil::assert_duration_approx;
+use crate::*;
 
 #[test]
 fn lame_398_constant_bitrate_320() {
 	let path = Path::new("test/CBR320.mp3");
 	let duration = from_path(path).unwrap();
-	assert_eq!(398, duration.as_secs());
-	let nanos = duration.subsec_nanos();
-	assert!(2 * 100_000_000 < nanos && nanos < 4 * 100_000_000);
+	assert_duration_approx(duration, 398.3, 100);
 }
 
 #[test]
 fn lame_398_variable_bitrate_v0() {
 	let path = Path::new("test/VBR0.mp3");
 	let duration = from_path(path).unwrap();
-	assert_eq!(398, duration.as_secs());
-	let nanos = duration.subsec_nanos();
-	assert!(2 * 100_000_000 < nanos && nanos < 4 * 100_000_000);
+	assert_duration_approx(duration, 398.3, 100);
 }
 
 #[test]
 fn lame_398_variable_bitrate_v9() {
 	let path = Path::new("test/VBR9.mp3");
 	let duration = from_path(path).unwrap();
-	assert_eq!(398, duration.as_secs());
-	let nanos = duration.subsec_nanos();
-	assert!(2 * 100_000_000 < nanos && nanos < 4 * 100_000_000);
+	assert_duration_approx(duration, 398.3, 100);
 }
 
 #[test]
 fn id3v1() {
 	let path = Path::new("test/ID3v1.mp3");
 	let duration = from_path(path).unwrap();
-	assert_eq!(398, duration.as_secs());
-	let nanos = duration.subsec_nanos();
-	assert!(2 * 100_000_000 < nanos && nanos < 4 * 100_000_000);
+	assert_duration_approx(duration, 398.3, 100);
+}
+
+#[test]
+fn parse_id3v1() {
+	let options = ParseOptions {
+		parse_id3v1: true,
+		..Default::default()
+	};
+	let measured = from_read_with_metadata(&mut File::open("test/ID3v1.mp3").unwrap(), &options).unwrap();
+	let expected = ID3v1Tag {
+		title: "Beethoven - Piano Sonata nr.15".to_owned(),
+		artist: "Karine Gilanyan".to_owned(),
+		album: "Beethoven's Sonata No. 15 in D".to_owned(),
+		year: "2017".to_owned(),
+		comment: String::new(),
+		track: None,
+		genre: 32,
+	};
+	assert_eq!(Some(expected), measured.metadata().id3v1);
+	assert_eq!(from_path("test/ID3v1.mp3").unwrap(), *measured);
+	let measured = from_read_with_metadata(&mut File::open("test/ID3v1.mp3").unwrap(), &Default::default()).unwrap();
+	assert_eq!(None, measured.metadata().id3v1);
+
+	// ID3v1.1 tag with Latin-1 text padded with spaces
+	let mut tag = b"TAGCaf\xE9".to_vec();
+	tag.resize(97, b' ');
+	tag.extend_from_slice(b"Comment");
+	tag.resize(125, 0);
+	tag.extend_from_slice(&[0, 7, 255]);
+	let data = [synthetic_frames(3), tag].concat();
+	for lenient in &[false, true] {
+		let options = ParseOptions {
+			lenient: *lenient,
+			..options.clone()
+		};
+		let measured = from_read_with_metadata(&mut Cursor::new(&data), &options).unwrap();
+		let id3v1 = measured.metadata().id3v1.clone().unwrap();
+		assert_eq!("Caf\u{E9}", id3v1.title);
+		assert_eq!("", id3v1.artist);
+		assert_eq!("Comment", id3v1.comment);
+		assert_eq!(Some(7), id3v1.track);
+		assert_eq!(255, id3v1.genre);
+		assert_eq!(synthetic_duration(3), *measured);
+	}
 }
 
 #[test]
 fn id3v2() {
 	let path = Path::new("test/ID3v2.mp3");
 	let duration = from_path(path).unwrap();
-	assert_eq!(398, duration.as_secs());
-	let nanos = duration.subsec_nanos();
-	assert!(2 * 100_000_000 < nanos && nanos < 4 * 100_000_000);
+	assert_duration_approx(duration, 398.3, 100);
 }
 
 #[test]
 fn id3v2_with_image() {
 	let path = Path::new("test/ID3v2WithImage.mp3");
 	let duration = from_path(path).unwrap();
-	assert_eq!(398, duration.as_secs());
-	let nanos = duration.subsec_nanos();
-	assert!(2 * 100_000_000 < nanos && nanos < 4 * 100_000_000);
+	assert_duration_approx(duration, 398.3, 100);
 }
 
 #[test]
 fn id3v2_empty() {
 	let path = Path::new("test/SineEmptyID3.mp3");
 	let duration = from_path(path).unwrap();
-	assert_eq!(1, duration.as_secs());
-	let nanos = duration.subsec_nanos();
-	assert!(0 < nanos && nanos < 1 * 100_000_000);
+	assert_duration_approx(duration, 1.05, 50);
 }
 
 #[test]
 fn id3v2_bad_padding() {
 	let path = Path::new("test/ID3v2WithBadPadding.mp3");
 	let duration = from_path(path).unwrap();
-	assert_eq!(398, duration.as_secs());
-	let nanos = duration.subsec_nanos();
-	assert!(2 < nanos && nanos < 3 * 100_000_000);
+	assert_duration_approx(duration, 398.15, 150);
 }
 
 #[test]
 fn apev2() {
 	let path = Path::new("test/APEv2.mp3");
 	let duration = from_path(path).unwrap();
-	assert_eq!(398, duration.as_secs());
-	let nanos = duration.subsec_nanos();
-	assert!(2 < nanos && nanos < 4 * 100_000_000);
+	assert_duration_approx(duration, 398.2, 200);
+}
+
+#[test]
+fn ape_items() {
+	let options = ParseOptions {
+		parse_ape_items: true,
+		..Default::default()
+	};
+	let measured = from_read_with_metadata(&mut File::open("test/APEv2.mp3").unwrap(), &options).unwrap();
+	let items = &measured.metadata().ape_items;
+	assert_eq!(9, items.len());
+	assert_eq!(Some(&b"Beethoven's Sonata No. 15 in D Major".to_vec()), items.get("Album"));
+	assert_eq!(from_path("test/APEv2.mp3").unwrap(), *measured);
+	let measured = from_read_with_metadata(&mut File::open("test/LeadingAPEv2.mp3").unwrap(), &options).unwrap();
+	assert_eq!(9, measured.metadata().ape_items.len());
+	assert_eq!(from_path("test/LeadingAPEv2.mp3").unwrap(), *measured);
+
+	// Items are skipped by default
+	let measured = from_read_with_metadata(&mut File::open("test/APEv2.mp3").unwrap(), &Default::default()).unwrap();
+	assert!(measured.metadata().ape_items.is_empty());
+
+	// Truncated tags
+	let data = std::fs::read("test/LeadingAPEv2.mp3").unwrap();
+	let error = from_read_with_metadata(&mut Cursor::new(&data[..100]), &options).unwrap_err();
+	assert!(matches!(error.kind, ErrorKind::UnexpectedEOF));
+	let options = ParseOptions {
+		lenient: true,
+		..options
+	};
+	let measured = from_read_with_metadata(&mut Cursor::new(&data[..100]), &options).unwrap();
+	assert_eq!(Some(&b"0".to_vec()), measured.metadata().ape_items.get("Track"));
+
+	// Tiny stream declaring a huge tag, whose items are only read as far as the stream goes
+	let mut data = b"APETAGEX\xD0\x07\x00\x00\xFF\xFF\xFF\xFF\x01\x00\x00\x00\x00\x00\x00\xA0".to_vec();
+	data.extend_from_slice(&[0; 8]);
+	data.extend_from_slice(b"\x01\x00\x00\x00\x00\x00\x00\x00A\x00B");
+	let options = ParseOptions {
+		max_tag_bytes: usize::MAX,
+		..options
+	};
+	let measured = from_read_with_metadata(&mut Cursor::new(&data), &options).unwrap();
+	assert_eq!(Some(&b"B".to_vec()), measured.metadata().ape_items.get("A"));
+	assert_eq!(data.len() as u64, measured.metadata().bytes_read);
+	let options = ParseOptions {
+		lenient: false,
+		..options
+	};
+	let error = from_read_with_metadata(&mut Cursor::new(&data), &options).unwrap_err();
+	assert!(matches!(error.kind, ErrorKind::UnexpectedEOF));
+}
+
+#[test]
+fn leading_apev2() {
+	let path = Path::new("test/LeadingAPEv2.mp3");
+	let duration = from_path(path).unwrap();
+	assert_eq!(from_path("test/SingleFrame.mp3").unwrap(), duration);
+	let analysis = analyze_from_path(path).unwrap();
+	assert_eq!(0, analysis.resynced_bytes);
+	assert_eq!(1, analysis.frames);
+
+	// Tag without header nor items
+	let mut data = b"APETAGEX\xD0\x07\x00\x00\x20\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x80".to_vec();
+	data.extend_from_slice(&[0; 8]);
+	data.extend(synthetic_frames(2));
+	assert_eq!(synthetic_duration(2), from_read(&mut Cursor::new(&data)).unwrap());
+
+	// Tag with a header but no footer
+	let mut data = b"APETAGEX\xD0\x07\x00\x00\x0A\x00\x00\x00\x01\x00\x00\x00\x00\x00\x00\xE0".to_vec();
+	data.extend_from_slice(&[0; 8]);
+	data.extend_from_slice(b"\x01\x00\x00\x00\x00\x00\x00\x00A\x00");
+	data.extend(synthetic_frames(2));
+	assert_eq!(synthetic_duration(2), from_read(&mut Cursor::new(&data)).unwrap());
 }
 
 #[test]
@@ -98,14 +199,34 @@ fn truncated() {
 
 	if let ErrorKind::UnexpectedEOF = error.kind {
 		let duration = error.at_duration;
-		assert_eq!(206, duration.as_secs());
-		let nanos = duration.subsec_nanos();
-		assert!(7 * 100_000_000 < nanos && nanos < 8 * 100_000_000);
+		assert_duration_approx(duration, 206.75, 50);
 	} else {
 		panic!("error.kind must be ErrorKind::UnexpectedEOF")
 	}
 }
 
+#[test]
+fn truncated_xing() {
+	// Cut in the middle of the table of contents, after the frame count
+	let path = Path::new("test/TruncatedXing.mp3");
+	assert_eq!(from_path("test/VBR0.mp3").unwrap(), from_path(path).unwrap());
+	let probe = probe_from_read(&mut File::open(path).unwrap()).unwrap();
+	let xing = probe.xing.unwrap();
+	assert_eq!(Some(15249), xing.frames);
+	assert!(!xing.has_toc);
+
+	// Cut before the frame count
+	let data = &std::fs::read(path).unwrap()[..40];
+	let error = from_read(&mut Cursor::new(data)).unwrap_err();
+	assert!(matches!(error.kind, ErrorKind::UnexpectedEOF));
+	let options = ParseOptions {
+		lenient: true,
+		..Default::default()
+	};
+	assert_eq!(Duration::default(), from_read_with_options(&mut Cursor::new(data), &options).unwrap());
+}
+
+// Default (strict) options report the malformed frame
 #[test]
 fn mpeg_frame_too_short() {
 	let path = Path::new("test/MPEGFrameTooShort.mp3");
@@ -113,10 +234,2404 @@ fn mpeg_frame_too_short() {
 
 	if let ErrorKind::MPEGFrameTooShort = error.kind {
 		let duration = error.at_duration;
-		assert_eq!(395, duration.as_secs());
-		let nanos = duration.subsec_nanos();
-		assert!(4 * 100_000_000 < nanos && nanos < 6 * 100_000_000);
+		assert_duration_approx(duration, 395.5, 100);
 	} else {
 		panic!("error.kind must be ErrorKind::MPEGFrameTooShort")
 	}
 }
+
+#[test]
+fn lenient_mpeg_frame_too_short() {
+	let options = ParseOptions {
+		lenient: true,
+		..Default::default()
+	};
+	let mut reader = File::open(Path::new("test/MPEGFrameTooShort.mp3")).unwrap();
+	let duration = from_read_with_options(&mut reader, &options).unwrap();
+	assert_duration_approx(duration, 395.5, 100);
+
+	// Parsing resumes after the short frame
+	let analysis = analyze_from_path(Path::new("test/MPEGFrameTooShort.mp3")).unwrap();
+	assert_eq!(1, analysis.header_anomalies);
+	assert!(analysis.clean_eof);
+
+	let mut data = synthetic_frames(2);
+	data.extend_from_slice(&[0xFF, 0xFB, 0x00, 0x00]);
+	data.extend(synthetic_frames(3));
+	let duration = from_read_with_options(&mut Cursor::new(&data), &options).unwrap();
+	assert_eq!(synthetic_duration(5), duration);
+}
+
+#[test]
+fn frame_length_boundaries() {
+	use crate::parser::remaining_frame_bytes;
+
+	assert_eq!(0, remaining_frame_bytes(48, 48).unwrap());
+	assert_eq!(1, remaining_frame_bytes(49, 48).unwrap());
+	assert!(matches!(remaining_frame_bytes(47, 48), Err(ErrorKind::MPEGFrameTooShort)));
+
+	// MPEG2 Layer III, 8kbps, 22050Hz (26 bytes, 576 samples): long enough for the header, side information (9 bytes)
+	// and start of a Xing/Info header of mono frames, but not for the side information of stereo frames (17 bytes)
+	let frame = |mode: u8| {
+		let mut frame = vec![0x55; 26];
+		frame[..4].copy_from_slice(&[0xFF, 0xF3, 0x10, mode]);
+		frame
+	};
+	let mono = frame(0xC0).repeat(3);
+	assert_eq!(duration_from_frames(&[(576, 22050); 3]), from_read(&mut Cursor::new(&mono)).unwrap());
+
+	let data = [synthetic_frames(1), frame(0x00), synthetic_frames(1)].concat();
+	let error = from_read(&mut Cursor::new(&data)).unwrap_err();
+	assert!(matches!(error.kind, ErrorKind::MPEGFrameTooShort));
+	assert_eq!(417 + 4, error.offset);
+	let options = ParseOptions {
+		lenient: true,
+		..Default::default()
+	};
+	assert_eq!(synthetic_duration(2), from_read_with_options(&mut Cursor::new(&data), &options).unwrap());
+}
+
+fn synthetic_duration(frames: usize) -> Duration {
+	duration_from_frames(&vec![(1152, 44100); frames])
+}
+
+// MPEG1 Layer III, 128kbps, 44100Hz, stereo, no padding (417 bytes, 1152 samples)
+fn synthetic_frames(count: usize) -> Vec<u8> {
+	let mut frame = vec![0x55; 417];
+	frame[..4].copy_from_slice(&[0xFF, 0xFB, 0x90, 0x00]);
+	frame.repeat(count)
+}
+
+#[test]
+fn lenient_ignores_id3v1_marker_inside_audio() {
+	let mut data = synthetic_frames(5);
+	data.extend_from_slice(b"TAG");
+	data.extend(synthetic_frames(5));
+
+	assert!(from_read(&mut Cursor::new(&data)).is_err());
+
+	let options = ParseOptions {
+		lenient: true,
+		..Default::default()
+	};
+	let duration = from_read_with_options(&mut Cursor::new(&data), &options).unwrap();
+	assert_eq!(synthetic_duration(10), duration);
+}
+
+#[test]
+fn lenient_accepts_id3v1_at_end_of_stream() {
+	let mut data = synthetic_frames(5);
+	data.extend_from_slice(b"TAG");
+	data.extend_from_slice(&[b' '; 125]);
+
+	let options = ParseOptions {
+		lenient: true,
+		..Default::default()
+	};
+	let duration = from_read_with_options(&mut Cursor::new(&data), &options).unwrap();
+	assert_eq!(synthetic_duration(5), duration);
+}
+
+#[test]
+fn skip_trailing_unknown() {
+	let mut data = synthetic_frames(5);
+	data.extend_from_slice(b"FILE \"album.wav\" WAVE\n");
+	data.extend(synthetic_frames(2));
+
+	assert!(from_read(&mut Cursor::new(&data)).is_err());
+
+	for lenient in &[false, true] {
+		let options = ParseOptions {
+			lenient: *lenient,
+			skip_trailing_unknown: true,
+			..Default::default()
+		};
+		let duration = from_read_with_options(&mut Cursor::new(&data), &options).unwrap();
+		assert_eq!(synthetic_duration(5), duration);
+
+		let mut scanner = DurationScanner::new(&options);
+		for chunk in data.chunks(100) {
+			scanner.feed(chunk).unwrap();
+		}
+		assert_eq!(synthetic_duration(5), scanner.finish().unwrap());
+	}
+
+	// Unrecognized data is only accepted after audio frames
+	let options = ParseOptions {
+		skip_trailing_unknown: true,
+		..Default::default()
+	};
+	let mut data = b"FILE".to_vec();
+	data.extend(synthetic_frames(5));
+	assert!(from_read_with_options(&mut Cursor::new(&data), &options).is_err());
+}
+
+#[test]
+fn min_sync_frames() {
+	let mut data = vec![0x12; 10];
+	data.extend_from_slice(&[0xFF, 0xFB, 0x90, 0x00]);
+	data.extend_from_slice(&[0x34; 600]);
+	data.extend(synthetic_frames(5));
+
+	let lenient = ParseOptions {
+		lenient: true,
+		..Default::default()
+	};
+	let duration = from_read_with_options(&mut Cursor::new(&data), &lenient).unwrap();
+	assert_eq!(synthetic_duration(6), duration);
+
+	let options = ParseOptions {
+		min_sync_frames: 3,
+		..lenient
+	};
+	let duration = from_read_with_options(&mut Cursor::new(&data), &options).unwrap();
+	assert_eq!(synthetic_duration(5), duration);
+
+	let options = ParseOptions {
+		min_sync_frames: 3,
+		..Default::default()
+	};
+	let error = from_read_with_options(&mut Cursor::new(&data[10..]), &options).unwrap_err();
+	assert!(matches!(error.kind, ErrorKind::UnexpectedFrame { .. }));
+
+	// Streams shorter than the chain of frames are accepted
+	for data in &[synthetic_frames(2), synthetic_frames(10)] {
+		let duration = from_read_with_options(&mut Cursor::new(data), &options).unwrap();
+		assert_eq!(from_read(&mut Cursor::new(data)).unwrap(), duration);
+	}
+	let data = std::fs::read("test/source.mp3").unwrap();
+	let duration = from_read_with_options(&mut Cursor::new(&data), &options).unwrap();
+	assert_eq!(from_read(&mut Cursor::new(&data)).unwrap(), duration);
+}
+
+#[test]
+fn version_and_layer_names() {
+	let probe = probe_from_read(&mut File::open("test/source.mp3").unwrap()).unwrap();
+	assert_eq!("MPEG-1", version_name(probe.header.version));
+	assert_eq!("Layer III", layer_name(probe.header.layer));
+	assert_eq!("MPEG-2.5", version_name(Version::Mpeg25));
+	assert_eq!("Layer I", layer_name(Layer::Layer1));
+}
+
+#[test]
+fn non_synchsafe_id3v2_size() {
+	let size: u32 = 456;
+	let mut data = b"ID3\x03\x00\x00".to_vec();
+	data.extend_from_slice(&size.to_be_bytes());
+	data.extend_from_slice(&[b'x'; 456]);
+	data.extend(synthetic_frames(5));
+
+	assert!(from_read(&mut Cursor::new(&data)).is_err());
+
+	let options = ParseOptions {
+		lenient: true,
+		..Default::default()
+	};
+	let duration = from_read_with_options(&mut Cursor::new(&data), &options).unwrap();
+	assert_eq!(synthetic_duration(5), duration);
+
+	let analysis = analyze_from_read(&mut Cursor::new(&data)).unwrap();
+	assert_eq!(0, analysis.resynced_bytes);
+	let warning = Warning {
+		kind: WarningKind::NonSynchsafeTagSize { size },
+		offset: 0,
+	};
+	assert_eq!(vec![warning], analysis.warnings);
+}
+
+#[test]
+fn text_preamble() {
+	let mut data = b"\xEF\xBB\xBF \r\n".to_vec();
+	data.extend(synthetic_frames(5));
+	assert_eq!(synthetic_duration(5), from_read(&mut Cursor::new(&data)).unwrap());
+
+	let measured = from_read_with_metadata(&mut Cursor::new(&data), &ParseOptions::default()).unwrap();
+	let warning = Warning {
+		kind: WarningKind::TextPreamble { size: 6 },
+		offset: 0,
+	};
+	assert_eq!(vec![warning], measured.metadata().warnings);
+
+	let data = [b"\t\tID3\x04\x00\x00\x00\x00\x00\x00".to_vec(), synthetic_frames(5)].concat();
+	assert_eq!(synthetic_duration(5), from_read(&mut Cursor::new(&data)).unwrap());
+
+	// Only at the start of the stream
+	let data = [synthetic_frames(1), b" ".to_vec(), synthetic_frames(1)].concat();
+	assert!(from_read(&mut Cursor::new(&data)).is_err());
+}
+
+#[test]
+fn id3v2_flags() {
+	for version in [3u8, 4] {
+		for flags in (0..16u8).map(|bits| bits << 4) {
+			let mut body = Vec::new();
+			if flags & 0x40 != 0 {
+				// Extended header without optional fields
+				match version {
+					3 => body.extend_from_slice(&[0, 0, 0, 6, 0, 0, 0, 0, 0, 0]),
+					_ => body.extend_from_slice(&[0, 0, 0, 6, 1, 0]),
+				}
+			}
+			body.extend_from_slice(&[0x55; 20]);
+			let header = [version, 0, flags, 0, 0, 0, body.len() as u8];
+			let mut data = b"ID3".to_vec();
+			data.extend_from_slice(&header);
+			data.extend(body);
+			if version == 4 && flags & 0x10 != 0 {
+				data.extend_from_slice(b"3DI");
+				data.extend_from_slice(&header);
+			}
+			data.extend(synthetic_frames(2));
+			let duration = from_read(&mut Cursor::new(&data)).ok();
+			assert_eq!(Some(synthetic_duration(2)), duration, "ID3v2.{} with flags {:#04X}", version, flags);
+		}
+	}
+
+	// Extended header larger than the declared tag size
+	let mut data = b"ID3\x03\x00\x40\x00\x00\x00\x04\x00\x00\x00\x08".to_vec();
+	data.extend_from_slice(&[0x55; 8]);
+	data.extend(synthetic_frames(2));
+	assert_eq!(synthetic_duration(2), from_read(&mut Cursor::new(&data)).unwrap());
+}
+
+#[test]
+fn find_sync_word() {
+	let frames = synthetic_frames(2);
+	for alignment in 0..4 {
+		let mut bytes = vec![0x55; alignment];
+		bytes.extend_from_slice(&frames);
+		assert_eq!(Some(alignment), find_next_sync(&bytes, 0));
+		assert_eq!(Some(alignment), find_next_sync(&bytes, alignment));
+		assert_eq!(Some(alignment + 417), find_next_sync(&bytes, alignment + 1));
+		assert_eq!(None, find_next_sync(&bytes[..alignment + 3], 0));
+	}
+
+	// Sync words followed by an invalid version, layer, bitrate or sampling rate, a forbidden combination of fields or
+	// a free-format header
+	let false_positives = [
+		[0xFF, 0xEB, 0x90, 0x00],
+		[0xFF, 0xF9, 0x90, 0x00],
+		[0xFF, 0xFB, 0xF0, 0x00],
+		[0xFF, 0xFB, 0x9C, 0x00],
+		[0xFF, 0xFD, 0x10, 0x00],
+		[0xFF, 0xFB, 0x00, 0x00],
+		[0xFF, 0xE0, 0x00, 0x00],
+	];
+	let mut bytes = false_positives.concat();
+	assert_eq!(None, find_next_sync(&bytes, 0));
+	bytes.extend_from_slice(&frames[..4]);
+	assert_eq!(Some(bytes.len() - 4), find_next_sync(&bytes, 0));
+	assert_eq!(None, find_next_sync(&bytes, bytes.len()));
+	assert_eq!(None, find_next_sync(&bytes, bytes.len() + 10));
+}
+
+#[test]
+fn id3v2_chapters_with_wrong_size() {
+	fn id3v2_frame(id: &[u8], data: &[u8]) -> Vec<u8> {
+		let mut frame = id.to_vec();
+		frame.extend_from_slice(&(data.len() as u32).to_be_bytes());
+		frame.extend_from_slice(&[0, 0]);
+		frame.extend_from_slice(data);
+		frame
+	}
+
+	// Chapter with a title and an embedded image, which contains something looking like a frame header
+	let mut image = vec![0x55; 600];
+	image[400..404].copy_from_slice(&[0xFF, 0xFB, 0x90, 0x00]);
+	let mut chapter = b"chp0\x00\x00\x00\x00\x00\x00\x27\x10\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF".to_vec();
+	chapter.extend(id3v2_frame(b"TIT2", b"\x00Introduction"));
+	chapter.extend(id3v2_frame(b"APIC", &[b"\x00image/jpeg\x00\x03\x00".as_ref(), &image].concat()));
+	let mut body = id3v2_frame(b"CTOC", b"toc\x00\x03\x01chp0\x00");
+	body.extend(id3v2_frame(b"CHAP", &chapter));
+
+	// The declared size misses the end of the image
+	let declared = body.len() - 300;
+	let mut data = b"ID3\x03\x00\x00".to_vec();
+	data.extend((0..4).rev().map(|i| (declared >> (7 * i)) as u8 & 0x7F));
+	data.extend(body);
+	data.extend(synthetic_frames(3));
+
+	assert!(from_read(&mut Cursor::new(&data)).is_err());
+	let options = ParseOptions {
+		lenient: true,
+		..Default::default()
+	};
+	let analysis = analyze_from_read(&mut Cursor::new(&data)).unwrap();
+	assert_eq!(synthetic_duration(3), analysis.duration);
+	assert_eq!(3, analysis.frames);
+	assert_eq!(synthetic_duration(3), from_read_with_options(&mut Cursor::new(&data), &options).unwrap());
+	let mut scanner = DurationScanner::new(&options);
+	scanner.feed(&data).unwrap();
+	assert_eq!(synthetic_duration(3), scanner.finish().unwrap());
+}
+
+#[test]
+fn crc() {
+	assert_eq!(0xAEE7, crate::crc::crc16(b"123456789"));
+
+	// MPEG1 Layer III frames with CRC
+	let mut frame = vec![0x55; 417];
+	frame[..4].copy_from_slice(&[0xFF, 0xFA, 0x90, 0x00]);
+	let protected = [&frame[2..4], &frame[6..38]].concat();
+	frame[4..6].copy_from_slice(&crate::crc::crc16(&protected).to_be_bytes());
+	let mut data = frame.repeat(3);
+
+	let options = ParseOptions {
+		validate_crc: true,
+		..Default::default()
+	};
+	assert_eq!(synthetic_duration(3), from_read_with_options(&mut Cursor::new(&data), &options).unwrap());
+
+	data[417 + 20] ^= 0x01;
+	assert_eq!(synthetic_duration(3), from_read(&mut Cursor::new(&data)).unwrap());
+	let error = from_read_with_options(&mut Cursor::new(&data), &options).unwrap_err();
+	assert!(matches!(error.kind, ErrorKind::CrcMismatch));
+	assert_eq!(synthetic_duration(1), error.at_duration);
+
+	let options = ParseOptions {
+		lenient: true,
+		..options
+	};
+	let measured = from_read_with_metadata(&mut Cursor::new(&data), &options).unwrap();
+	assert_eq!(synthetic_duration(3), *measured);
+	let expected = Warning {
+		kind: WarningKind::CrcMismatch,
+		offset: 417,
+	};
+	assert_eq!(vec![expected], measured.metadata().warnings);
+}
+
+#[test]
+fn forbidden_field_combination() {
+	// MPEG-1 Layer II, 224 kbps, mono
+	let header = FrameHeader::parse(0xFFFD_B0C0).unwrap();
+	assert!(!header.is_allowed_combination());
+	assert!(FrameHeader::parse(0xFFFD_B000).unwrap().is_allowed_combination());
+
+	let mut data = vec![0xFF, 0xFD, 0xB0, 0xC0];
+	data.extend_from_slice(&[0x42; 8]);
+	data.extend(synthetic_frames(3));
+	let error = from_read(&mut Cursor::new(&data)).unwrap_err();
+	assert!(matches!(error.kind, ErrorKind::UnexpectedFrame { header: 0xFFFD_B0C0 }));
+
+	let analysis = analyze_from_read(&mut Cursor::new(&data)).unwrap();
+	assert_eq!(synthetic_duration(3), analysis.duration);
+	assert_eq!(1, analysis.header_anomalies);
+}
+
+#[test]
+fn bytes_read() {
+	let data = [b"ID3\x04\x00\x00\x00\x00\x00\x05".to_vec(), vec![0; 5], synthetic_frames(4)].concat();
+	let mut reader = Cursor::new(&data);
+	assert_eq!((synthetic_duration(4), data.len() as u64), measure_from_read(&mut reader).unwrap());
+	assert_eq!(data.len() as u64, reader.position());
+
+	// Reading stops after Xing/Info headers
+	let data = std::fs::read("test/VBR0.mp3").unwrap();
+	let mut reader = Cursor::new(&data);
+	let (duration, bytes_read) = measure_from_read(&mut reader).unwrap();
+	assert_eq!(from_bytes(&data).unwrap(), duration);
+	assert!(bytes_read < 1000);
+	assert_eq!(bytes_read, reader.position());
+
+	let error = measure_from_read(&mut Cursor::new(&synthetic_frames(2)[..600])).unwrap_err();
+	assert!(matches!(error.kind, ErrorKind::UnexpectedEOF));
+	assert_eq!(600, error.offset);
+}
+
+#[test]
+fn two_slices() {
+	let data = std::fs::read("test/source.mp3").unwrap();
+	let expected = from_bytes(&data).unwrap();
+	for split in &[0, 1, 3, 100, 5000, data.len() / 2, data.len() - 1, data.len()] {
+		let (front, back) = data.split_at(*split);
+		assert_eq!(expected, from_two_slices(front, back).unwrap());
+	}
+
+	// Ring buffer wrapping around, ending in the middle of a frame
+	let mut buffer = std::collections::VecDeque::with_capacity(2048);
+	buffer.extend(vec![0; 1500]);
+	buffer.drain(..1500 - 1);
+	buffer.pop_front();
+	buffer.extend(synthetic_frames(4)[..417 * 3 + 200].iter());
+	let (front, back) = buffer.as_slices();
+	assert!(!back.is_empty());
+	let error = from_two_slices(front, back).unwrap_err();
+	assert!(matches!(error.kind, ErrorKind::UnexpectedEOF));
+	assert_eq!(synthetic_duration(3), error.at_duration);
+}
+
+#[test]
+fn saturating() {
+	let data = std::fs::read("test/source.mp3").unwrap();
+	let (duration, error) = from_bytes_saturating(&data);
+	assert_eq!(from_bytes(&data).unwrap(), duration);
+	assert!(error.is_none());
+
+	// Xing header declaring 2^32 - 1 frames
+	let mut frame = vec![0; 417];
+	frame[..4].copy_from_slice(&[0xFF, 0xFB, 0x90, 0x00]);
+	frame[36..48].copy_from_slice(b"Xing\x00\x00\x00\x01\xFF\xFF\xFF\xFF");
+	let declared = from_bytes(&frame).unwrap();
+	assert_eq!(112_195_064, declared.as_secs());
+	let (duration, error) = from_bytes_saturating(&frame);
+	assert_eq!(estimate_from_size(417, 4_000), duration);
+	assert!(error.is_none());
+
+	let data = vec![0; 2 * 1024 * 1024];
+	let (duration, error) = from_bytes_saturating(&data);
+	assert_eq!(Duration::default(), duration);
+	assert!(error.is_none());
+}
+
+#[test]
+fn custom_validator() {
+	// MPEG-1 Layer II, 224 kbps, mono
+	let mut frame = vec![0; 731];
+	frame[..4].copy_from_slice(&[0xFF, 0xFD, 0xB0, 0xC0]);
+	let data = [frame.repeat(3), synthetic_frames(2)].concat();
+	assert!(from_read(&mut Cursor::new(&data)).is_err());
+
+	let options = ParseOptions::default();
+	let duration = from_read_with_validator(&mut Cursor::new(&data), &options, |_| true).unwrap();
+	assert_eq!(synthetic_duration(5), duration);
+	let error = from_read_with_validator(&mut Cursor::new(&data), &options, |header| header.bitrate != 224_000);
+	assert!(matches!(error.unwrap_err().kind, ErrorKind::UnexpectedFrame { header: 0xFFFD_B0C0 }));
+
+	let options = ParseOptions {
+		lenient: true,
+		..Default::default()
+	};
+	let is_layer2 = |header: &FrameHeader| header.layer == Layer::Layer2;
+	let duration = from_read_with_validator(&mut Cursor::new(&data), &options, is_layer2).unwrap();
+	assert_eq!(synthetic_duration(3), duration);
+}
+
+#[test]
+fn max_leading_zeros() {
+	let zeros = vec![0; 2 * 1024 * 1024];
+	assert_eq!(Duration::default(), from_read(&mut Cursor::new(&zeros)).unwrap());
+	let options = ParseOptions {
+		max_leading_zeros: 4096,
+		..Default::default()
+	};
+	let error = from_read_with_options(&mut Cursor::new(&zeros), &options).unwrap_err();
+	assert!(matches!(error.kind, ErrorKind::UnexpectedFrame { header: 0 }));
+	assert_eq!(4096 + 1, error.offset);
+
+	let mut data = synthetic_frames(2);
+	data.extend_from_slice(&[0; 1000]);
+	data.extend(synthetic_frames(2));
+	for (max_leading_zeros, succeeds) in &[(1000, true), (999, false)] {
+		let options = ParseOptions {
+			max_leading_zeros: *max_leading_zeros,
+			..Default::default()
+		};
+		let expected = summarize(from_read_with_options(&mut Cursor::new(&data), &options));
+		assert_eq!(*succeeds, expected == Ok(synthetic_duration(4)));
+		let mut small_buffer = std::io::BufReader::with_capacity(300, Cursor::new(&data));
+		assert_eq!(expected, summarize(Parser::new(BufContext::new(&mut small_buffer), &options).measure()));
+		let mut scanner = DurationScanner::new(&options);
+		let scanned = data.chunks(300).try_for_each(|chunk| scanner.feed(chunk).map(|_| ()));
+		assert_eq!(expected, summarize(scanned.and_then(|_| scanner.finish())));
+	}
+}
+
+#[test]
+fn assume_format() {
+	let mut data = vec![0x55; 100];
+	data.extend_from_slice(&[0xFF, 0xF3, 0x90, 0x00]);
+	data.extend_from_slice(&[0x55; 100]);
+	data.extend(synthetic_frames(3));
+	assert!(from_read(&mut Cursor::new(&data)).is_err());
+
+	let options = ParseOptions {
+		assume_format: Some(FrameHeader::parse(0xFFFB_9000).unwrap()),
+		..Default::default()
+	};
+	let duration = from_read_with_options(&mut Cursor::new(&data), &options).unwrap();
+	assert_eq!(synthetic_duration(3), duration);
+
+	// Only the data preceding the first frame is skipped in strict mode
+	data.extend_from_slice(&[0x55; 4]);
+	data.extend(synthetic_frames(1));
+	assert!(from_read_with_options(&mut Cursor::new(&data), &options).is_err());
+}
+
+#[test]
+fn zero_frame_length() {
+	// Free-format frame header
+	let mut data = vec![0xFF, 0xFB, 0x00, 0x00];
+	data.extend(synthetic_frames(3));
+	let error = from_read(&mut Cursor::new(&data)).unwrap_err();
+	assert!(matches!(error.kind, ErrorKind::FreeFormatUnsupported));
+	assert_eq!(4, error.offset);
+
+	let options = ParseOptions {
+		lenient: true,
+		..Default::default()
+	};
+	let duration = from_read_with_options(&mut Cursor::new(&data), &options).unwrap();
+	assert_eq!(synthetic_duration(3), duration);
+	let mut scanner = DurationScanner::new(&options);
+	data.chunks(3).try_for_each(|chunk| scanner.feed(chunk).map(|_| ())).unwrap();
+	assert_eq!(synthetic_duration(3), scanner.finish().unwrap());
+
+	// Only free-format frames
+	let mut frame = vec![0x55; 417];
+	frame[..4].copy_from_slice(&[0xFF, 0xFB, 0x00, 0x00]);
+	let data = frame.repeat(3);
+	let error = from_read_with_options(&mut Cursor::new(&data), &options).unwrap_err();
+	assert!(matches!(error.kind, ErrorKind::FreeFormatUnsupported));
+}
+
+#[test]
+fn mpeg2_layer1() {
+	// 32kbps, 22050Hz, mono, every third frame padded
+	let path = Path::new("test/MPEG2Layer1.mp3");
+	let header = parse_frame_at(&mut File::open(path).unwrap(), 0).unwrap();
+	assert_eq!(Version::Mpeg2, header.version);
+	assert_eq!(Layer::Layer1, header.layer);
+	assert_eq!(32_000, header.bitrate);
+	assert_eq!(68, header.frame_length());
+	let padded = FrameHeader {
+		padding: true,
+		..header
+	};
+	assert_eq!(72, padded.frame_length());
+	assert_eq!(duration_from_frames(&vec![(384, 22050); 100]), from_path(path).unwrap());
+}
+
+#[test]
+fn resume_scanner() {
+	let data = std::fs::read("test/source.mp3").unwrap();
+	let options = ParseOptions::default();
+	let mut scanner = DurationScanner::new(&options);
+	assert_eq!(None, scanner.feed(&data[..100_000]).unwrap());
+	let paused = scanner.clone();
+
+	assert_eq!(None, scanner.feed(&data[100_000..]).unwrap());
+	let expected = from_read(&mut Cursor::new(&data)).unwrap();
+	assert_eq!(expected, scanner.finish().unwrap());
+
+	let mut resumed = paused;
+	#[cfg(feature = "serde")]
+	{
+		let stored = serde_json::to_string(&resumed).unwrap();
+		resumed = serde_json::from_str(&stored).unwrap();
+	}
+	assert_eq!(None, resumed.feed(&data[100_000..]).unwrap());
+	assert_eq!(expected, resumed.finish().unwrap());
+}
+
+#[test]
+fn scanner_estimate() {
+	let data = synthetic_frames(10);
+	let mut scanner = DurationScanner::new(&ParseOptions::default());
+	assert_eq!((Duration::default(), Duration::default()), scanner.estimate(data.len() as u64));
+	scanner.feed(&data[..5 * 417]).unwrap();
+	let (measured, estimated) = scanner.estimate(data.len() as u64);
+	assert_eq!(synthetic_duration(5), measured);
+	assert_duration_approx(estimated, synthetic_duration(10).as_secs_f64(), 1);
+
+	let data = std::fs::read("test/VBR0.mp3").unwrap();
+	let mut scanner = DurationScanner::new(&ParseOptions::default());
+	let duration = scanner.feed(&data[..4096]).unwrap().unwrap();
+	assert_eq!((duration, duration), scanner.estimate(data.len() as u64));
+}
+
+#[test]
+fn read_region() {
+	let data = synthetic_frames(5);
+	let mut reader = Cursor::new(&data);
+	reader.set_position(417);
+	assert_eq!(synthetic_duration(3), from_read_region(&mut reader, 3 * 417).unwrap());
+	assert_eq!(4 * 417, reader.position());
+
+	let mut reader = Cursor::new(&data);
+	let error = from_read_region(&mut reader, 3 * 417 - 10).unwrap_err();
+	assert!(matches!(error.kind, ErrorKind::UnexpectedEOF));
+	assert_eq!(synthetic_duration(2), error.at_duration);
+}
+
+#[test]
+fn probe_xing() {
+	let path = Path::new("test/VBR0.mp3");
+	let probe = probe_from_read(&mut File::open(path).unwrap()).unwrap();
+	assert_eq!(Version::Mpeg1, probe.header.version);
+	assert_eq!(Layer::Layer3, probe.header.layer);
+	assert_eq!(SampleRate::Hz44100, probe.header.sampling_rate);
+	assert_eq!(44100, probe.header.sampling_rate.hz());
+	assert!(probe.has_xing);
+	assert_eq!(from_path(path).unwrap(), probe.xing_duration.unwrap());
+
+	let xing = probe.xing.unwrap();
+	assert_eq!(Some(15249), xing.frames);
+	assert_eq!(Some(std::fs::metadata(path).unwrap().len() as u32), xing.bytes);
+	assert!(xing.has_toc);
+	assert_eq!(Some(100), xing.quality);
+}
+
+#[test]
+fn count_info_frame() {
+	let data = std::fs::read("test/VBR0.mp3").unwrap();
+	let options = ParseOptions {
+		count_info_frame: true,
+		..Default::default()
+	};
+	assert_eq!(duration_from_frames(&[(1152, 44100); 15249]), from_read(&mut Cursor::new(&data)).unwrap());
+	let duration = from_read_with_options(&mut Cursor::new(&data), &options).unwrap();
+	assert_eq!(duration_from_frames(&[(1152, 44100); 15250]), duration);
+	assert_eq!(duration, *from_read_with_metadata(&mut Cursor::new(&data), &options).unwrap());
+}
+
+#[test]
+fn parse_frame_at_offset() {
+	let mut data = Cursor::new(synthetic_frames(3));
+	let header = parse_frame_at(&mut data, 417).unwrap();
+	assert_eq!(417, header.frame_length());
+	assert_eq!(128_000, header.bitrate);
+
+	let error = parse_frame_at(&mut data, 418).unwrap_err();
+	assert!(matches!(error.kind, ErrorKind::UnexpectedFrame { .. }));
+	assert_eq!(418, error.offset);
+	let error = parse_frame_at(&mut data, 2 * 417 + 415).unwrap_err();
+	assert!(matches!(error.kind, ErrorKind::UnexpectedEOF));
+}
+
+#[test]
+fn measured_duration() {
+	let options = ParseOptions::default();
+	let data = std::fs::read("test/VBR0.mp3").unwrap();
+	let measured = from_read_with_metadata(&mut Cursor::new(&data), &options).unwrap();
+	assert_eq!(from_read(&mut Cursor::new(&data)).unwrap(), *measured);
+	assert!(measured.metadata().from_xing_header);
+	assert!(measured.metadata().bytes_read < data.len() as u64);
+
+	let data = synthetic_frames(3);
+	let measured = from_read_with_metadata(&mut Cursor::new(&data), &options).unwrap();
+	assert_eq!(synthetic_duration(3).as_millis(), measured.as_millis());
+	assert!(!measured.metadata().from_xing_header);
+	assert_eq!(3 * 417, measured.metadata().bytes_read);
+	assert_eq!(synthetic_duration(3), Duration::from(measured));
+}
+
+#[test]
+fn replay_gain() {
+	let mut data = synthetic_frames(3);
+	data[36..48].copy_from_slice(b"Xing\x00\x00\x00\x01\x00\x00\x00\x02");
+	let mut lame = [0; 36];
+	lame[..9].copy_from_slice(b"LAME3.99r");
+	lame[11..15].copy_from_slice(&[0x00, 0x40, 0x00, 0x00]);
+	lame[15..17].copy_from_slice(&[0x2E, 0x41]);
+	lame[17..19].copy_from_slice(&[0x4C, 0x14]);
+	data[48..84].copy_from_slice(&lame);
+
+	let expected = ReplayGain {
+		track_gain: Some(-6.5),
+		album_gain: Some(2.0),
+		peak: Some(0.5),
+	};
+	let probe = probe_from_read(&mut Cursor::new(&data)).unwrap();
+	assert_eq!(Some(expected), probe.replay_gain);
+	let measured = from_read_with_metadata(&mut Cursor::new(&data), &ParseOptions::default()).unwrap();
+	assert_eq!(Some(expected), measured.metadata().replay_gain);
+	assert_eq!(synthetic_duration(2), *measured);
+
+	// Generic Xing header
+	data[48..52].copy_from_slice(b"XXXX");
+	assert_eq!(None, probe_from_read(&mut Cursor::new(&data)).unwrap().replay_gain);
+	// LAME extension without ReplayGain
+	let probe = probe_from_read(&mut File::open("test/VBR0.mp3").unwrap()).unwrap();
+	assert_eq!(None, probe.replay_gain);
+}
+
+#[test]
+fn xing_sampling_rate_mismatch() {
+	// Xing header at 44100Hz declaring 100 frames, followed by 48000Hz frames (384 bytes each)
+	let mut data = synthetic_frames(1);
+	data[36..48].copy_from_slice(b"Xing\x00\x00\x00\x01\x00\x00\x00\x64");
+	let mut frame = vec![0x55; 384];
+	frame[..4].copy_from_slice(&[0xFF, 0xFB, 0x94, 0x00]);
+	data.extend(frame.repeat(5));
+
+	let expected = duration_from_frames(&[(1152, 48000); 5]);
+	assert_eq!(expected, from_read(&mut Cursor::new(&data)).unwrap());
+	let mut scanner = DurationScanner::new(&ParseOptions::default());
+	assert_eq!(None, scanner.feed(&data[..500]).unwrap());
+	assert_eq!(None, scanner.feed(&data[500..]).unwrap());
+	assert_eq!(expected, scanner.finish().unwrap());
+
+	let measured = from_read_with_metadata(&mut Cursor::new(&data), &ParseOptions::default()).unwrap();
+	assert_eq!(expected, *measured);
+	assert!(!measured.metadata().from_xing_header);
+	assert_eq!(5, measured.metadata().audio_frames);
+	let expected_warning = Warning {
+		kind: WarningKind::XingSamplingRateMismatch {
+			declared: synthetic_duration(100),
+			xing_rate: 44100,
+			audio_rate: 48000,
+		},
+		offset: 0,
+	};
+	assert_eq!(vec![expected_warning], measured.metadata().warnings);
+
+	// Headers followed by frames at the same sampling rate are trusted
+	let mut data = synthetic_frames(3);
+	data[36..48].copy_from_slice(b"Xing\x00\x00\x00\x01\x00\x00\x00\x64");
+	assert_eq!(synthetic_duration(100), from_read(&mut Cursor::new(&data)).unwrap());
+}
+
+#[test]
+fn metadata_summary() {
+	let options = ParseOptions::default();
+	let summary = |data: &[u8]| from_read_with_metadata(&mut Cursor::new(data), &options).unwrap().to_string();
+	let vbr0 = std::fs::read("test/VBR0.mp3").unwrap();
+	assert_eq!("6:38, MPEG-1 Layer III, 44100 Hz, joint stereo, VBR ~168 kbps (Xing)", summary(&vbr0));
+	let cbr320 = std::fs::read("test/CBR320.mp3").unwrap();
+	assert_eq!("6:38, MPEG-1 Layer III, 44100 Hz, joint stereo, CBR 320 kbps (Xing)", summary(&cbr320));
+	let source = std::fs::read("test/source.mp3").unwrap();
+	assert_eq!("6:38, MPEG-1 Layer III, 44100 Hz, stereo, CBR 160 kbps", summary(&source));
+	let mpeg2_layer1 = std::fs::read("test/MPEG2Layer1.mp3").unwrap();
+	assert_eq!("0:01, MPEG-2 Layer I, 22050 Hz, mono, CBR 32 kbps", summary(&mpeg2_layer1));
+
+	// Scanned frames with different bitrates (128 and 160kbps)
+	let mut frame = vec![0x55; 522];
+	frame[..4].copy_from_slice(&[0xFF, 0xFB, 0xA0, 0x00]);
+	let data = [synthetic_frames(1), frame].concat();
+	assert_eq!("0:00, MPEG-1 Layer III, 44100 Hz, stereo, VBR ~144 kbps", summary(&data));
+	// Hours are shown for long streams
+	let mut data = synthetic_frames(1);
+	data[36..48].copy_from_slice(b"Xing\x00\x00\x00\x01\x00\x04\x00\x00");
+	assert_eq!("1:54:07, MPEG-1 Layer III, 44100 Hz, stereo, VBR (Xing)", summary(&data));
+	assert_eq!("0:00", summary(b""));
+}
+
+#[test]
+fn content_duration() {
+	let data = std::fs::read("test/VBR0.mp3").unwrap();
+	let measured = from_read_with_metadata(&mut Cursor::new(&data), &ParseOptions::default()).unwrap();
+	assert_eq!(Some(576), measured.metadata().encoder_delay);
+	assert_eq!(Some(2257), measured.metadata().encoder_padding);
+	assert_eq!(duration_from_frames(&[(1152, 44100); 15249]), measured.duration());
+	let content_samples = 15249 * 1152 - 576 - 2257;
+	assert_eq!(duration_from_frames(&[(content_samples, 44100)]), measured.content_duration());
+
+	let data = synthetic_frames(3);
+	let measured = from_read_with_metadata(&mut Cursor::new(&data), &ParseOptions::default()).unwrap();
+	assert_eq!(None, measured.metadata().encoder_delay);
+	assert_eq!(synthetic_duration(3), measured.content_duration());
+}
+
+#[test]
+fn verify_xing() {
+	let data = std::fs::read("test/VBR0.mp3").unwrap();
+	let measured = from_read_with_metadata(&mut Cursor::new(&data), &ParseOptions::default()).unwrap();
+	assert_eq!(Some(15249), measured.metadata().xing_frames);
+	assert_eq!(None, measured.metadata().scanned_frames);
+	assert_eq!(None, measured.metadata().scanned_duration);
+
+	let options = ParseOptions {
+		verify_xing: true,
+		..Default::default()
+	};
+	let verified = from_read_with_metadata(&mut Cursor::new(&data), &options).unwrap();
+	assert_eq!(measured.duration(), verified.duration());
+	assert_eq!(Some(15249), verified.metadata().xing_frames);
+	assert_eq!(Some(15249), verified.metadata().scanned_frames);
+	assert_eq!(Some(from_read_multistream(&mut Cursor::new(&data)).unwrap()), verified.metadata().scanned_duration);
+	assert_eq!(data.len() as u64, verified.metadata().bytes_read);
+
+	let data = synthetic_frames(3);
+	let measured = from_read_with_metadata(&mut Cursor::new(&data), &options).unwrap();
+	assert_eq!(None, measured.metadata().xing_frames);
+	assert_eq!(Some(3), measured.metadata().scanned_frames);
+	assert_eq!(Some(synthetic_duration(3)), measured.metadata().scanned_duration);
+}
+
+#[test]
+fn scan_past_xing() {
+	let options = ParseOptions {
+		scan_past_xing: true,
+		..Default::default()
+	};
+	let data = std::fs::read("test/VBR0.mp3").unwrap();
+	let scanned = from_read_multistream(&mut Cursor::new(&data)).unwrap();
+	assert_eq!(scanned, from_read_with_options(&mut Cursor::new(&data), &options).unwrap());
+	let measured = from_read_with_metadata(&mut Cursor::new(&data), &options).unwrap();
+	assert_eq!(scanned, *measured);
+	assert!(!measured.metadata().from_xing_header);
+	assert_eq!(Some(from_bytes(&data).unwrap()), measured.metadata().xing_duration);
+	assert_eq!(Some(15249), measured.metadata().xing_frames);
+	assert_eq!(Some(15249), measured.metadata().scanned_frames);
+
+	// Xing/Info frame declaring 100 frames, followed by 10 audio frames
+	let mut data = synthetic_frames(11);
+	data[36..48].copy_from_slice(b"Xing\x00\x00\x00\x01\x00\x00\x00\x64");
+	let mut scanner = DurationScanner::new(&options);
+	assert_eq!(None, scanner.feed(&data).unwrap());
+	assert_eq!(synthetic_duration(10), scanner.finish().unwrap());
+	let measured = from_read_with_metadata(&mut Cursor::new(&data), &options).unwrap();
+	assert_eq!(synthetic_duration(10), *measured);
+	assert_eq!(10, measured.metadata().audio_frames);
+	assert_eq!(Some(synthetic_duration(100)), measured.metadata().xing_duration);
+}
+
+#[test]
+fn only_xing_frame() {
+	let options = ParseOptions::default();
+	let vbr0 = from_read_with_metadata(&mut File::open("test/VBR0.mp3").unwrap(), &options).unwrap();
+	assert!(!vbr0.metadata().only_xing_frame);
+	let measured = from_read_with_metadata(&mut File::open("test/XingStub.mp3").unwrap(), &options).unwrap();
+	assert!(measured.metadata().only_xing_frame);
+	assert_eq!(*vbr0, *measured);
+	assert_eq!(Some(15249), measured.metadata().xing_frames);
+	let measured = from_read_with_metadata(&mut File::open("test/TruncatedXing.mp3").unwrap(), &options).unwrap();
+	assert!(!measured.metadata().only_xing_frame);
+}
+
+#[test]
+fn bitrate_range() {
+	let data = std::fs::read("test/VBR0.mp3").unwrap();
+	let measured = from_read_with_metadata(&mut Cursor::new(&data), &ParseOptions::default()).unwrap();
+	assert_eq!(None, measured.metadata().min_bitrate);
+	assert_eq!(None, measured.metadata().max_bitrate);
+	let options = ParseOptions {
+		verify_xing: true,
+		..Default::default()
+	};
+	let verified = from_read_with_metadata(&mut Cursor::new(&data), &options).unwrap();
+	assert_eq!(Some(32_000), verified.metadata().min_bitrate);
+	assert_eq!(Some(320_000), verified.metadata().max_bitrate);
+
+	let source = from_read_with_metadata(&mut File::open("test/source.mp3").unwrap(), &Default::default()).unwrap();
+	assert_eq!(Some(160_000), source.metadata().min_bitrate);
+	assert_eq!(Some(160_000), source.metadata().max_bitrate);
+	let empty = from_read_with_metadata(&mut Cursor::new(b""), &Default::default()).unwrap();
+	assert_eq!(None, empty.metadata().min_bitrate);
+}
+
+#[test]
+fn reject_tags() {
+	let options = ParseOptions {
+		reject_tags: true,
+		..Default::default()
+	};
+	let expected = [
+		("test/ID3v1.mp3", TagKind::ID3v1),
+		("test/ID3v2.mp3", TagKind::ID3v2),
+		("test/APEv2.mp3", TagKind::APEv2),
+	];
+	for (path, kind) in &expected {
+		let error = from_read_with_options(&mut File::open(path).unwrap(), &options).unwrap_err();
+		assert!(matches!(error.kind, ErrorKind::UnexpectedTag { kind: k } if k == *kind), "{}", path);
+	}
+
+	let mut data = synthetic_frames(2);
+	assert_eq!(synthetic_duration(2), from_read_with_options(&mut Cursor::new(&data), &options).unwrap());
+	data.extend_from_slice(b"LYRICSBEGIN");
+	let error = from_read_with_options(&mut Cursor::new(&data), &options).unwrap_err();
+	assert!(matches!(error.kind, ErrorKind::UnexpectedTag { kind: TagKind::Lyrics3 }));
+	assert_eq!(synthetic_duration(2), error.at_duration);
+
+	struct JunkHandler;
+	impl TagHandler for JunkHandler {
+		fn try_handle(&self, magic: &[u8], _: &mut dyn ByteSource) -> Option<Result<usize, ErrorKind>> {
+			Some(Ok(magic.len())).filter(|_| magic == b"JUNK")
+		}
+	}
+	let data = b"JUNK";
+	let error = from_read_with_tag_handlers(&mut Cursor::new(data), &options, &[&JunkHandler]).unwrap_err();
+	assert!(matches!(error.kind, ErrorKind::UnexpectedTag { kind: TagKind::Custom }));
+}
+
+#[test]
+fn tags_only() {
+	let mut data = b"ID3\x04\x00\x00\x00\x00\x00\x10".to_vec();
+	data.extend_from_slice(&[0; 16]);
+	let measured = from_read_with_metadata(&mut Cursor::new(&data), &ParseOptions::default()).unwrap();
+	assert_eq!(0, measured.metadata().audio_frames);
+	assert_eq!(Duration::from_secs(0), *measured);
+
+	let options = ParseOptions {
+		require_audio: true,
+		..Default::default()
+	};
+	let error = from_read_with_options(&mut Cursor::new(&data), &options).unwrap_err();
+	assert!(matches!(error.kind, ErrorKind::NoAudioFrames));
+	let error = from_read_with_metadata(&mut Cursor::new(&data), &options).unwrap_err();
+	assert!(matches!(error.kind, ErrorKind::NoAudioFrames));
+
+	let measured = from_read_with_metadata(&mut Cursor::new(synthetic_frames(3)), &options).unwrap();
+	assert_eq!(3, measured.metadata().audio_frames);
+}
+
+#[test]
+fn probe_without_xing() {
+	let path = Path::new("test/ID3v1.mp3");
+	let probe = probe_from_read(&mut File::open(path).unwrap()).unwrap();
+	assert_eq!(Layer::Layer3, probe.header.layer);
+	assert_eq!(Mode::Stereo, probe.header.mode);
+	assert!(!probe.has_xing);
+	assert!(probe.xing_duration.is_none());
+	assert!(probe.xing.is_none());
+}
+
+#[test]
+fn long_zero_padding() {
+	let mut data = vec![0; 100_000];
+	data.extend(synthetic_frames(3));
+	data.extend_from_slice(&[0; 5000]);
+	data.extend_from_slice(b"JUNK");
+
+	let error = from_read(&mut Cursor::new(&data)).unwrap_err();
+	assert_eq!(data.len() as u64, error.offset);
+	assert_eq!(synthetic_duration(3), error.at_duration);
+}
+
+#[test]
+fn analyze_clean_file() {
+	let analysis = analyze_from_path(Path::new("test/ID3v1.mp3")).unwrap();
+	assert_eq!(from_path(Path::new("test/ID3v1.mp3")).unwrap(), analysis.duration);
+	assert_eq!(0, analysis.resynced_bytes);
+	assert_eq!(0, analysis.header_anomalies);
+	assert_eq!(analysis.frames, analysis.longest_valid_run_frames);
+	assert!(analysis.clean_eof);
+	assert_eq!(1.0, analysis.integrity_score());
+}
+
+#[test]
+fn analyze_damaged_file() {
+	let mut data = synthetic_frames(5);
+	data.extend_from_slice(b"JUNK");
+	data.extend(synthetic_frames(7));
+	data.truncate(data.len() - 10);
+
+	let analysis = analyze_from_read(&mut Cursor::new(&data)).unwrap();
+	assert_eq!(11, analysis.frames);
+	assert_eq!(6, analysis.longest_valid_run_frames);
+	assert_eq!(4, analysis.resynced_bytes);
+	assert!(!analysis.clean_eof);
+	assert!(analysis.integrity_score() < 0.5);
+}
+
+#[test]
+fn analyze_mode_histogram() {
+	let mut data = synthetic_frames(5);
+	data[417 + 3] = 0b0100_0000;
+	data[3 * 417 + 3] = 0b1100_0000;
+
+	let analysis = analyze_from_read(&mut Cursor::new(&data)).unwrap();
+	assert_eq!(3, analysis.frames_with_mode(Mode::Stereo));
+	assert_eq!(1, analysis.frames_with_mode(Mode::JointStereo));
+	assert_eq!(0, analysis.frames_with_mode(Mode::DualChannel));
+	assert_eq!(1, analysis.frames_with_mode(Mode::Mono));
+}
+
+#[test]
+fn lenient_truncated() {
+	let options = ParseOptions {
+		lenient: true,
+		..Default::default()
+	};
+	let mut reader = File::open(Path::new("test/Truncated.mp3")).unwrap();
+	let duration = from_read_with_options(&mut reader, &options).unwrap();
+	assert_duration_approx(duration, 206.75, 50);
+}
+
+struct LyricsHandler;
+
+impl TagHandler for LyricsHandler {
+	fn try_handle(&self, magic: &[u8], source: &mut dyn ByteSource) -> Option<Result<usize, ErrorKind>> {
+		if magic != b"LYRI" {
+			return None;
+		}
+		let mut body = [0; 7];
+		match source.peek(&mut body) {
+			Ok(_) if &body != b"CSBEGIN" => None,
+			Ok(_) => Some(source.skip(16).map(|_| 20)),
+			Err(e) => Some(Err(e)),
+		}
+	}
+}
+
+#[test]
+fn custom_tag_handler() {
+	let mut data = synthetic_frames(2);
+	data.extend_from_slice(b"LYRICSBEGIN123456789");
+	data.extend(synthetic_frames(2));
+
+	let options = ParseOptions::default();
+	assert!(from_read(&mut Cursor::new(&data)).is_err());
+	let duration = from_read_with_tag_handlers(&mut Cursor::new(&data), &options, &[&LyricsHandler]).unwrap();
+	assert_eq!(synthetic_duration(4), duration);
+}
+
+#[test]
+fn single_frame() {
+	let path = Path::new("test/SingleFrame.mp3");
+	let duration = from_path(path).unwrap();
+	assert_duration_approx(duration, 0.0265, 1);
+}
+
+#[test]
+fn multistream() {
+	let mut data = std::fs::read("test/VBR0.mp3").unwrap();
+	data.extend(std::fs::read("test/ID3v2.mp3").unwrap());
+	data.extend(std::fs::read("test/SingleFrame.mp3").unwrap());
+
+	let duration = from_read_multistream(&mut Cursor::new(&data)).unwrap();
+	assert_duration_approx(duration, 796.75, 150);
+}
+
+#[test]
+fn trust_first_xing() {
+	let mut data = std::fs::read("test/VBR0.mp3").unwrap();
+	data.extend(std::fs::read("test/SingleFrame.mp3").unwrap());
+
+	let first = from_path("test/VBR0.mp3").unwrap();
+	assert_eq!(first, from_read(&mut Cursor::new(&data)).unwrap());
+	let options = ParseOptions {
+		trust_first_xing: false,
+		..Default::default()
+	};
+	let all_frames = from_read_multistream(&mut Cursor::new(&data)).unwrap();
+	assert_eq!(all_frames, from_read_with_options(&mut Cursor::new(&data), &options).unwrap());
+	let measured = from_read_with_metadata(&mut Cursor::new(&data), &options).unwrap();
+	assert_eq!(all_frames, *measured);
+	assert!(!measured.metadata().from_xing_header);
+	let mut scanner = DurationScanner::new(&options);
+	assert_eq!(None, scanner.feed(&data).unwrap());
+	assert_eq!(all_frames, scanner.finish().unwrap());
+}
+
+#[test]
+fn assume_cbr_sampling() {
+	let options = ParseOptions {
+		assume_cbr_sampling: Some(10),
+		..Default::default()
+	};
+	for path in &["test/source.mp3", "test/ID3v1.mp3", "test/MPEG2Layer1.mp3", "test/VBR0.mp3"] {
+		let data = std::fs::read(path).unwrap();
+		let expected = from_read(&mut Cursor::new(&data)).unwrap();
+		assert_eq!(expected, from_read_with_options(&mut Cursor::new(&data), &options).unwrap(), "{}", path);
+		let mut scanner = DurationScanner::new(&options);
+		let scanned = data.chunks(1000).try_for_each(|chunk| scanner.feed(chunk).map(|_| ()));
+		assert_eq!(Ok(expected), summarize(scanned.and_then(|_| scanner.finish())));
+	}
+
+	// The last frames are measured from the size of the stream
+	for frames in 20..30 {
+		let data = synthetic_frames(frames);
+		let duration = from_read_with_options(&mut Cursor::new(&data), &options).unwrap();
+		assert_eq!(synthetic_duration(frames), duration);
+	}
+}
+
+#[test]
+fn strict_emphasis() {
+	let mut data = synthetic_frames(3);
+	data[417 + 3] = 0b10;
+
+	assert_eq!(
+		synthetic_duration(3),
+		from_read(&mut Cursor::new(&data)).unwrap()
+	);
+
+	let options = ParseOptions {
+		strict_emphasis: true,
+		..Default::default()
+	};
+	let error = from_read_with_options(&mut Cursor::new(&data), &options).unwrap_err();
+	assert!(matches!(error.kind, ErrorKind::ReservedEmphasis));
+	assert_eq!(417 + 4, error.offset);
+}
+
+#[test]
+fn estimate_size() {
+	assert_eq!(Duration::from_millis(2_500), estimate_from_size(40_000, 128_000));
+	assert_eq!(Duration::new(0, 333_333_333), estimate_from_size(5_000, 120_000));
+	assert_eq!(Duration::from_secs(0), estimate_from_size(40_000, 0));
+}
+
+#[test]
+fn tag_too_large() {
+	let mut data = b"ID3\x04\x00\x00\x00\x00\x0F\x50".to_vec(); // 2000 bytes
+	data.extend_from_slice(&[0; 2000]);
+	data.extend(synthetic_frames(1));
+	assert!(from_read(&mut Cursor::new(&data)).is_ok());
+
+	let options = ParseOptions {
+		max_tag_bytes: 1000,
+		..Default::default()
+	};
+	let error = from_read_with_options(&mut Cursor::new(&data), &options).unwrap_err();
+	assert!(matches!(error.kind, ErrorKind::TagTooLarge { declared: 2000 }));
+
+	let mut data = b"APETAGEX\xD0\x07\x00\x00\xFF\xFF\xFF\xFF\x00\x00\x00\x00\x00\x00\x00\xA0".to_vec();
+	data.extend_from_slice(&[0; 64]);
+	let error = from_read(&mut Cursor::new(&data)).unwrap_err();
+	assert!(matches!(error.kind, ErrorKind::TagTooLarge { .. }));
+}
+
+#[test]
+fn sample_rate_conversion() {
+	use std::convert::TryFrom;
+	assert_eq!(Ok(SampleRate::Hz22050), SampleRate::try_from(22050));
+	assert_eq!(Err(44000), SampleRate::try_from(44000));
+	assert_eq!(11025, SampleRate::Hz11025.hz());
+}
+
+#[test]
+fn side_information_sizes() {
+	assert_eq!(32, side_information_size(Version::Mpeg1, Mode::JointStereo));
+	assert_eq!(17, side_information_size(Version::Mpeg1, Mode::Mono));
+	assert_eq!(17, side_information_size(Version::Mpeg2, Mode::DualChannel));
+	assert_eq!(9, side_information_size(Version::Mpeg25, Mode::Mono));
+}
+
+#[test]
+fn lenient_stacked_id3v2_with_wrong_size() {
+	// First tag declares 30 bytes more than its actual size, which lands in the middle of the second tag
+	let mut data = b"ID3\x04\x00\x00\x00\x00\x00\x6E".to_vec();
+	data.extend_from_slice(&[b' '; 80]);
+	data.extend_from_slice(b"ID3\x04\x00\x00\x00\x00\x00\x3C");
+	let mut second_tag_body = [b' '; 60];
+	second_tag_body[30..34].copy_from_slice(&[0xFF, 0xFB, 0xE0, 0x00]);
+	data.extend_from_slice(&second_tag_body);
+	data.extend(synthetic_frames(5));
+
+	assert!(from_read(&mut Cursor::new(&data)).is_err());
+
+	let options = ParseOptions {
+		lenient: true,
+		..Default::default()
+	};
+	let duration = from_read_with_options(&mut Cursor::new(&data), &options).unwrap();
+	assert_eq!(synthetic_duration(5), duration);
+}
+
+#[test]
+fn cross_check() {
+	let mut reader = File::open(Path::new("test/VBR0.mp3")).unwrap();
+	let (xing, scanned) = cross_check_from_seek(&mut reader).unwrap();
+	assert_eq!(from_path(Path::new("test/VBR0.mp3")).unwrap(), xing);
+	assert_eq!(398, scanned.as_secs());
+
+	let mut reader = File::open(Path::new("test/ID3v1.mp3")).unwrap();
+	let error = cross_check_from_seek(&mut reader).unwrap_err();
+	assert!(matches!(error.kind, ErrorKind::NoXingHeader));
+}
+
+#[test]
+fn xing_is_accurate() {
+	assert!(xing_is_accurate_from_seek(&mut File::open("test/VBR0.mp3").unwrap()).unwrap());
+	let error = xing_is_accurate_from_seek(&mut File::open("test/ID3v1.mp3").unwrap()).unwrap_err();
+	assert!(matches!(error.kind, ErrorKind::NoXingHeader));
+
+	// Xing/Info frame followed by 10 audio frames
+	let mut data = synthetic_frames(11);
+	for (declared, accurate) in &[(9, true), (10, true), (11, true), (12, false), (100, false), (0, false)] {
+		data[36..48].copy_from_slice(b"Xing\x00\x00\x00\x01\x00\x00\x00\x00");
+		data[47] = *declared;
+		assert_eq!(*accurate, xing_is_accurate_from_seek(&mut Cursor::new(&data)).unwrap(), "{}", declared);
+	}
+}
+
+#[test]
+fn scanner_split_at_every_offset() {
+	let mut tagged = b"ID3\x04\x00\x10\x00\x00\x00\x14".to_vec();
+	tagged.extend_from_slice(&[0x55; 20]);
+	tagged.extend_from_slice(b"3DI\x04\x00\x10\x00\x00\x00\x14");
+	tagged.extend(synthetic_frames(3));
+	tagged.extend_from_slice(b"APETAGEX\xD0\x07\x00\x00\x20\x00\x00\x00");
+	tagged.extend_from_slice(&[0; 48]);
+	tagged.extend_from_slice(b"TAG");
+	tagged.extend_from_slice(&[0x20; 125]);
+
+	let mut damaged = synthetic_frames(2);
+	damaged.extend_from_slice(&[0xFF, 0xE0, 0x12, 0xFF]);
+	damaged.extend(synthetic_frames(2));
+
+	let xing = std::fs::read("test/VBR0.mp3").unwrap()[..3000].to_vec();
+
+	let lenient = ParseOptions {
+		lenient: true,
+		..Default::default()
+	};
+	let cases = [
+		(&tagged, ParseOptions::default(), synthetic_duration(3)),
+		(&tagged, lenient.clone(), synthetic_duration(3)),
+		(&damaged, lenient, synthetic_duration(4)),
+		(&xing, ParseOptions::default(), from_path("test/VBR0.mp3").unwrap()),
+	];
+	for (data, options, duration) in &cases {
+		let expected = summarize(from_read_with_options(&mut Cursor::new(data), options));
+		assert_eq!(Ok(*duration), expected);
+		for split in 0..=data.len() {
+			let mut scanner = DurationScanner::new(options);
+			let result = scanner
+				.feed(&data[..split])
+				.and_then(|duration| match duration {
+					Some(duration) => Ok(Some(duration)),
+					None => scanner.feed(&data[split..]),
+				})
+				.and_then(|duration| duration.map_or_else(|| scanner.finish(), Ok));
+			assert_eq!(expected, summarize(result), "split at {}", split);
+		}
+	}
+}
+
+// Uncompressed sample files, which all functions measure alike
+fn sample_paths() -> Vec<std::path::PathBuf> {
+	let mut paths: Vec<_> = std::fs::read_dir("test")
+		.unwrap()
+		.map(|entry| entry.unwrap().path())
+		.filter(|path| path.extension() != Some("gz".as_ref()))
+		.collect();
+	paths.sort();
+	paths
+}
+
+fn sample_files() -> Vec<Vec<u8>> {
+	let paths = sample_paths();
+	paths.iter().map(|path| std::fs::read(path).unwrap()).collect()
+}
+
+fn summarize(result: Result<Duration, MP3DurationError>) -> Result<Duration, String> {
+	result.map_err(|e| e.to_string())
+}
+
+#[test]
+fn default_options() {
+	let options = ParseOptions::default();
+	assert!(!options.lenient);
+	assert!(!options.skip_trailing_unknown);
+	assert!(options.assume_format.is_none());
+	assert!(!options.require_audio);
+	assert!(!options.count_info_frame);
+	assert!(!options.reject_tags);
+	assert!(!options.validate_crc);
+
+	let paths = sample_paths();
+	for path in &paths {
+		let data = std::fs::read(path).unwrap();
+		let expected = from_read(&mut Cursor::new(&data));
+		let result = from_read_with_options(&mut Cursor::new(&data), &options);
+		match (expected, result) {
+			(Ok(expected), Ok(duration)) => assert_eq!(expected, duration, "{:?}", path),
+			(Err(expected), Err(error)) => {
+				assert_eq!(expected.to_string(), error.to_string(), "{:?}", path);
+				assert_eq!(expected.offset, error.offset, "{:?}", path);
+				assert_eq!(expected.at_duration, error.at_duration, "{:?}", path);
+			}
+			(expected, result) => panic!("{:?}: {:?} != {:?}", path, expected, result),
+		}
+	}
+}
+
+#[test]
+fn at_least() {
+	// Scanning stops once the threshold is reached
+	let data = synthetic_frames(20);
+	let mut reader = Cursor::new(&data);
+	assert!(at_least_from_read(&mut reader, synthetic_duration(5)).unwrap());
+	assert_eq!(417 * 5, reader.position());
+	assert!(at_least_from_read(&mut Cursor::new(&data), synthetic_duration(20)).unwrap());
+	assert!(!at_least_from_read(&mut Cursor::new(&data), synthetic_duration(21)).unwrap());
+	assert!(at_least_from_read(&mut Cursor::new(&data), Duration::default()).unwrap());
+
+	for name in &["test/CBR320.mp3", "test/VBR0.mp3", "test/ID3v2WithImage.mp3"] {
+		let duration = from_path(name).unwrap();
+		assert!(at_least_from_path(name, duration).unwrap());
+		assert!(!at_least_from_path(name, duration + Duration::from_millis(1)).unwrap());
+	}
+	assert!(at_least_from_path("test/piano.jpeg", Duration::from_secs(1)).is_err());
+}
+
+#[test]
+fn reuse_parser() {
+	let options = ParseOptions {
+		lenient: true,
+		..Default::default()
+	};
+	let mut parser = DurationParser::new(&options);
+	assert!(parser.options().lenient);
+	let paths = sample_paths();
+	for path in &paths {
+		let expected = summarize(from_read_with_options(&mut File::open(path).unwrap(), &options));
+		assert_eq!(expected, summarize(parser.measure_from_read(&mut File::open(path).unwrap())));
+		assert_eq!(expected, summarize(parser.measure_from_path(path)));
+	}
+	assert!(parser.measure_from_path("test/missing.mp3").is_err());
+}
+
+#[test]
+fn file_with_buffer() {
+	let mut buffer = vec![0; 4096];
+	let paths = sample_paths();
+	for path in &paths {
+		let expected = summarize(from_read(&mut File::open(path).unwrap()));
+		let file = File::open(path).unwrap();
+		assert_eq!(expected, summarize(from_file_with_buffer(&file, &mut buffer)));
+		if std::fs::metadata(path).unwrap().len() < 10_000 {
+			let file = File::open(path).unwrap();
+			assert_eq!(expected, summarize(from_file_with_buffer(&file, &mut buffer[..1])));
+		}
+	}
+}
+
+#[test]
+fn read_and_slice_sources_agree() {
+	for data in sample_files() {
+		let expected = summarize(from_read(&mut Cursor::new(&data)));
+		assert_eq!(expected, summarize(from_bytes(&data)));
+		assert_eq!(expected, summarize(from_buf_read(&mut Cursor::new(&data))));
+		let mut small_buffer = std::io::BufReader::with_capacity(3, Cursor::new(&data));
+		assert_eq!(expected, summarize(from_buf_read(&mut small_buffer)));
+	}
+}
+
+#[test]
+fn scanner_agrees_with_read_source() {
+	let lenient = ParseOptions {
+		lenient: true,
+		..Default::default()
+	};
+	for options in &[ParseOptions::default(), lenient] {
+		for data in sample_files() {
+			let expected = summarize(from_read_with_options(&mut Cursor::new(&data), options));
+			for chunk_size in &[1, 417, 4096] {
+				if *chunk_size == 1 && data.len() > 10_000 {
+					continue;
+				}
+				let mut scanner = DurationScanner::new(options);
+				let mut result = None;
+				for chunk in data.chunks(*chunk_size) {
+					match scanner.feed(chunk) {
+						Ok(None) => (),
+						Ok(Some(duration)) => result = Some(Ok(duration)),
+						Err(e) => result = Some(Err(e)),
+					}
+					if result.is_some() {
+						break;
+					}
+				}
+				let result = result.unwrap_or_else(|| scanner.finish());
+				assert_eq!(expected, summarize(result));
+			}
+		}
+	}
+}
+
+#[test]
+fn scanner_reset() {
+	let mut scanner = DurationScanner::new(&ParseOptions::default());
+	let data = synthetic_frames(3);
+	assert_eq!(None, scanner.feed(&data[..1000]).unwrap());
+	scanner.reset();
+	assert_eq!(0, scanner.bytes_fed());
+	assert_eq!(Duration::from_secs(0), scanner.duration());
+
+	let data = synthetic_frames(2);
+	assert_eq!(None, scanner.feed(&data).unwrap());
+	assert_eq!(synthetic_duration(2), scanner.finish().unwrap());
+	scanner.reset();
+	let data = synthetic_frames(4);
+	assert_eq!(None, scanner.feed(&data).unwrap());
+	assert_eq!(synthetic_duration(4), scanner.finish().unwrap());
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn async_read() {
+	let data = std::fs::read("test/source.mp3").unwrap();
+	let duration = futures::executor::block_on(from_async_read(&mut futures::io::Cursor::new(&data)));
+	assert_eq!(from_bytes(&data).unwrap(), duration.unwrap());
+}
+
+#[test]
+fn deadline() {
+	let data = synthetic_frames(3);
+	let in_an_hour = std::time::Instant::now() + Duration::from_secs(3600);
+	let duration = from_read_with_deadline(&mut Cursor::new(&data), in_an_hour).unwrap();
+	assert_eq!(synthetic_duration(3), duration);
+
+	let error = from_read_with_deadline(&mut Cursor::new(&data), std::time::Instant::now()).unwrap_err();
+	assert!(matches!(error.kind, ErrorKind::Timeout));
+	assert_eq!(0, error.offset);
+}
+
+#[cfg(feature = "capi")]
+#[test]
+fn capi_round_trip() {
+	use crate::capi::*;
+	use std::ffi::{CStr, CString};
+
+	let mut seconds = 0.0;
+	let path = CString::new("test/source.mp3").unwrap();
+	let result = unsafe { mp3_duration_from_path(path.as_ptr(), &mut seconds) };
+	assert_eq!(MP3_DURATION_OK, result);
+	assert_eq!(from_path(Path::new("test/source.mp3")).unwrap().as_secs_f64(), seconds);
+
+	let path = CString::new("test/Truncated.mp3").unwrap();
+	let result = unsafe { mp3_duration_from_path(path.as_ptr(), &mut seconds) };
+	assert_eq!(MP3_DURATION_ERROR_UNEXPECTED_EOF, result);
+	let message = unsafe { CStr::from_ptr(mp3_duration_last_error()) };
+	assert!(message.to_str().unwrap().starts_with("Unexpected end of file"));
+
+	let result = unsafe { mp3_duration_from_path(std::ptr::null(), &mut seconds) };
+	assert_eq!(MP3_DURATION_ERROR_INVALID_ARGUMENT, result);
+}
+
+#[test]
+fn exact_duration() {
+	let exact = |samples: u64, rate: u64| Duration::from_nanos((samples as u128 * 1_000_000_000 / rate as u128) as u64);
+
+	let options = ParseOptions {
+		trust_first_xing: false,
+		..Default::default()
+	};
+	for name in &["test/ID3v1.mp3", "test/source.mp3", "test/SineEmptyID3.mp3"] {
+		let mut frames = Vec::new();
+		visit_frames_from_read(&mut File::open(name).unwrap(), &options, |frame| {
+			if frame.xing.is_none() {
+				frames.push(frame.header.samples_per_frame() as u64);
+			}
+		})
+		.unwrap();
+		let rate = probe_from_read(&mut File::open(name).unwrap()).unwrap().header.sampling_rate.hz() as u64;
+		let duration = from_path(name).unwrap();
+		let sample_period = Duration::from_nanos(1_000_000_000 / rate);
+		assert!(duration.abs_diff(exact(frames.iter().sum(), rate)) < sample_period);
+	}
+
+	let probe = probe_from_read(&mut File::open("test/VBR0.mp3").unwrap()).unwrap();
+	let samples = probe.xing.unwrap().frames.unwrap() as u64 * probe.header.samples_per_frame() as u64;
+	let duration = from_path("test/VBR0.mp3").unwrap();
+	assert_eq!(exact(samples, probe.header.sampling_rate.hz() as u64), duration);
+}
+
+#[test]
+fn duration_accumulation() {
+	// Per-frame durations are not whole nanoseconds, which adds up over long files
+	let frames = vec![(1152, 44100); 100_000];
+	assert_eq!(Duration::from_nanos(2_612_244_897_959), duration_from_frames(&frames));
+
+	let mut frames = vec![(1152, 48000); 125];
+	frames.extend(vec![(576, 24000); 250]);
+	frames.push((1152, 0));
+	assert_eq!(Duration::from_secs(9), duration_from_frames(&frames));
+	assert_eq!(Duration::from_secs(0), duration_from_frames(&[]));
+}
+
+#[test]
+fn bad_bitrate_index() {
+	assert!(matches!(FrameHeader::parse(0xFFFB_F000), Err(ErrorKind::BadBitrateIndex)));
+
+	let mut data = synthetic_frames(2);
+	data.extend_from_slice(&[0xFF, 0xFB, 0xF0, 0x00]);
+	data.extend(synthetic_frames(2));
+	let error = from_read(&mut Cursor::new(&data)).unwrap_err();
+	assert!(matches!(error.kind, ErrorKind::BadBitrateIndex));
+
+	let options = ParseOptions {
+		lenient: true,
+		..Default::default()
+	};
+	let duration = from_read_with_options(&mut Cursor::new(&data), &options).unwrap();
+	assert_eq!(synthetic_duration(4), duration);
+}
+
+#[cfg(feature = "md5")]
+#[test]
+fn audio_md5() {
+	use md5::{Digest, Md5};
+
+	let mut frames = synthetic_frames(3);
+	frames[500] = 0x42;
+	let (duration, digest) = audio_md5_from_read(&mut Cursor::new(&frames), &ParseOptions::default()).unwrap();
+	assert_eq!(synthetic_duration(3), duration);
+	assert_eq!(<[u8; 16]>::from(Md5::digest(&frames)), digest);
+
+	// Tags are not hashed
+	let mut id3v1 = vec![0; 128];
+	id3v1[..3].copy_from_slice(b"TAG");
+	let tagged = [b"ID3\x04\x00\x00\x00\x00\x00\x05".to_vec(), vec![0x55; 5], frames.clone(), id3v1].concat();
+	assert_eq!(digest, audio_md5_from_read(&mut Cursor::new(&tagged), &ParseOptions::default()).unwrap().1);
+
+	frames[501] = 0x42;
+	assert_ne!(digest, audio_md5_from_read(&mut Cursor::new(&frames), &ParseOptions::default()).unwrap().1);
+}
+
+#[test]
+fn visit_frames() {
+	let mut data = synthetic_frames(3);
+	data[2 * 417 + 400..3 * 417].copy_from_slice(b"WATERMARK-IN-ANC!");
+	data.truncate(data.len() - 1);
+
+	let mut frames = Vec::new();
+	let options = ParseOptions::default();
+	let error = visit_frames_from_read(&mut Cursor::new(&data), &options, |frame| {
+		frames.push((frame.offset, frame.bytes.to_vec()))
+	})
+	.unwrap_err();
+	assert!(matches!(error.kind, ErrorKind::UnexpectedEOF));
+	assert_eq!(2, frames.len());
+
+	data.push(b'!');
+	frames.clear();
+	let duration = visit_frames_from_read(&mut Cursor::new(&data), &options, |frame| {
+		assert!(frame.xing.is_none());
+		frames.push((frame.offset, frame.bytes.to_vec()))
+	})
+	.unwrap();
+	assert_eq!(synthetic_duration(3), duration);
+	assert_eq!(vec![0, 417, 834], frames.iter().map(|(offset, _)| *offset).collect::<Vec<_>>());
+	assert_eq!(&data[417..834], &frames[1].1[..]);
+	assert!(frames[2].1.ends_with(b"WATERMARK-IN-ANC!"));
+}
+
+#[test]
+fn visit_frames_with_options() {
+	let option_sets = [
+		ParseOptions::default(),
+		ParseOptions {
+			count_info_frame: true,
+			..Default::default()
+		},
+		ParseOptions {
+			trust_first_xing: false,
+			count_info_frame: true,
+			..Default::default()
+		},
+		ParseOptions {
+			lenient: true,
+			require_audio: true,
+			..Default::default()
+		},
+	];
+	for options in &option_sets {
+		for name in &["test/VBR0.mp3", "test/XingStub.mp3", "test/TruncatedXing.mp3", "test/SineEmptyID3.mp3"] {
+			let expected = summarize(from_read_with_options(&mut File::open(name).unwrap(), options));
+			let duration = visit_frames_from_read(&mut File::open(name).unwrap(), options, |_| ());
+			assert_eq!(expected, summarize(duration), "{} {:?}", name, options);
+		}
+	}
+
+	// Reading stops after a trusted Xing/Info header
+	let mut frames = 0;
+	visit_frames_from_read(&mut File::open("test/VBR0.mp3").unwrap(), &ParseOptions::default(), |frame| {
+		assert!(frame.xing.is_some());
+		frames += 1;
+	})
+	.unwrap();
+	assert_eq!(1, frames);
+
+	// Streams without audio are rejected like with `from_read_with_options`
+	let options = ParseOptions {
+		require_audio: true,
+		..Default::default()
+	};
+	let id3v1 = [b"TAG".to_vec(), vec![0; 125]].concat();
+	let error = visit_frames_from_read(&mut Cursor::new(&id3v1), &options, |_| ()).unwrap_err();
+	assert!(matches!(error.kind, ErrorKind::NoAudioFrames));
+}
+
+#[test]
+fn bit_reservoir() {
+	let mut data = synthetic_frames(3);
+	data[4..4 + 32].copy_from_slice(&[0; 32]);
+	data[417 + 4..417 + 6].copy_from_slice(&[0x01, 0x80]);
+	let mut main_data_begin = Vec::new();
+	visit_frames_from_read(&mut Cursor::new(&data), &ParseOptions::default(), |frame| {
+		main_data_begin.push(frame.main_data_begin)
+	})
+	.unwrap();
+	assert_eq!(vec![0, 3, 0xAA], main_data_begin);
+
+	let analysis = analyze_from_read(&mut Cursor::new(&data)).unwrap();
+	assert_eq!(2, analysis.bit_reservoir_frames);
+}
+
+// First frame of a Fraunhofer VBR stream of 20 synthetic frames, with a VBRI header whose table of contents uses
+// 2-byte entries scaled by 3
+fn vbri_frame(header: [u8; 4], toc: &[u16]) -> Vec<u8> {
+	let mut vbri = b"VBRI\x00\x01\x02\x40\x00\x4B".to_vec();
+	vbri.extend_from_slice(&(417 * 20u32).to_be_bytes());
+	vbri.extend_from_slice(&20u32.to_be_bytes());
+	vbri.extend_from_slice(&(toc.len() as u16).to_be_bytes());
+	vbri.extend_from_slice(&[0x00, 0x03, 0x00, 0x02, 0x00, 0x05]);
+	for entry in toc {
+		vbri.extend_from_slice(&entry.to_be_bytes());
+	}
+	let mut frame = vec![0; 417];
+	frame[..4].copy_from_slice(&header);
+	vbri.truncate(frame.len() - 36);
+	frame[36..36 + vbri.len()].copy_from_slice(&vbri);
+	frame
+}
+
+#[test]
+fn vbri() {
+	for header in [[0xFF, 0xFB, 0x90, 0x00], [0xFF, 0xFB, 0x90, 0xC0]] {
+		let data = [vbri_frame(header, &[695; 4]), synthetic_frames(20)].concat();
+		let probe = probe_from_read(&mut Cursor::new(&data)).unwrap();
+		let expected = VbriInfo {
+			version: 1,
+			delay: 576,
+			quality: 75,
+			bytes: 417 * 20,
+			frames: 20,
+			toc: vec![417 * 5; 4],
+			frames_per_entry: 5,
+		};
+		assert_eq!(Some(expected), probe.vbri);
+		assert_eq!(synthetic_duration(21), from_read(&mut Cursor::new(&data)).unwrap());
+
+		let index = vbri_seek_index_from_read(&mut Cursor::new(&data)).unwrap().unwrap();
+		let offsets: Vec<_> = index.points().iter().map(|(offset, _)| *offset).collect();
+		assert_eq!(vec![417, 417 * 6, 417 * 11, 417 * 16], offsets);
+		assert_eq!(synthetic_duration(20), index.duration());
+		assert_eq!(Some(417 * 6), index.time_to_byte(synthetic_duration(7)));
+		assert_eq!(Some(synthetic_duration(15)), index.byte_to_time(data.len() as u64 - 1));
+	}
+
+	// The table of contents must fit in the frame
+	let data = [vbri_frame([0xFF, 0xFB, 0x90, 0x00], &[0; 200]), synthetic_frames(20)].concat();
+	let probe = probe_from_read(&mut Cursor::new(&data)).unwrap();
+	assert_eq!(Some(20), probe.vbri.map(|vbri| vbri.frames));
+	assert_eq!(None, vbri_seek_index_from_read(&mut Cursor::new(&data)).unwrap());
+
+	let path = Path::new("test/VBR0.mp3");
+	assert_eq!(None, vbri_seek_index_from_read(&mut File::open(path).unwrap()).unwrap());
+}
+
+#[test]
+fn detect_encoder() {
+	let detect = |path| detect_encoder_from_read(&mut File::open(path).unwrap()).unwrap();
+	assert_eq!(EncoderGuess::Lame("3.98r".to_owned()), detect("test/VBR0.mp3"));
+	assert_eq!(EncoderGuess::Lame("3.98r".to_owned()), detect("test/CBR320.mp3"));
+	assert_eq!(EncoderGuess::Ffmpeg("Lavc58.35".to_owned()), detect("test/SineEmptyID3.mp3"));
+	// Version string in the ancillary data of frames
+	assert_eq!(EncoderGuess::Lame("3.91".to_owned()), detect("test/source.mp3"));
+	assert_eq!(EncoderGuess::Unknown, detect("test/MPEG2Layer1.mp3"));
+
+	let data = [vbri_frame([0xFF, 0xFB, 0x90, 0x00], &[695; 4]), synthetic_frames(20)].concat();
+	assert_eq!(EncoderGuess::FraunhoferVbr, detect_encoder_from_read(&mut Cursor::new(&data)).unwrap());
+	let mut data = synthetic_frames(30);
+	data[417 * 25 + 100..417 * 25 + 108].copy_from_slice(b"LAME3.90");
+	assert_eq!(EncoderGuess::Unknown, detect_encoder_from_read(&mut Cursor::new(&data)).unwrap());
+	assert_eq!(EncoderGuess::Unknown, detect_encoder_from_read(&mut Cursor::new(b"")).unwrap());
+}
+
+#[test]
+fn seek_index() {
+	let mut data = vec![0; 100];
+	data.extend(synthetic_frames(10));
+	let frame_duration = synthetic_duration(1);
+
+	let index = build_seek_index_from_read(&mut Cursor::new(&data)).unwrap();
+	assert_eq!(10, index.points().len());
+	assert_eq!(synthetic_duration(10), index.duration());
+	assert_eq!(Some(100), index.time_to_byte(Duration::default()));
+	assert_eq!(Some(100 + 417), index.time_to_byte(frame_duration));
+	assert_eq!(Some(100 + 2 * 417), index.time_to_byte(frame_duration * 3 - Duration::from_nanos(1)));
+	assert_eq!(None, index.time_to_byte(synthetic_duration(10)));
+	assert_eq!(None, index.byte_to_time(99));
+	assert_eq!(Some(Duration::default()), index.byte_to_time(100));
+	assert_eq!(Some(synthetic_duration(2)), index.byte_to_time(100 + 3 * 417 - 1));
+	assert_eq!(Some(synthetic_duration(9)), index.byte_to_time(data.len() as u64 - 1));
+	assert_eq!(None, index.byte_to_time(data.len() as u64));
+
+	let strided = build_seek_index_from_read_with_stride(&mut Cursor::new(&data), 4).unwrap();
+	let offsets: Vec<_> = strided.points().iter().map(|(offset, _)| *offset).collect();
+	assert_eq!(vec![100, 100 + 4 * 417, 100 + 8 * 417], offsets);
+	assert_eq!(Some(100 + 4 * 417), strided.time_to_byte(synthetic_duration(7)));
+	assert_eq!(Some(synthetic_duration(8)), strided.byte_to_time(data.len() as u64 - 1));
+	assert_eq!(index.duration(), strided.duration());
+
+	// Xing/Info frames are not indexed
+	let path = Path::new("test/VBR0.mp3");
+	let index = build_seek_index_from_read(&mut File::open(path).unwrap()).unwrap();
+	assert_eq!(15249, index.points().len());
+	assert!(index.points()[0].0 > 0);
+}
+
+#[test]
+fn frame_timestamps() {
+	let timestamps = frame_timestamps_from_read(&mut Cursor::new(synthetic_frames(3))).unwrap();
+	let expected: Vec<_> = (0..3).map(synthetic_duration).collect();
+	assert_eq!(expected, timestamps);
+
+	let mut reader = File::open("test/VBR0.mp3").unwrap();
+	let timestamps = frame_timestamps_from_read(&mut reader).unwrap();
+	let mut reader = File::open("test/VBR0.mp3").unwrap();
+	let (_, scanned) = cross_check_from_seek(&mut reader).unwrap();
+	assert!(timestamps.windows(2).all(|pair| pair[0] < pair[1]));
+	assert!(*timestamps.last().unwrap() < scanned);
+}
+
+#[test]
+fn downstream_parser_offsets() {
+	let mut data = synthetic_frames(2);
+	data.extend_from_slice(b"JUNK");
+	let expected = from_read(&mut Cursor::new(&data)).unwrap_err();
+
+	let mut reader = Cursor::new(&data);
+	let mut source = Context::new(&mut reader);
+	let mut header = [0; 4];
+	let error = loop {
+		source.read_exact(&mut header).unwrap();
+		let header = u32::from_be_bytes(header);
+		match FrameHeader::parse(header) {
+			Ok(frame_header) if header >> 21 == 0x7FF => source.skip(frame_header.frame_length() - 4).unwrap(),
+			_ => {
+				break MP3DurationError {
+					kind: ErrorKind::UnexpectedFrame { header },
+					offset: source.bytes_read(),
+					at_duration: synthetic_duration(2),
+					first_header: None,
+				}
+			}
+		}
+	};
+	assert_eq!(expected.to_string(), error.to_string());
+}
+
+#[test]
+fn offsets_beyond_4_gb() {
+	use crate::parser::Parser;
+	use crate::source::SliceSource;
+
+	let base_offset = 5 * 1024 * 1024 * 1024;
+	let mut data = synthetic_frames(2);
+	data.extend_from_slice(b"JUNK");
+	let options = ParseOptions::default();
+	let error = Parser::new(SliceSource::new(&data, base_offset, true), &options)
+		.measure()
+		.unwrap_err();
+	assert_eq!(base_offset + 2 * 417 + 4, error.offset);
+}
+
+#[test]
+fn parser_state_rollback() {
+	use crate::parser::Parser;
+	use crate::source::SliceSource;
+
+	// Text preamble and invalid header before a frame whose side information is cut short
+	let data = [b"  \xFF\xE0\x12\xFF".to_vec(), synthetic_frames(1)].concat();
+	let options = ParseOptions {
+		lenient: true,
+		..Default::default()
+	};
+	let mut parser = Parser::new(SliceSource::new(&data[..16], 0, false), &options);
+	let checkpoint = parser.state.checkpoint();
+	let _ = parser.measure_next_block();
+	assert!(parser.source.incomplete);
+	assert!(!parser.state.warnings.is_empty());
+	parser.state.rollback(checkpoint);
+	assert!(parser.state.warnings.is_empty());
+	assert_eq!(0, parser.state.resynced_bytes);
+
+	let mut scanner = DurationScanner::new(&options);
+	for byte in &data {
+		assert_eq!(None, scanner.feed(&[*byte]).unwrap());
+	}
+	assert_eq!(synthetic_duration(1), scanner.finish().unwrap());
+}
+
+#[test]
+fn segments() {
+	let vbr0 = std::fs::read("test/VBR0.mp3").unwrap();
+	let mut data = vbr0.clone();
+	data.extend(std::fs::read("test/ID3v2.mp3").unwrap());
+	data.extend(std::fs::read("test/ID3v1.mp3").unwrap());
+
+	// The last file starts with audio frames, which cannot be told apart from the previous file
+	let segments = segments_from_read(&mut Cursor::new(&data)).unwrap();
+	assert_eq!(2, segments.len());
+	assert_eq!((0, vbr0.len() as u64), (segments[0].start, segments[0].end));
+	assert!(segments[0].has_xing);
+	assert_eq!((vbr0.len() as u64, data.len() as u64), (segments[1].start, segments[1].end));
+	// Each segment duration is rounded down to the nanosecond
+	let total: Duration = segments.iter().map(|segment| segment.duration).sum();
+	let expected = from_read_multistream(&mut Cursor::new(&data)).unwrap();
+	assert!(expected - total <= Duration::from_nanos(segments.len() as u64));
+
+	let mut data = synthetic_frames(3);
+	data.extend_from_slice(b"ID3\x04\x00\x00\x00\x00\x00\x00");
+	data.extend(synthetic_frames(2));
+	data.extend_from_slice(b"TAG");
+	data.extend_from_slice(&[b' '; 125]);
+	let segments = segments_from_read(&mut Cursor::new(&data)).unwrap();
+	assert_eq!(
+		vec![(0, 3 * 417, 3), (3 * 417, data.len() as u64, 2)],
+		segments
+			.iter()
+			.map(|segment| (segment.start, segment.end, segment.frames))
+			.collect::<Vec<_>>()
+	);
+	assert_eq!(synthetic_duration(2), segments[1].duration);
+}
+
+// Fails every other read, and never returns more than 100 bytes at once
+struct FlakyReader {
+	inner: Cursor<Vec<u8>>,
+	reads: usize,
+}
+
+impl std::io::Read for FlakyReader {
+	fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+		self.reads += 1;
+		match self.reads % 4 {
+			1 => Err(std::io::ErrorKind::WouldBlock.into()),
+			3 => Err(std::io::ErrorKind::Interrupted.into()),
+			_ => {
+				let length = buffer.len().min(100);
+				self.inner.read(&mut buffer[..length])
+			}
+		}
+	}
+}
+
+#[test]
+fn retry_spurious_read_errors() {
+	use std::io::Read;
+
+	let data = std::fs::read("test/ID3v2.mp3").unwrap();
+	let expected = from_bytes(&data).unwrap();
+	let flaky_reader = || FlakyReader {
+		inner: Cursor::new(data.clone()),
+		reads: 0,
+	};
+	assert_eq!(expected, from_read(&mut flaky_reader()).unwrap());
+	assert_eq!(expected, from_buf_read(&mut std::io::BufReader::new(flaky_reader())).unwrap());
+
+	let mut reader = flaky_reader().take(1000);
+	assert!(matches!(from_read(&mut reader).unwrap_err().kind, ErrorKind::UnexpectedEOF));
+}
+
+// Non-blocking reader which never has any data available
+struct StalledReader;
+
+impl std::io::Read for StalledReader {
+	fn read(&mut self, _buffer: &mut [u8]) -> std::io::Result<usize> {
+		Err(std::io::ErrorKind::WouldBlock.into())
+	}
+}
+
+#[test]
+fn give_up_on_stalled_reader() {
+	let error = from_read(&mut StalledReader).unwrap_err();
+	assert!(matches!(error.kind, ErrorKind::IOError(ref e) if e.kind() == std::io::ErrorKind::WouldBlock));
+	assert_eq!(0, error.offset);
+	let error = from_buf_read(&mut std::io::BufReader::new(StalledReader)).unwrap_err();
+	assert!(matches!(error.kind, ErrorKind::IOError(ref e) if e.kind() == std::io::ErrorKind::WouldBlock));
+}
+
+#[test]
+fn quick_estimate() {
+	for name in &["test/ID3v1.mp3", "test/ID3v2WithImage.mp3", "test/VBR9.mp3"] {
+		let exact = from_path(Path::new(name)).unwrap();
+		let estimate = quick_estimate_from_path(Path::new(name)).unwrap();
+		assert_duration_approx(estimate, exact.as_secs_f64(), 1);
+	}
+	assert!(quick_estimate_from_path(Path::new("test/piano.jpeg")).is_err());
+}
+
+#[test]
+fn gaps() {
+	let data = [
+		synthetic_frames(3),
+		vec![0x55; 100],
+		synthetic_frames(2),
+		vec![0x55; 10],
+		vec![0; 10],
+		vec![0x55; 10],
+		b"ID3\x04\x00\x00\x00\x00\x00\x0A".to_vec(),
+		vec![0; 10],
+		vec![0x55; 20],
+	]
+	.concat();
+	let analysis = analyze_from_read(&mut Cursor::new(&data)).unwrap();
+	assert_eq!(synthetic_duration(5), analysis.duration);
+	let gaps = vec![
+		Gap {
+			offset: 417 * 3,
+			bytes: 100,
+		},
+		Gap {
+			offset: 417 * 5 + 100,
+			bytes: 30,
+		},
+		Gap {
+			offset: 417 * 5 + 150,
+			bytes: 20,
+		},
+	];
+	assert_eq!(gaps, analysis.gaps);
+
+	let analysis = analyze_from_read(&mut Cursor::new(synthetic_frames(5))).unwrap();
+	assert!(analysis.gaps.is_empty());
+}
+
+#[test]
+fn dual_channel() {
+	let mut frame = vec![0; 417];
+	frame[..4].copy_from_slice(&[0xFF, 0xFB, 0x90, 0x80]);
+	let data = frame.repeat(5);
+	let measured = from_read_with_metadata(&mut Cursor::new(&data), &ParseOptions::default()).unwrap();
+	assert_eq!(synthetic_duration(5), *measured);
+	assert_eq!(Some(Mode::DualChannel), measured.metadata().mode);
+	assert!(measured.metadata().is_dual_channel());
+
+	let measured = from_read_with_metadata(&mut Cursor::new(synthetic_frames(5)), &ParseOptions::default()).unwrap();
+	assert_eq!(Some(Mode::Stereo), measured.metadata().mode);
+	assert!(!measured.metadata().is_dual_channel());
+
+	let measured = from_read_with_metadata(&mut Cursor::new(b"TAG"), &ParseOptions::default()).unwrap();
+	assert_eq!(None, measured.metadata().mode);
+}
+
+#[test]
+fn padded_frames() {
+	let measured = from_read_with_metadata(&mut Cursor::new(synthetic_frames(5)), &ParseOptions::default()).unwrap();
+	assert_eq!(Some(0), measured.metadata().padded_frames);
+
+	let data = std::fs::read("test/MPEG2Layer1.mp3").unwrap();
+	let measured = from_read_with_metadata(&mut Cursor::new(&data), &ParseOptions::default()).unwrap();
+	assert_eq!(Some(100), measured.metadata().scanned_frames);
+	assert_eq!(Some(33), measured.metadata().padded_frames);
+
+	let data = std::fs::read("test/VBR0.mp3").unwrap();
+	let measured = from_read_with_metadata(&mut Cursor::new(&data), &ParseOptions::default()).unwrap();
+	assert_eq!(None, measured.metadata().padded_frames);
+}
+
+#[test]
+fn read_with_size() {
+	// Constant bitrate streams are not read past their first frames
+	let data = synthetic_frames(20);
+	let mut reader = Cursor::new(&data);
+	let duration = from_read_with_size(&mut reader, 417 * 100).unwrap();
+	assert_eq!(estimate_from_size(417 * 100, 128_000), duration);
+	assert!(reader.position() < data.len() as u64);
+
+	// Variable bitrate streams without Xing/Info header are scanned entirely
+	let mut data = synthetic_frames(3);
+	let mut frame = vec![0; 522];
+	frame[..4].copy_from_slice(&[0xFF, 0xFB, 0xA0, 0x00]);
+	data.extend(frame);
+	data.extend(synthetic_frames(20));
+	let duration = from_read_with_size(&mut Cursor::new(&data), 1_000_000).unwrap();
+	assert_eq!(synthetic_duration(24), duration);
+
+	for name in &["test/CBR320.mp3", "test/VBR0.mp3", "test/SingleFrame.mp3"] {
+		let data = std::fs::read(name).unwrap();
+		let exact = from_bytes(&data).unwrap();
+		let estimate = from_read_with_size(&mut Cursor::new(&data), data.len() as u64).unwrap();
+		assert_duration_approx(estimate, exact.as_secs_f64(), 1);
+	}
+}
+
+#[cfg(feature = "stream")]
+#[test]
+fn byte_stream() {
+	use futures::executor::block_on;
+	use futures::stream;
+
+	for name in &["test/ID3v2WithImage.mp3", "test/Truncated.mp3"] {
+		let data = std::fs::read(name).unwrap();
+		let chunks = data.chunks(1000).map(|chunk| Ok::<_, std::io::Error>(chunk.to_vec()));
+		let duration = block_on(from_byte_stream(stream::iter(chunks)));
+		assert_eq!(summarize(from_bytes(&data)), summarize(duration));
+	}
+
+	let chunks = vec![Ok(vec![0xFF, 0xFB]), Err(std::io::Error::from(std::io::ErrorKind::ConnectionReset))];
+	let error = block_on(from_byte_stream(stream::iter(chunks))).unwrap_err();
+	assert!(matches!(error.kind, ErrorKind::IOError(_)));
+	assert_eq!(2, error.offset);
+}
+
+#[cfg(feature = "gzip")]
+#[test]
+fn gzip() {
+	use flate2::write::GzEncoder;
+	use std::io::Write;
+
+	for name in &["test/source.mp3", "test/Truncated.mp3"] {
+		let data = std::fs::read(name).unwrap();
+		let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+		encoder.write_all(&data).unwrap();
+		let compressed = encoder.finish().unwrap();
+		let expected = summarize(from_read_with_options(&mut Cursor::new(&data), &ParseOptions::default()));
+		assert_eq!(expected, summarize(from_read(&mut Cursor::new(&data))));
+		assert_eq!(expected, summarize(from_read(&mut Cursor::new(&compressed))));
+
+		let path = std::env::temp_dir().join(format!("mp3-duration-gzip-{}.mp3.gz", compressed.len()));
+		std::fs::write(&path, &compressed).unwrap();
+		let duration = from_path(&path);
+		std::fs::remove_file(&path).unwrap();
+		assert_eq!(expected, summarize(duration));
+	}
+
+	// Streams shorter than the magic number are not affected
+	let expected = summarize(from_read_with_options(&mut Cursor::new(&[0x1F]), &ParseOptions::default()));
+	assert_eq!(expected, summarize(from_read(&mut Cursor::new(&[0x1F]))));
+}
+
+#[cfg(feature = "gzip")]
+#[test]
+fn gzip_fixture() {
+	let data = std::fs::read("test/SineEmptyID3.mp3").unwrap();
+	let compressed = std::fs::read("test/SineEmptyID3.mp3.gz").unwrap();
+	let (duration, bytes_read) = measure_from_read(&mut Cursor::new(&data)).unwrap();
+	assert_eq!((duration, bytes_read), measure_from_read(&mut Cursor::new(&compressed)).unwrap());
+	assert_eq!(duration, from_read(&mut Cursor::new(&compressed)).unwrap());
+	let options = ParseOptions {
+		lenient: true,
+		..Default::default()
+	};
+	let expected = from_read_with_options(&mut Cursor::new(&data), &options).unwrap();
+	assert_eq!(expected, from_read_with_options(&mut Cursor::new(&compressed), &options).unwrap());
+	assert_eq!(duration, from_path("test/SineEmptyID3.mp3.gz").unwrap());
+	assert_eq!(duration, from_file(&File::open("test/SineEmptyID3.mp3.gz").unwrap()).unwrap());
+}
+
+#[cfg(feature = "tar")]
+#[test]
+fn tar_archive() {
+	let mut builder = tar::Builder::new(Vec::new());
+	let files = [
+		("a.mp3", "test/source.mp3"),
+		("cover.jpeg", "test/piano.jpeg"),
+		("b.MP3", "test/Truncated.mp3"),
+	];
+	for (name, path) in &files {
+		builder.append_path_with_name(path, name).unwrap();
+	}
+	let archive = builder.into_inner().unwrap();
+
+	let results: Vec<_> = from_tar(Cursor::new(&archive)).map(|(name, result)| (name, summarize(result))).collect();
+	let expected = vec![
+		("a.mp3".to_owned(), summarize(from_path("test/source.mp3"))),
+		("b.MP3".to_owned(), summarize(from_path("test/Truncated.mp3"))),
+	];
+	assert_eq!(expected, results);
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn measure_many_readers() {
+	let paths = ["test/source.mp3", "test/piano.jpeg", "test/VBR0.mp3", "test/Truncated.mp3"];
+	let readers = paths.iter().map(|path| Cursor::new(std::fs::read(path).unwrap()));
+	let results: Vec<_> = measure_many(readers).into_iter().map(summarize).collect();
+	let expected: Vec<_> = paths.iter().map(|path| summarize(from_path(path))).collect();
+	assert_eq!(expected, results);
+	assert!(measure_many(Vec::<Cursor<Vec<u8>>>::new()).is_empty());
+}
+
+#[cfg(feature = "zip")]
+#[test]
+fn zip_archive() {
+	use zip::write::{SimpleFileOptions, ZipWriter};
+	use zip::CompressionMethod;
+
+	let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+	let files = [
+		("a.mp3", "test/source.mp3", CompressionMethod::Deflated),
+		("cover.jpeg", "test/piano.jpeg", CompressionMethod::Stored),
+		("b.MP3", "test/Truncated.mp3", CompressionMethod::Stored),
+	];
+	for (name, path, method) in &files {
+		writer.start_file(*name, SimpleFileOptions::default().compression_method(*method)).unwrap();
+		writer.write_all(&std::fs::read(path).unwrap()).unwrap();
+	}
+	let archive = writer.finish().unwrap().into_inner();
+
+	let results: Vec<_> = from_zip(Cursor::new(&archive)).map(|(name, result)| (name, summarize(result))).collect();
+	let expected = vec![
+		("a.mp3".to_owned(), summarize(from_path("test/source.mp3"))),
+		("b.MP3".to_owned(), summarize(from_path("test/Truncated.mp3"))),
+	];
+	assert_eq!(expected, results);
+
+	let results: Vec<_> = from_zip(Cursor::new(&archive[..10])).collect();
+	assert_eq!(1, results.len());
+	assert!(results[0].1.is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn pipe() {
+	use std::process::{Command, Stdio};
+
+	for path in &["test/VBR0.mp3", "test/ID3v2WithBadPadding.mp3", "test/APEv2.mp3", "test/Truncated.mp3"] {
+		let expected = summarize(from_path(path));
+		let mut child = Command::new("cat").arg(path).stdout(Stdio::piped()).spawn().unwrap();
+		let mut stdout = child.stdout.take().unwrap();
+		assert_eq!(expected, summarize(from_read(&mut stdout)), "{}", path);
+		drop(stdout);
+		child.wait().unwrap();
+
+		let mut child = Command::new("cat").arg(path).stdout(Stdio::piped()).spawn().unwrap();
+		let mut stdout = std::io::BufReader::new(child.stdout.take().unwrap());
+		assert_eq!(expected, summarize(from_buf_read(&mut stdout)), "{}", path);
+		drop(stdout);
+		child.wait().unwrap();
+	}
+}
+
+#[cfg(unix)]
+#[test]
+fn file_descriptor() {
+	use std::os::unix::io::AsFd;
+	use std::process::{Command, Stdio};
+
+	let mut child = Command::new("cat").arg("test/source.mp3").stdout(Stdio::piped()).spawn().unwrap();
+	let duration = from_fd(child.stdout.as_ref().unwrap().as_fd());
+	child.wait().unwrap();
+	assert_eq!(from_path("test/source.mp3").unwrap(), duration.unwrap());
+}
+
+// Fails for good after returning a given number of bytes
+struct BrokenReader {
+	inner: Cursor<Vec<u8>>,
+	remaining: usize,
+}
+
+impl Read for BrokenReader {
+	fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+		if self.remaining == 0 {
+			return Err(std::io::ErrorKind::ConnectionReset.into());
+		}
+		let length = buffer.len().min(self.remaining);
+		let read = self.inner.read(&mut buffer[..length])?;
+		self.remaining -= read;
+		Ok(read)
+	}
+}
+
+#[test]
+fn io_error_reports_duration() {
+	let data = synthetic_frames(10);
+	// Fails while skipping over the content of the fifth frame, and when reading its header
+	for remaining in &[4 * 417 + 100, 4 * 417 + 2] {
+		let mut reader = BrokenReader {
+			inner: Cursor::new(data.clone()),
+			remaining: *remaining,
+		};
+		let error = from_read(&mut reader).unwrap_err();
+		assert!(matches!(error.kind, ErrorKind::IOError(_)));
+		assert_eq!(synthetic_duration(4), error.at_duration);
+		let mut reader = std::io::BufReader::with_capacity(
+			64,
+			BrokenReader {
+				inner: Cursor::new(data.clone()),
+				remaining: *remaining,
+			},
+		);
+		let error = from_buf_read(&mut reader).unwrap_err();
+		assert!(matches!(error.kind, ErrorKind::IOError(_)));
+		assert_eq!(synthetic_duration(4), error.at_duration);
+	}
+}
+
+#[test]
+fn io_error_source_chain() {
+	use std::error::Error;
+
+	struct FailingReader;
+	impl Read for FailingReader {
+		fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+			Err(std::io::Error::from_raw_os_error(5))
+		}
+	}
+
+	let error = from_read(&mut FailingReader).unwrap_err();
+	let kind = error.source().unwrap();
+	assert!(kind.is::<ErrorKind>());
+	let io_error = kind.source().unwrap().downcast_ref::<std::io::Error>().unwrap();
+	assert_eq!(Some(5), io_error.raw_os_error());
+	assert!(kind.source().unwrap().source().is_none());
+}
+
+#[test]
+fn error_first_header() {
+	let mut data = vec![0; 10];
+	data.extend(synthetic_frames(3));
+	data.extend_from_slice(&[0x55; 10]);
+	let error = from_read(&mut Cursor::new(&data)).unwrap_err();
+	let header = error.first_header.unwrap();
+	assert_eq!(Version::Mpeg1, header.version);
+	assert_eq!(Layer::Layer3, header.layer);
+	assert_eq!(44100, header.sampling_rate.hz());
+
+	let mut scanner = DurationScanner::new(&ParseOptions::default());
+	assert!(scanner.first_header().is_none());
+	let error = scanner.feed(&data).unwrap_err();
+	assert_eq!(Some(header), error.first_header);
+	assert_eq!(Some(header), scanner.first_header());
+
+	let error = from_read(&mut Cursor::new(&data[..20])).unwrap_err();
+	assert!(error.first_header.is_none());
+	let error = from_path("test/piano.jpeg").unwrap_err();
+	assert!(error.first_header.is_none());
+}
+
+#[test]
+fn into_io_error() {
+	fn duration(data: &[u8]) -> std::io::Result<Duration> {
+		Ok(from_read(&mut Cursor::new(data))?)
+	}
+
+	let error = duration(b"JUNK").unwrap_err();
+	assert_eq!(std::io::ErrorKind::InvalidData, error.kind());
+	assert!(error.to_string().starts_with("Unexpected frame"));
+	let inner = error.into_inner().unwrap().downcast::<MP3DurationError>().unwrap();
+	assert!(matches!(inner.kind, ErrorKind::UnexpectedFrame { .. }));
+
+	let error = duration(&synthetic_frames(2)[..600]).unwrap_err();
+	assert_eq!(std::io::ErrorKind::UnexpectedEof, error.kind());
+
+	let error: std::io::Error = from_read(&mut BrokenReader {
+		inner: Cursor::new(Vec::new()),
+		remaining: 0,
+	})
+	.unwrap_err()
+	.into();
+	assert_eq!(std::io::ErrorKind::ConnectionReset, error.kind());
+	assert!(error.get_ref().is_none());
+}
+
+#[test]
+fn hms_format() {
+	assert_eq!("00:00.000", format_hms(Duration::default()));
+	assert_eq!("00:00.999", format_hms(Duration::from_nanos(999_999_999)));
+	assert_eq!("00:01.250", format_hms(Duration::from_millis(1250)));
+	assert_eq!("59:59.999", format_hms(Duration::from_millis(3_599_999)));
+	assert_eq!("01:00:00.000", format_hms(Duration::from_secs(3600)));
+	assert_eq!("02:03:04.005", format_hms(Duration::from_millis(7_384_005)));
+	assert_eq!("123:00:00.000", format_hms(Duration::from_secs(123 * 3600)));
+	let duration = from_path("test/source.mp3").unwrap();
+	assert_eq!("06:38.288", format_hms(duration));
+}
+
+#[test]
+fn max_frames() {
+	let data = synthetic_frames(10);
+	let options = ParseOptions {
+		max_frames: Some(10),
+		..Default::default()
+	};
+	assert_eq!(synthetic_duration(10), from_read_with_options(&mut Cursor::new(&data), &options).unwrap());
+	let options = ParseOptions {
+		max_frames: Some(6),
+		..Default::default()
+	};
+	let error = from_read_with_options(&mut Cursor::new(&data), &options).unwrap_err();
+	assert!(matches!(error.kind, ErrorKind::FrameLimitExceeded { max_frames: 6 }));
+	assert_eq!(synthetic_duration(6), error.at_duration);
+	let error = from_read_with_metadata(&mut Cursor::new(&data), &options).unwrap_err();
+	assert!(matches!(error.kind, ErrorKind::FrameLimitExceeded { .. }));
+	assert_eq!(synthetic_duration(6), error.at_duration);
+
+	// Frames declared by a Xing/Info header count, even when they are not there
+	let options = ParseOptions {
+		max_frames: Some(15000),
+		..Default::default()
+	};
+	let error = from_read_with_options(&mut File::open("test/XingStub.mp3").unwrap(), &options).unwrap_err();
+	assert!(matches!(error.kind, ErrorKind::FrameLimitExceeded { max_frames: 15000 }));
+	assert_eq!(Duration::default(), error.at_duration);
+	let options = ParseOptions {
+		max_frames: Some(15249),
+		..Default::default()
+	};
+	assert!(from_read_with_options(&mut File::open("test/XingStub.mp3").unwrap(), &options).is_ok());
+}
+
+#[test]
+fn detect_album_art() {
+	let options = ParseOptions {
+		detect_album_art: true,
+		..Default::default()
+	};
+	let data = std::fs::read("test/ID3v2WithImage.mp3").unwrap();
+	let measured = from_read_with_metadata(&mut Cursor::new(&data), &options).unwrap();
+	assert!(measured.metadata().has_album_art);
+	assert_eq!(2938424, measured.metadata().album_art_bytes);
+	assert_eq!(from_bytes(&data).unwrap(), *measured);
+	let measured = from_read_with_metadata(&mut Cursor::new(&data), &ParseOptions::default()).unwrap();
+	assert!(!measured.metadata().has_album_art);
+	assert_eq!(0, measured.metadata().album_art_bytes);
+
+	for path in ["test/ID3v2.mp3", "test/ID3v2WithBadPadding.mp3", "test/SineEmptyID3.mp3"] {
+		let measured = from_read_with_metadata(&mut File::open(path).unwrap(), &options).unwrap();
+		assert!(!measured.metadata().has_album_art);
+		assert_eq!(from_path(path).unwrap(), *measured);
+	}
+
+	// ID3v2.2 tag with a PIC frame followed by padding
+	let mut data = b"ID3\x02\x00\x00\x00\x00\x00\x40".to_vec();
+	data.extend(b"TT2\x00\x00\x05\x00Test");
+	data.extend(b"PIC\x00\x00\x20");
+	data.extend([0x42; 0x20]);
+	data.resize(10 + 0x40, 0);
+	data.extend(synthetic_frames(3));
+	let measured = from_read_with_metadata(&mut Cursor::new(&data), &options).unwrap();
+	assert!(measured.metadata().has_album_art);
+	assert_eq!(0x20, measured.metadata().album_art_bytes);
+	assert_eq!(synthetic_duration(3), *measured);
+
+	// Truncated tag
+	let error = from_read_with_metadata(&mut Cursor::new(&data[..40]), &options).unwrap_err();
+	assert!(matches!(error.kind, ErrorKind::UnexpectedEOF));
+}
+
+#[test]
+fn validate() {
+	let report = validate_from_read(&mut File::open("test/source.mp3").unwrap()).unwrap();
+	assert!(report.is_valid());
+	assert_eq!(from_path("test/source.mp3").unwrap(), report.duration);
+	let report = validate_from_read(&mut File::open("test/ID3v2WithBadPadding.mp3").unwrap()).unwrap();
+	let padding = Diagnostic {
+		kind: DiagnosticKind::StrayPadding { bytes: 20 },
+		offset: 1034,
+	};
+	assert_eq!(vec![padding], report.diagnostics);
+	let report = validate_from_read(&mut File::open("test/XingStub.mp3").unwrap()).unwrap();
+	let mismatch = Diagnostic {
+		kind: DiagnosticKind::XingFrameCountMismatch { declared: 15249, found: 0 },
+		offset: 0,
+	};
+	assert_eq!(vec![mismatch], report.diagnostics);
+
+	// Info frame declaring 4 frames, followed by 3 frames and a frame with another bitrate and the reserved emphasis
+	let mut data = synthetic_frames(4);
+	data[36..48].copy_from_slice(b"Info\x00\x00\x00\x01\x00\x00\x00\x04");
+	let mut frame = vec![0x55; 522];
+	frame[..4].copy_from_slice(&[0xFF, 0xFB, 0xA0, 0x02]);
+	data.extend(&frame);
+	// MPEG-1 Layer II frame at 32 kbps in stereo mode
+	let mut frame = vec![0x55; 104];
+	frame[..4].copy_from_slice(&[0xFF, 0xFD, 0x10, 0x00]);
+	data.extend(&frame);
+	data.extend(synthetic_frames(1));
+	data.truncate(data.len() - 100);
+	let report = validate_from_read(&mut Cursor::new(&data)).unwrap();
+	let kinds: Vec<_> = report.diagnostics.iter().map(|diagnostic| (diagnostic.offset, &diagnostic.kind)).collect();
+	assert_eq!(
+		vec![
+			(0, &DiagnosticKind::XingFrameCountMismatch { declared: 4, found: 5 }),
+			(1668, &DiagnosticKind::ReservedEmphasis),
+			(1668, &DiagnosticKind::BitrateChange { previous: 128_000, bitrate: 160_000 }),
+			(2190, &DiagnosticKind::DisallowedCombination { bitrate: 32_000, mode: Mode::Stereo }),
+			(2190, &DiagnosticKind::BitrateChange { previous: 160_000, bitrate: 32_000 }),
+			(2294, &DiagnosticKind::Truncated),
+		],
+		kinds
+	);
+	assert_eq!(duration_from_frames(&[(1152, 44100); 5]), report.duration);
+}
@@ -0,0 +1,27 @@
+//! Helpers for tests comparing measured durations against reference values, available with the `testutil` feature.
+
+use std::time::Duration;
+
+/// Asserts that `actual` is within `tolerance_ms` milliseconds of `expected_secs` seconds.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use mp3_duration::testutil::assert_duration_approx;
+///
+/// let duration = mp3_duration::from_path(Path::new("test/source.mp3")).unwrap();
+/// assert_duration_approx(duration, 398.3, 100);
+/// ```
+#[track_caller]
+pub fn assert_duration_approx(actual: Duration, expected_secs: f64, tolerance_ms: u64) {
+	let expected = Duration::from_secs_f64(expected_secs);
+	let difference = actual.abs_diff(expected);
+	assert!(
+		difference <= Duration::from_millis(tolerance_ms),
+		"{:?} is not within {}ms of {}s",
+		actual,
+		tolerance_ms,
+		expected_secs
+	);
+}
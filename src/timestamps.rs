@@ -0,0 +1,45 @@
+use std::io::prelude::*;
+use std::time::Duration;
+
+use crate::context::Context;
+use crate::error::MP3DurationError;
+use crate::options::ParseOptions;
+use crate::parser::{Measured, Parser, Progress};
+
+/// Lists the start time of every audio frame of a mp3 file contained in any struct implementing Read.
+///
+/// Xing/Info headers are not trusted, so this always scans the entire file. Xing/Info frames do not contain audio and
+/// are not listed.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use std::fs::File;
+/// use std::io::BufReader;
+/// use mp3_duration;
+///
+/// let path = Path::new("test/source.mp3");
+/// let file = File::open(path).unwrap();
+/// let mut reader = BufReader::new(file);
+/// let timestamps = mp3_duration::frame_timestamps_from_read(&mut reader).unwrap();
+/// println!("Last frame starts at {:?}", timestamps.last());
+/// ```
+pub fn frame_timestamps_from_read<T>(reader: &mut T) -> Result<Vec<Duration>, MP3DurationError>
+where
+	T: Read,
+{
+	let options = ParseOptions {
+		trust_first_xing: false,
+		..Default::default()
+	};
+	let mut parser = Parser::new(Context::new(reader), &options);
+	let mut timestamps = Vec::new();
+
+	while let Progress::Continue = parser.measure_next_block_with(|parser, block| match block {
+		Measured::Frame(measured) if measured.is_audio => timestamps.push(parser.state.duration()),
+		_ => (),
+	})? {}
+
+	Ok(timestamps)
+}
@@ -0,0 +1,258 @@
+use std::fmt;
+use std::io::prelude::*;
+use std::time::Duration;
+
+use crate::constants::{Emphasis, Mode};
+use crate::context::Context;
+use crate::error::MP3DurationError;
+use crate::options::ParseOptions;
+use crate::parser::{Measured, Parser, Progress};
+use crate::source::ByteSource;
+use crate::warning::{Warning, WarningKind};
+
+/// Duration of a mp3 file along with every deviation from the specification found in it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ValidationReport {
+	/// Sum of the durations of all audio frames (excluding Xing/Info frames).
+	pub duration: Duration,
+	/// Deviations found, in the order of their offsets.
+	pub diagnostics: Vec<Diagnostic>,
+}
+
+impl ValidationReport {
+	/// Whether no deviation was found.
+	pub fn is_valid(&self) -> bool {
+		self.diagnostics.is_empty()
+	}
+}
+
+/// Deviation from the specification, which did not prevent measuring the duration.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Diagnostic {
+	pub kind: DiagnosticKind,
+	/// Position in the stream of the block or bytes the deviation was found in.
+	pub offset: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DiagnosticKind {
+	/// Anomaly worked around by the parser, such as a CRC mismatch or a tag size which is not synchsafe.
+	Warning(WarningKind),
+	/// A frame uses the reserved emphasis value.
+	ReservedEmphasis,
+	/// A frame uses a combination of bitrate and channel mode which the specification does not allow (see
+	/// `FrameHeader::is_allowed_combination`).
+	DisallowedCombination { bitrate: u32, mode: Mode },
+	/// The bitrate changes from one frame to the next in a stream whose Info header declares it constant.
+	BitrateChange { previous: u32, bitrate: u32 },
+	/// The number of audio frames declared by the Xing/Info header differs from the number of frames found.
+	XingFrameCountMismatch { declared: u64, found: u64 },
+	/// Run of 0x00 bytes between blocks (or around them), such as padding written after an ID3v2 tag instead of
+	/// within it.
+	StrayPadding { bytes: u64 },
+	/// Data which is neither a frame nor a tag follows a tag, which suggests its declared size is too small. `bytes`
+	/// were skipped to find the next frame or tag, including any 0x00 bytes.
+	TagSizeMismatch { bytes: u64 },
+	/// Data which is neither a frame nor a tag was skipped to find the next one, see `TagSizeMismatch`.
+	UnparsableData { bytes: u64 },
+	/// The stream ends in the middle of a frame or tag.
+	Truncated,
+}
+
+impl fmt::Display for DiagnosticKind {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			DiagnosticKind::Warning(kind) => write!(f, "{}", kind),
+			DiagnosticKind::ReservedEmphasis => write!(f, "Reserved emphasis value"),
+			DiagnosticKind::DisallowedCombination { bitrate, mode } => {
+				write!(f, "Bitrate of {} kbps not allowed in {:?} mode", bitrate / 1000, mode)
+			}
+			DiagnosticKind::BitrateChange { previous, bitrate } => write!(
+				f,
+				"Bitrate changes from {} to {} kbps in a constant bitrate stream",
+				previous / 1000,
+				bitrate / 1000
+			),
+			DiagnosticKind::XingFrameCountMismatch { declared, found } => {
+				write!(f, "Xing/Info header declares {} frames but {} were found", declared, found)
+			}
+			DiagnosticKind::StrayPadding { bytes } => write!(f, "{} bytes of padding between blocks", bytes),
+			DiagnosticKind::TagSizeMismatch { bytes } => write!(f, "{} bytes of unknown data after a tag", bytes),
+			DiagnosticKind::UnparsableData { bytes } => write!(f, "{} bytes of unknown data", bytes),
+			DiagnosticKind::Truncated => write!(f, "Stream ends within a frame or tag"),
+		}
+	}
+}
+
+impl fmt::Display for Diagnostic {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{} at offset {} (0x{:X})", self.kind, self.offset, self.offset)
+	}
+}
+
+/// Scans an entire mp3 file contained in any struct implementing Read, and reports its duration along with every
+/// deviation from the specification found in it, to check the output of encoders.
+///
+/// Parsing is lenient and never stops at the first deviation: invalid data is skipped over, frames with the reserved
+/// emphasis value or a disallowed combination of bitrate and channel mode are measured, and the CRC of Layer III
+/// frames is checked (see `ParseOptions::validate_crc`). Xing/Info headers are not trusted, every frame is measured
+/// and their declared frame count is checked. Only IO errors end parsing with an error.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs::File;
+/// use std::io::BufReader;
+/// use mp3_duration;
+///
+/// let file = File::open("test/source.mp3").unwrap();
+/// let report = mp3_duration::validate_from_read(&mut BufReader::new(file)).unwrap();
+/// for diagnostic in &report.diagnostics {
+///     println!("{}", diagnostic);
+/// }
+/// ```
+pub fn validate_from_read<T>(reader: &mut T) -> Result<ValidationReport, MP3DurationError>
+where
+	T: Read,
+{
+	let options = ParseOptions {
+		lenient: true,
+		validate_crc: true,
+		trust_first_xing: false,
+		..Default::default()
+	};
+	let mut parser = Parser::new(Context::new(reader), &options);
+	// Disallowed combinations are reported rather than skipped
+	parser.validator = Some(&|_| true);
+	let mut diagnostics = Vec::new();
+
+	// First Xing/Info frame, its declared frame count and whether it declares a constant bitrate
+	let mut xing = None;
+	let mut frames = 0;
+	let mut previous_bitrate = None;
+	// End of the last frame or tag (and whether it was a tag), and number of bytes skipped until then
+	let mut block_end = 0;
+	let mut after_tag = false;
+	let mut resynced_bytes = 0;
+
+	loop {
+		let progress = parser.measure_next_block_with(|parser, block| {
+			let start = match &block {
+				Measured::Frame(measured) => measured.frame.offset,
+				Measured::Tag(offset) => *offset,
+			};
+			// A text preamble is reported as a warning rather than as a gap
+			if block_end == 0 {
+				block_end = text_preamble_size(&parser.state.warnings);
+			}
+			let resynced = parser.state.resynced_bytes > resynced_bytes;
+			if let Some(kind) = gap_diagnostic(start - block_end, resynced, after_tag) {
+				diagnostics.push(Diagnostic { kind, offset: block_end });
+			}
+			resynced_bytes = parser.state.resynced_bytes;
+			block_end = parser.source.bytes_read();
+
+			let measured = match block {
+				Measured::Frame(measured) => measured,
+				Measured::Tag(_) => {
+					after_tag = true;
+					return;
+				}
+			};
+			after_tag = false;
+			let frame = measured.frame;
+			let header = &frame.header;
+			let mut diagnose = |kind| diagnostics.push(Diagnostic { kind, offset: frame.offset });
+			if header.emphasis == Emphasis::Reserved {
+				diagnose(DiagnosticKind::ReservedEmphasis);
+			}
+			if !header.is_allowed_combination() {
+				diagnose(DiagnosticKind::DisallowedCombination { bitrate: header.bitrate, mode: header.mode });
+			}
+			if !measured.complete {
+				diagnose(DiagnosticKind::Truncated);
+				return;
+			}
+			match &frame.xing {
+				Some(info) if xing.is_none() => xing = Some((frame.offset, info.frames, !info.is_vbr)),
+				Some(_) => (),
+				None => {
+					let is_cbr = matches!(xing, Some((_, _, true)));
+					match previous_bitrate {
+						Some(previous) if is_cbr && previous != header.bitrate => {
+							diagnose(DiagnosticKind::BitrateChange { previous, bitrate: header.bitrate });
+						}
+						_ => (),
+					}
+					previous_bitrate = Some(header.bitrate);
+					frames += 1;
+				}
+			}
+		});
+		match progress {
+			Ok(Progress::Continue) => (),
+			Ok(Progress::Finished(_)) => {
+				if block_end == 0 {
+					block_end = text_preamble_size(&parser.state.warnings);
+				}
+				let bytes = parser.source.bytes_read() - block_end;
+				let resynced = parser.state.resynced_bytes > resynced_bytes;
+				if let Some(kind) = gap_diagnostic(bytes, resynced, after_tag) {
+					diagnostics.push(Diagnostic { kind, offset: block_end });
+				}
+				break;
+			}
+			Err(_) if parser.source.reached_eof() => {
+				diagnostics.push(Diagnostic {
+					kind: DiagnosticKind::Truncated,
+					offset: block_end,
+				});
+				break;
+			}
+			Err(e) => return Err(e),
+		}
+	}
+
+	if let Some((offset, Some(declared), _)) = xing {
+		if declared as u64 != frames {
+			let kind = DiagnosticKind::XingFrameCountMismatch { declared: declared as u64, found: frames };
+			diagnostics.push(Diagnostic { kind, offset });
+		}
+	}
+	diagnostics.extend(parser.state.warnings.into_iter().map(|warning| Diagnostic {
+		kind: DiagnosticKind::Warning(warning.kind),
+		offset: warning.offset,
+	}));
+	diagnostics.sort_by_key(|diagnostic| diagnostic.offset);
+	Ok(ValidationReport {
+		duration: parser.state.measured.duration(),
+		diagnostics,
+	})
+}
+
+// Diagnostic for `bytes` skipped between blocks, which are only 0x00 bytes unless the parser resynced
+fn gap_diagnostic(bytes: u64, resynced: bool, after_tag: bool) -> Option<DiagnosticKind> {
+	if resynced && after_tag {
+		Some(DiagnosticKind::TagSizeMismatch { bytes })
+	} else if resynced {
+		Some(DiagnosticKind::UnparsableData { bytes })
+	} else if bytes > 0 {
+		Some(DiagnosticKind::StrayPadding { bytes })
+	} else {
+		None
+	}
+}
+
+// Number of bytes skipped before the first frame or tag because they looked like text
+fn text_preamble_size(warnings: &[Warning]) -> u64 {
+	warnings
+		.iter()
+		.find_map(|warning| match warning.kind {
+			WarningKind::TextPreamble { size } => Some(size),
+			_ => None,
+		})
+		.unwrap_or_default()
+}
@@ -0,0 +1,63 @@
+// VBRI header, written by Fraunhofer encoders in the first frame of VBR files in place of a Xing header.
+
+/// Content of a VBRI header, found in the first frame of VBR files encoded by Fraunhofer encoders.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VbriInfo {
+	/// Version of the header format.
+	pub version: u16,
+	/// Encoder delay, in samples.
+	pub delay: u16,
+	/// Quality indicator.
+	pub quality: u16,
+	/// Size of the stream in bytes.
+	pub bytes: u32,
+	/// Number of frames in the stream.
+	pub frames: u32,
+	/// Size in bytes of consecutive segments of the stream, starting right after the frame holding the header, for
+	/// seeking. Each segment is made of `frames_per_entry` frames. Empty when the table of contents does not fit in
+	/// the frame.
+	pub toc: Vec<u32>,
+	/// Number of frames covered by each entry of `toc`.
+	pub frames_per_entry: u16,
+}
+
+impl VbriInfo {
+	// Position of the header, relative to the start of the frame
+	pub const OFFSET: usize = 36;
+	// Size of the header, excluding its table of contents
+	pub const SIZE: usize = 26;
+
+	// Parses the header, leaving its table of contents empty, and returns it along with the size of the table of
+	// contents (see `parse_toc`). Returns `None` if `bytes` do not start with a VBRI header.
+	pub fn parse(bytes: &[u8; Self::SIZE]) -> Option<(Self, usize)> {
+		if &bytes[0..4] != b"VBRI" {
+			return None;
+		}
+		let u16_at = |i: usize| u16::from_be_bytes([bytes[i], bytes[i + 1]]);
+		let u32_at = |i: usize| u32::from_be_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+		let entries = u16_at(18) as usize;
+		let entry_size = u16_at(22) as usize;
+		// Entries are integers of 1 to 4 bytes
+		let toc_size = if (1..=4).contains(&entry_size) { entries * entry_size } else { 0 };
+		let vbri = VbriInfo {
+			version: u16_at(4),
+			delay: u16_at(6),
+			quality: u16_at(8),
+			bytes: u32_at(10),
+			frames: u32_at(14),
+			toc: Vec::new(),
+			frames_per_entry: u16_at(24),
+		};
+		Some((vbri, toc_size))
+	}
+
+	// Fills the table of contents from its raw bytes, as sized by `parse`
+	pub fn parse_toc(&mut self, header: &[u8; Self::SIZE], toc: &[u8]) {
+		let scale = u16::from_be_bytes([header[20], header[21]]) as u32;
+		let entry_size = u16::from_be_bytes([header[22], header[23]]) as usize;
+		self.toc = toc
+			.chunks_exact(entry_size)
+			.map(|entry| entry.iter().fold(0u32, |value, byte| (value << 8) | *byte as u32).saturating_mul(scale))
+			.collect();
+	}
+}
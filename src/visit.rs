@@ -0,0 +1,126 @@
+use std::io::prelude::*;
+use std::time::Duration;
+
+#[cfg(feature = "md5")]
+use md5::{Digest, Md5};
+
+use crate::context::Context;
+use crate::error::MP3DurationError;
+use crate::header::FrameHeader;
+use crate::options::ParseOptions;
+use crate::parser::{Measured, Parser, Progress, XingInfo};
+
+/// Frame passed to the visitor of `visit_frames_from_read`.
+#[derive(Clone, Copy, Debug)]
+pub struct FrameView<'a> {
+	/// Position of the first byte of the frame in the stream.
+	pub offset: u64,
+	pub header: &'a FrameHeader,
+	/// Content of the Xing/Info header of the frame, if any. Such frames do not contain audio.
+	pub xing: Option<&'a XingInfo>,
+	/// Number of bytes of audio data of the frame stored at the end of previous frames (the bit reservoir of Layer III
+	/// frames). Frames can only be cut apart cleanly before a frame where it is 0.
+	pub main_data_begin: u16,
+	/// All the bytes of the frame, header included. Any ancillary data stored after the encoded audio is at the end.
+	pub bytes: &'a [u8],
+}
+
+/// Measures the duration of a mp3 file contained in any struct implementing Read, calling `visitor` with the content
+/// of every frame.
+///
+/// Frames are measured like with `from_read_with_options`: Xing/Info frames are visited but do not count towards the
+/// duration (unless `ParseOptions::count_info_frame` is set), and reading stops after the first one declaring the
+/// duration of the stream when `ParseOptions::trust_first_xing` is set. Unset it to visit every frame until the end of
+/// the stream. In lenient mode, a last frame cut short by the end of the stream is not visited.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use std::fs::File;
+/// use std::io::BufReader;
+/// use mp3_duration::{self, ParseOptions};
+///
+/// let path = Path::new("test/source.mp3");
+/// let file = File::open(path).unwrap();
+/// let mut reader = BufReader::new(file);
+/// let options = ParseOptions {
+///     trust_first_xing: false,
+///     ..Default::default()
+/// };
+/// let mut largest_frame = 0;
+/// let duration = mp3_duration::visit_frames_from_read(&mut reader, &options, |frame| {
+///     largest_frame = largest_frame.max(frame.bytes.len());
+/// })
+/// .unwrap();
+/// println!("File duration: {:?}, largest frame: {} bytes", duration, largest_frame);
+/// ```
+pub fn visit_frames_from_read<T, F>(
+	reader: &mut T,
+	options: &ParseOptions,
+	mut visitor: F,
+) -> Result<Duration, MP3DurationError>
+where
+	T: Read,
+	F: FnMut(FrameView),
+{
+	let mut parser = Parser::new(Context::new(reader), options);
+	parser.keep_frame_bytes = true;
+
+	loop {
+		let progress = parser.measure_next_block_with(|_, block| match block {
+			Measured::Frame(measured) if measured.complete => visitor(FrameView {
+				offset: measured.frame.offset,
+				header: &measured.frame.header,
+				xing: measured.frame.xing.as_ref(),
+				main_data_begin: measured.frame.main_data_begin,
+				bytes: measured.bytes,
+			}),
+			_ => (),
+		})?;
+		if let Progress::Finished(duration) = progress {
+			return Ok(duration);
+		}
+	}
+}
+
+/// Measures the duration of a mp3 file contained in any struct implementing Read, along with the MD5 digest of its
+/// audio frames.
+///
+/// Only the bytes of audio frames are hashed, so the digest identifies the audio content of a file regardless of its
+/// tags: files which only differ by their tags have the same digest. Xing/Info frames are not hashed either, as some
+/// tools edit them (e.g. to store ReplayGain values). Xing/Info headers are not trusted: every frame is read until the
+/// end of the stream, whatever `ParseOptions::trust_first_xing`.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use std::fs::File;
+/// use std::io::BufReader;
+/// use mp3_duration::{self, ParseOptions};
+///
+/// let path = Path::new("test/source.mp3");
+/// let file = File::open(path).unwrap();
+/// let mut reader = BufReader::new(file);
+/// let (duration, digest) = mp3_duration::audio_md5_from_read(&mut reader, &ParseOptions::default()).unwrap();
+/// let digest: String = digest.iter().map(|byte| format!("{:02x}", byte)).collect();
+/// println!("File duration: {:?}, audio digest: {}", duration, digest);
+/// ```
+#[cfg(feature = "md5")]
+pub fn audio_md5_from_read<T>(reader: &mut T, options: &ParseOptions) -> Result<(Duration, [u8; 16]), MP3DurationError>
+where
+	T: Read,
+{
+	let options = ParseOptions {
+		trust_first_xing: false,
+		..options.clone()
+	};
+	let mut md5 = Md5::new();
+	let duration = visit_frames_from_read(reader, &options, |frame| {
+		if frame.xing.is_none() {
+			md5.update(frame.bytes);
+		}
+	})?;
+	Ok((duration, md5.finalize().into()))
+}
@@ -0,0 +1,51 @@
+use std::fmt;
+use std::time::Duration;
+
+/// Anomaly which was worked around while parsing, and did not prevent measuring the duration.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Warning {
+	pub kind: WarningKind,
+	/// Position in the stream of the block the anomaly was found in.
+	pub offset: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum WarningKind {
+	/// The size of an ID3v2 tag was not a valid synchsafe integer and was decoded as a plain 32-bit integer instead.
+	NonSynchsafeTagSize { size: u32 },
+	/// The CRC of a frame did not match its content (see `ParseOptions::validate_crc`). The frame was measured anyway.
+	CrcMismatch,
+	/// The stream started with a UTF-8 byte order mark or ASCII whitespace, as prepended by tools treating it as text.
+	/// These `size` bytes were skipped.
+	TextPreamble { size: u64 },
+	/// The frame holding a Xing/Info header used another sampling rate than the audio frame following it, so the
+	/// `declared` duration (counted at `xing_rate` Hz) was ignored and the frames were measured instead.
+	XingSamplingRateMismatch { declared: Duration, xing_rate: u32, audio_rate: u32 },
+}
+
+impl fmt::Display for WarningKind {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			WarningKind::NonSynchsafeTagSize { size } => {
+				write!(f, "ID3v2 tag size is not synchsafe, decoded as {} bytes", size)
+			}
+			WarningKind::CrcMismatch => write!(f, "Frame CRC does not match its content"),
+			WarningKind::TextPreamble { size } => {
+				write!(f, "Skipped {} bytes of byte order mark or whitespace before the stream", size)
+			}
+			WarningKind::XingSamplingRateMismatch { declared, xing_rate, audio_rate } => write!(
+				f,
+				"Xing/Info header declares {:?} at {} Hz but audio frames are at {} Hz, measured frames instead",
+				declared, xing_rate, audio_rate
+			),
+		}
+	}
+}
+
+impl fmt::Display for Warning {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{} at offset {} (0x{:X})", self.kind, self.offset, self.offset)
+	}
+}
@@ -0,0 +1,70 @@
+use std::io;
+use std::io::prelude::*;
+use std::path::Path;
+use std::time::Duration;
+
+use zip::ZipArchive;
+
+use crate::error::MP3DurationError;
+
+fn is_mp3(name: &str) -> bool {
+	Path::new(name).extension().is_some_and(|extension| extension.eq_ignore_ascii_case("mp3"))
+}
+
+/// Measures the duration of every `.mp3` file of a zip archive contained in any struct implementing Read and Seek,
+/// without extracting it.
+///
+/// Returns the name of each file within the archive along with its duration, in the order they appear in the archive's
+/// central directory. Files are measured as the iterator is consumed. An entry which cannot be opened (e.g. because
+/// it uses an unsupported compression method) is reported as an error, and the iteration continues with the next
+/// entry. If the archive itself cannot be read, a single error is returned with an empty name.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::path::Path;
+/// use std::fs::File;
+/// use std::io::BufReader;
+/// use mp3_duration;
+///
+/// let path = Path::new("music.zip");
+/// let file = File::open(path).unwrap();
+/// for (name, duration) in mp3_duration::from_zip(BufReader::new(file)) {
+///     println!("{}: {:?}", name, duration);
+/// }
+/// ```
+pub fn from_zip<R>(reader: R) -> impl Iterator<Item = (String, Result<Duration, MP3DurationError>)>
+where
+	R: Read + Seek,
+{
+	let (mut archive, mut error) = match ZipArchive::new(reader) {
+		Ok(archive) => (Some(archive), None),
+		Err(e) => (None, Some(MP3DurationError::at_start(io::Error::from(e).into()))),
+	};
+	let mut index = 0;
+
+	std::iter::from_fn(move || {
+		if let Some(error) = error.take() {
+			return Some((String::new(), Err(error)));
+		}
+		let archive = archive.as_mut()?;
+		while index < archive.len() {
+			let name = match archive.name_for_index(index) {
+				Some(name) if is_mp3(name) => name.to_owned(),
+				_ => {
+					index += 1;
+					continue;
+				}
+			};
+			let file = archive.by_index(index);
+			index += 1;
+			let mut file = match file {
+				Ok(file) if file.is_file() => file,
+				Ok(_) => continue,
+				Err(e) => return Some((name, Err(MP3DurationError::at_start(io::Error::from(e).into())))),
+			};
+			return Some((name, crate::from_read(&mut file)));
+		}
+		None
+	})
+}